@@ -0,0 +1,136 @@
+use common::backtest::{MarketRecord, load_markets, market_file, run_cycle, summarize};
+use common::gamma::GammaClient;
+use common::market::MarketFamily;
+use common::paper::{BookSource, LiveBookSource};
+use common::{Asset, BotConfig, MarketResponse, StrategySettings, config_path, unix_now};
+use polymarket_client_sdk::clob::Client;
+use reqwest::Client as http_client;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const USAGE: &str = "usage:
+  backtest run <asset> <data_dir>       replay recorded markets offline
+  backtest record <asset> <data_dir>    record books of live markets into data_dir
+both use the market family configured for the asset";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [command, asset, dir] = args.as_slice() else {
+        anyhow::bail!(USAGE);
+    };
+    let asset: Asset = asset.parse().map_err(anyhow::Error::msg)?;
+    let dir = PathBuf::from(dir);
+    let config = BotConfig::load(&config_path())?;
+    config.register_assets();
+    let Some(settings) = config.settings(&asset) else {
+        anyhow::bail!("no [assets.{asset}] section in {}", config_path());
+    };
+
+    match command.as_str() {
+        "run" => backtest(asset, settings, dir).await,
+        "record" => record(asset, settings.market, dir).await,
+        _ => anyhow::bail!(USAGE),
+    }
+}
+
+async fn backtest(asset: Asset, settings: StrategySettings, dir: PathBuf) -> anyhow::Result<()> {
+    let markets = load_markets(&dir, &asset, &settings.market)?;
+    println!("Replaying {} {asset} markets with {:?}", markets.len(), settings);
+
+    let mut reports = Vec::with_capacity(markets.len());
+    for market in &markets {
+        let report = run_cycle(market, &settings).await;
+        println!(
+            "{:<32} {:<12} entry {:>8} hedge {:>8} pnl {:>10}",
+            report.slug,
+            format!("{:?}", report.outcome),
+            report.entry_size,
+            report.hedge_size,
+            report.pnl.round_dp(4)
+        );
+        reports.push(report);
+    }
+
+    let summary = summarize(&reports);
+    println!("---");
+    println!(
+        "cycles: {}, traded: {}, skipped: {}, no fill: {}",
+        summary.cycles, summary.traded, summary.skipped, summary.no_fill
+    );
+    println!(
+        "wins: {}, stop-losses: {}, close failed: {}, unhedged: {}, win rate: {}%",
+        summary.wins,
+        summary.losses,
+        summary.close_failed,
+        summary.unhedged,
+        (summary.win_rate * Decimal::ONE_HUNDRED).round_dp(2)
+    );
+    println!(
+        "pnl: {}, max drawdown: {}",
+        summary.pnl.round_dp(4),
+        summary.max_drawdown.round_dp(4)
+    );
+    println!(
+        "entry legs placed: {}, filled: {} ({}%), partial fills: {}",
+        summary.legs_placed,
+        summary.legs_filled,
+        (summary.fill_rate * Decimal::ONE_HUNDRED).round_dp(2),
+        summary.partial_fills
+    );
+    Ok(())
+}
+
+/// polls both books of the live and the two upcoming markets every second
+async fn record(asset: Asset, family: MarketFamily, dir: PathBuf) -> anyhow::Result<()> {
+    fs::create_dir_all(&dir)?;
+    let gamma = GammaClient::new(http_client::new());
+    let books = LiveBookSource::new(Client::default());
+    let mut tokens: HashMap<i64, MarketResponse> = HashMap::new();
+
+    loop {
+        let now = unix_now();
+        let current = family.current(now);
+        let next = family.end(current);
+        let timestamps = [current, next, family.end(next)];
+        tokens.retain(|t, _| timestamps.contains(t));
+
+        for timestamp in timestamps {
+            let market = match tokens.entry(timestamp) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match gamma.market(&family, timestamp, asset).await {
+                    Ok(market) => entry.insert(market.tokens),
+                    Err(e) => {
+                        println!("{asset} {timestamp}: market not available yet: {e}");
+                        continue;
+                    }
+                },
+            };
+
+            let (up, down) = match (
+                books.book(&market.first_asset_id).await,
+                books.book(&market.second_asset_id).await,
+            ) {
+                (Ok(up), Ok(down)) => (up, down),
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("{asset} {timestamp}: failed to fetch books: {e}");
+                    continue;
+                }
+            };
+
+            let path = dir.join(market_file(&asset, &family, timestamp));
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            let line = serde_json::to_string(&MarketRecord { ts: now, up, down })?;
+            writeln!(file, "{line}")?;
+        }
+
+        sleep(Duration::from_secs(1)).await;
+    }
+}
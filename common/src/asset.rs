@@ -0,0 +1,137 @@
+//! Tradable assets. An asset is the key of its `[assets.<key>]` section, how it is spelled
+//! in slugs and labelled in metrics comes from the registry the config fills on startup.
+//! btc, eth, sol and xrp are known without it.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// spelled out names of the assets Polymarket lists up/down markets for
+const KNOWN: &[(&str, &str)] = &[
+    ("btc", "bitcoin"),
+    ("eth", "ethereum"),
+    ("sol", "solana"),
+    ("xrp", "xrp"),
+];
+
+/// every asset seen so far, keys and infos live as long as the process
+static REGISTRY: RwLock<BTreeMap<&'static str, &'static AssetInfo>> =
+    RwLock::new(BTreeMap::new());
+
+/// how an asset shows up in slugs and metrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+    /// `{asset}` in slugs, btc
+    pub slug_prefix: String,
+    /// `{name}` in slugs, bitcoin
+    pub name: String,
+    /// `asset` label of the metrics
+    pub label: String,
+}
+
+impl AssetInfo {
+    /// the key everywhere, known assets keep their spelled out name
+    pub fn default_for(key: &str) -> Self {
+        let name = KNOWN.iter().find(|(k, _)| *k == key).map_or(key, |(_, name)| *name);
+        AssetInfo {
+            slug_prefix: key.to_string(),
+            name: name.to_string(),
+            label: key.to_string(),
+        }
+    }
+}
+
+/// a lowercase key like `btc`, cheap to copy and compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Asset(&'static str);
+
+impl Asset {
+    pub const BTC: Asset = Asset("btc");
+    pub const ETH: Asset = Asset("eth");
+    pub const SOL: Asset = Asset("sol");
+    pub const XRP: Asset = Asset("xrp");
+
+    pub fn key(&self) -> &'static str {
+        self.0
+    }
+
+    /// registered info, the defaults of the key when nothing was registered for it
+    pub fn info(&self) -> &'static AssetInfo {
+        if let Some(info) = REGISTRY.read().expect("asset registry poisoned").get(self.0) {
+            return info;
+        }
+        let mut registry = REGISTRY.write().expect("asset registry poisoned");
+        registry
+            .entry(self.0)
+            .or_insert_with(|| Box::leak(Box::new(AssetInfo::default_for(self.0))))
+    }
+
+    /// how the asset starts its slugs
+    pub fn slug_prefix(&self) -> &'static str {
+        &self.info().slug_prefix
+    }
+
+    /// how Polymarket spells the asset out in slugs
+    pub fn name(&self) -> &'static str {
+        &self.info().name
+    }
+
+    /// the `asset` label of its metrics
+    pub fn label(&self) -> &'static str {
+        &self.info().label
+    }
+}
+
+/// Makes `info` the one of `asset`. Assets are registered once on startup, earlier
+/// lookups keep what they got.
+pub fn register(asset: Asset, info: AssetInfo) {
+    let mut registry = REGISTRY.write().expect("asset registry poisoned");
+    registry.insert(asset.0, Box::leak(Box::new(info)));
+}
+
+impl Display for Asset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Asset {
+    type Err = String;
+
+    /// any key of lowercase letters, digits, `-` and `_`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = s.trim().to_lowercase();
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+        if key.is_empty() || !key.chars().all(valid) {
+            return Err(format!(
+                "invalid asset: {s}, expected lowercase letters, digits, - and _"
+            ));
+        }
+        if let Some((key, _)) = REGISTRY
+            .read()
+            .expect("asset registry poisoned")
+            .get_key_value(key.as_str())
+        {
+            return Ok(Asset(key));
+        }
+        let asset = Asset(Box::leak(key.into_boxed_str()));
+        asset.info();
+        Ok(asset)
+    }
+}
+
+impl Serialize for Asset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+/// the key, stored hedges from before the registry spell it in capitals
+impl<'de> Deserialize<'de> for Asset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        key.parse().map_err(serde::de::Error::custom)
+    }
+}
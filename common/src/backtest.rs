@@ -0,0 +1,475 @@
+//! Offline replay of recorded markets through the straddle-then-hedge strategy.
+//!
+//! Data lives in a directory with one file per market, named after the market family:
+//! `{asset}-updown-{family}-{timestamp}.jsonl`, e.g. `btc-updown-15m-1765000000.jsonl`.
+//! Every line is a [`MarketRecord`], a snapshot of both books at `ts`. Orders are matched by
+//! [`PaperExchange`] against the snapshot that is current at each step, so fills and partial
+//! fills behave exactly like in paper mode.
+
+use crate::clock::{Clock, ManualClock};
+use crate::cycle::{CycleAction, CycleEvent, CycleMachine, CycleState, Market};
+use crate::asset::Asset;
+use crate::dto::{BookSnapshot, MarketResponse, OrderState};
+use crate::exchange::Exchange;
+use crate::market::MarketFamily;
+use crate::outcome::CycleOutcome;
+use crate::paper::{BookSource, PaperExchange};
+use crate::pricing::entry_prices;
+use crate::settings::StrategySettings;
+use async_trait::async_trait;
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::error::Error;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::Zero;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+const UP: &str = "up";
+const DOWN: &str = "down";
+/// big enough that the bankroll never limits a replayed cycle
+const BANKROLL: i64 = 1_000_000;
+/// tick size the recorded markets are priced in
+const TICK: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+/// most polls per snapshot, a cancel is followed up on the same snapshot like it is live
+const STEPS_PER_SNAPSHOT: usize = 6;
+
+/// one line of a market file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketRecord {
+    pub ts: i64,
+    pub up: BookSnapshot,
+    pub down: BookSnapshot,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketData {
+    pub asset: Asset,
+    pub slug: String,
+    pub timestamp: i64,
+    pub records: Vec<MarketRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacktestOutcome {
+    /// hedge matched, both sides held to resolution
+    Hedged,
+    /// hedge didn't match in time, position closed by market
+    StopLoss,
+    /// stop-loss fired but nothing could be sold before the market ended
+    CloseFailed,
+    /// market ended with an unhedged position that was never stopped out
+    Unhedged,
+    /// neither entry leg filled
+    NoFill,
+    /// no snapshot before the trade cutoff, the bot wouldn't have entered
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct CycleReport {
+    pub slug: String,
+    pub timestamp: i64,
+    pub outcome: BacktestOutcome,
+    pub entry_size: Decimal,
+    pub hedge_size: Decimal,
+    pub pnl: Decimal,
+    pub legs_placed: u32,
+    pub legs_filled: u32,
+    pub partial_fills: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BacktestSummary {
+    pub cycles: usize,
+    pub traded: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub close_failed: usize,
+    pub unhedged: usize,
+    pub no_fill: usize,
+    pub skipped: usize,
+    pub win_rate: Decimal,
+    pub pnl: Decimal,
+    pub max_drawdown: Decimal,
+    pub legs_placed: u32,
+    pub legs_filled: u32,
+    pub partial_fills: u32,
+    pub fill_rate: Decimal,
+}
+
+/// serves the latest snapshot at or before the replayed time
+struct ReplayBookSource {
+    records: Vec<MarketRecord>,
+    clock: Arc<ManualClock>,
+}
+
+#[async_trait]
+impl BookSource for ReplayBookSource {
+    async fn book(&self, token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        let now = self.clock.now();
+        let record = self
+            .records
+            .iter()
+            .take_while(|r| r.ts <= now)
+            .last()
+            .ok_or_else(|| Error::validation(format!("no snapshot before {now}")))?;
+
+        match token_id {
+            UP => Ok(record.up.clone()),
+            DOWN => Ok(record.down.clone()),
+            other => Err(Error::validation(format!("unknown token {other}"))),
+        }
+    }
+}
+
+fn is_partial(order: &OrderState) -> bool {
+    order.size_matched > Decimal::zero() && order.size_matched < order.original_size
+}
+
+/// file a recorded market of the family is kept in
+pub fn market_file(asset: &Asset, family: &MarketFamily, timestamp: i64) -> String {
+    format!("{asset}-updown-{}-{timestamp}.jsonl", family.name)
+}
+
+/// reads every recorded market of the family in `dir`, oldest market first
+pub fn load_markets(
+    dir: &Path,
+    asset: &Asset,
+    family: &MarketFamily,
+) -> anyhow::Result<Vec<MarketData>> {
+    let prefix = format!("{asset}-updown-{}-", family.name);
+    let mut markets = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(slug) = name.strip_suffix(".jsonl") else {
+            continue;
+        };
+        let Some(timestamp) = slug.strip_prefix(&prefix).and_then(|t| t.parse::<i64>().ok())
+        else {
+            continue;
+        };
+
+        let mut records = vec![];
+        for (n, line) in BufReader::new(fs::File::open(&path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: MarketRecord = serde_json::from_str(&line)
+                .map_err(|e| anyhow::anyhow!("{}:{}: {e}", path.display(), n + 1))?;
+            records.push(record);
+        }
+        records.sort_by_key(|r| r.ts);
+
+        markets.push(MarketData {
+            asset: *asset,
+            slug: slug.to_string(),
+            timestamp,
+            records,
+        });
+    }
+
+    markets.sort_by_key(|m| m.timestamp);
+    Ok(markets)
+}
+
+/// Replays one market through the same [`CycleMachine`] the live loop drives. Entries are
+/// `order_size` whatever the sizing, a replay has no bankroll to size off.
+pub async fn run_cycle(market: &MarketData, settings: &StrategySettings) -> CycleReport {
+    let mut report = CycleReport {
+        slug: market.slug.clone(),
+        timestamp: market.timestamp,
+        outcome: BacktestOutcome::Skipped,
+        entry_size: Decimal::zero(),
+        hedge_size: Decimal::zero(),
+        pnl: Decimal::zero(),
+        legs_placed: 0,
+        legs_filled: 0,
+        partial_fills: 0,
+    };
+
+    let timestamp = market.timestamp;
+    let entry_cutoff = timestamp - settings.dont_allow_trade_before;
+    let Some(first) = market.records.first().filter(|r| r.ts <= entry_cutoff) else {
+        return report;
+    };
+
+    let clock = Arc::new(ManualClock::new(first.ts));
+    let bankroll = Decimal::from(BANKROLL);
+    let exchange = PaperExchange::new(
+        Box::new(ReplayBookSource {
+            records: market.records.clone(),
+            clock: clock.clone(),
+        }),
+        bankroll,
+    )
+    .with_logging(false);
+
+    let mut replay = Replay {
+        exchange: &exchange,
+        entries: vec![],
+        hedge_order: None,
+        entry_size: None,
+        hedge_size: Decimal::zero(),
+        unfilled_closes: vec![],
+    };
+    let mut machine = CycleMachine::new(market.asset, settings.clone()).with_logging(false);
+    let tokens = MarketResponse {
+        first_asset_id: UP.to_string(),
+        second_asset_id: DOWN.to_string(),
+    };
+    let quote = entry_prices(
+        &settings.entry,
+        settings.limit_enter_price,
+        TICK,
+        [Some(&first.up), Some(&first.down)],
+    );
+    let prices = match quote {
+        Ok(quote) => quote.prices,
+        Err(e) => {
+            println!("{}: not entered: {e}", market.slug);
+            return report;
+        }
+    };
+    let opened = CycleEvent::MarketOpened(Market { timestamp, tokens, prices });
+    if let Err(e) = replay.feed(&mut machine, opened, clock.now()).await {
+        println!("{}: failed to open positions: {e}", market.slug);
+        return report;
+    }
+    if replay.entries.is_empty() {
+        return report;
+    }
+    report.legs_placed = 2;
+
+    let mut failed = false;
+    let market_end = settings.market.end(timestamp);
+    for record in market.records.iter().skip(1).take_while(|r| r.ts < market_end) {
+        clock.set(record.ts);
+        if let Err(e) = replay.snapshot(&mut machine, clock.now()).await {
+            println!("{}: replay error at {}: {e}", market.slug, record.ts);
+            failed = true;
+            break;
+        }
+        if machine.result().is_some() {
+            break;
+        }
+    }
+
+    report.outcome = match machine.state() {
+        _ if failed => BacktestOutcome::Unhedged,
+        CycleState::Done(CycleOutcome::Hedged(_)) => BacktestOutcome::Hedged,
+        CycleState::Done(CycleOutcome::StopLoss(_)) => BacktestOutcome::StopLoss,
+        CycleState::Done(_) if replay.entry_size.is_some() => BacktestOutcome::CloseFailed,
+        CycleState::Done(_) | CycleState::AwaitingMarket { .. } | CycleState::EntryResting { .. } => {
+            BacktestOutcome::NoFill
+        }
+        CycleState::LegMatched { .. } | CycleState::Hedging { .. } => BacktestOutcome::Unhedged,
+        CycleState::StopLoss { .. } | CycleState::Flattening { .. } => BacktestOutcome::CloseFailed,
+    };
+    if let Some(entry_size) = replay.entry_size {
+        report.legs_filled = 1;
+        report.entry_size = entry_size;
+        report.hedge_size = replay.hedge_size;
+    }
+    for order_id in replay.entries.iter().chain(&replay.hedge_order) {
+        if let Ok(order) = exchange.get_order(order_id).await
+            && is_partial(&order)
+        {
+            report.partial_fills += 1;
+        }
+    }
+    report.pnl = mark_to_market(&exchange, market, market_end, bankroll);
+    report
+}
+
+/// carries out the machine's actions on the paper exchange, the snapshots are its clock
+struct Replay<'a> {
+    exchange: &'a PaperExchange,
+    entries: Vec<String>,
+    hedge_order: Option<String>,
+    /// the held leg, set once one matched
+    entry_size: Option<Decimal>,
+    hedge_size: Decimal,
+    /// market sells the book couldn't fill yet
+    unfilled_closes: Vec<(String, Decimal)>,
+}
+
+impl Replay<'_> {
+    /// one snapshot: failed closes are retried, the watched orders polled until nothing
+    /// changes any more and the clock ticks
+    async fn snapshot(
+        &mut self,
+        machine: &mut CycleMachine,
+        now: i64,
+    ) -> polymarket_client_sdk::Result<()> {
+        for (token_id, size) in std::mem::take(&mut self.unfilled_closes) {
+            if let Some(closed) = self.close(token_id, size).await? {
+                self.feed(machine, closed, now).await?;
+            }
+        }
+
+        let mut ticked = false;
+        for _ in 0..STEPS_PER_SNAPSHOT {
+            let watched = machine.watched();
+            if !watched.is_empty() {
+                let mut orders = Vec::with_capacity(watched.len());
+                for order_id in &watched {
+                    orders.push(self.exchange.get_order(order_id).await?);
+                }
+                if self.feed(machine, CycleEvent::Orders(orders), now).await? {
+                    continue;
+                }
+            }
+            if ticked || !self.feed(machine, CycleEvent::Tick, now).await? {
+                break;
+            }
+            ticked = true;
+        }
+        Ok(())
+    }
+
+    /// like the runner's `feed`, `true` when anything was done
+    async fn feed(
+        &mut self,
+        machine: &mut CycleMachine,
+        event: CycleEvent,
+        now: i64,
+    ) -> polymarket_client_sdk::Result<bool> {
+        let mut events = VecDeque::from([event]);
+        let mut acted = false;
+
+        while let Some(event) = events.pop_front() {
+            let actions = machine.step(event, now);
+            if let Some(hedge) = machine.hedge() {
+                self.entry_size = Some(hedge.close_size);
+            }
+
+            for action in actions {
+                acted = true;
+                match action {
+                    CycleAction::PlaceEntries { tokens, size, prices } => {
+                        let first = self
+                            .exchange
+                            .place_limit_order(&tokens.first_asset_id, size, prices[0], Side::Buy)
+                            .await?;
+                        let second = self
+                            .exchange
+                            .place_limit_order(&tokens.second_asset_id, size, prices[1], Side::Buy)
+                            .await?;
+                        self.entries = vec![first.order_id.clone(), second.order_id.clone()];
+                        events.push_back(CycleEvent::EntriesPlaced([first, second]));
+                    }
+                    // a failed placement stops the run, nothing is ever left to clear
+                    CycleAction::ClearEntries { .. } => {
+                        events.push_back(CycleEvent::EntriesCleared {
+                            cancelled: vec![],
+                            held: vec![],
+                        });
+                    }
+                    CycleAction::Cancel(order_id) => self.exchange.cancel_order(&order_id).await?,
+                    CycleAction::PlaceHedge { token_id, size, price } => {
+                        let order = self
+                            .exchange
+                            .place_limit_order(&token_id, size, price, Side::Buy)
+                            .await?;
+                        self.hedge_order = Some(order.order_id.clone());
+                        self.hedge_size = size;
+                        events.push_back(CycleEvent::HedgePlaced(order));
+                    }
+                    CycleAction::Close { token_id, size } => {
+                        if let Some(closed) = self.close(token_id, size).await? {
+                            events.push_back(closed);
+                        }
+                    }
+                    // the next snapshot is the next poll
+                    CycleAction::Wait(_) => {}
+                }
+            }
+        }
+        Ok(acted)
+    }
+
+    /// `Closed` once the sell went through, otherwise it is tried again on the next snapshot
+    async fn close(
+        &mut self,
+        token_id: String,
+        size: Decimal,
+    ) -> polymarket_client_sdk::Result<Option<CycleEvent>> {
+        let response = self.exchange.place_market_order(&token_id, size, Side::Sell).await?;
+        match response.error_msg.as_deref() {
+            Some("") | None => Ok(Some(CycleEvent::Closed {
+                token_id,
+                sold: Some(response.sold()),
+            })),
+            Some(_) => {
+                self.unfilled_closes.push((token_id, size));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// cash plus complete up/down pairs at 1, unpaired shares at the last recorded best bid
+fn mark_to_market(
+    exchange: &PaperExchange,
+    market: &MarketData,
+    market_end: i64,
+    bankroll: Decimal,
+) -> Decimal {
+    let up = exchange.position(UP);
+    let down = exchange.position(DOWN);
+    let pairs = up.min(down);
+
+    let last = market
+        .records
+        .iter()
+        .take_while(|r| r.ts < market_end)
+        .last();
+    let up_bid = last.and_then(|r| r.up.best_bid()).unwrap_or_default();
+    let down_bid = last.and_then(|r| r.down.best_bid()).unwrap_or_default();
+
+    exchange.balance() - bankroll + pairs + (up - pairs) * up_bid + (down - pairs) * down_bid
+}
+
+pub fn summarize(reports: &[CycleReport]) -> BacktestSummary {
+    let mut summary = BacktestSummary {
+        cycles: reports.len(),
+        ..BacktestSummary::default()
+    };
+    let mut peak = Decimal::zero();
+
+    for report in reports {
+        match report.outcome {
+            BacktestOutcome::Hedged => summary.wins += 1,
+            BacktestOutcome::StopLoss => summary.losses += 1,
+            BacktestOutcome::CloseFailed => summary.close_failed += 1,
+            BacktestOutcome::Unhedged => summary.unhedged += 1,
+            BacktestOutcome::NoFill => summary.no_fill += 1,
+            BacktestOutcome::Skipped => summary.skipped += 1,
+        }
+        summary.legs_placed += report.legs_placed;
+        summary.legs_filled += report.legs_filled;
+        summary.partial_fills += report.partial_fills;
+
+        summary.pnl += report.pnl;
+        peak = peak.max(summary.pnl);
+        summary.max_drawdown = summary.max_drawdown.max(peak - summary.pnl);
+    }
+
+    summary.traded = summary.wins + summary.losses + summary.close_failed + summary.unhedged;
+    if summary.traded > 0 {
+        summary.win_rate = Decimal::from(summary.wins) / Decimal::from(summary.traded);
+    }
+    if summary.legs_placed > 0 {
+        summary.fill_rate = Decimal::from(summary.legs_filled) / Decimal::from(summary.legs_placed);
+    }
+    summary
+}
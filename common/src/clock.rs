@@ -0,0 +1,189 @@
+//! Where the time of the trading logic comes from. Live and paper trading run on the
+//! [`ExchangeClock`], the host clock corrected by the exchange's server time, the backtest
+//! on a [`ManualClock`] set to the replayed snapshots, and a [`FixedClock`] pins a single
+//! moment.
+
+use crate::error::BotError;
+use crate::metrics::CLOCK_SKEW_SECONDS;
+use crate::utils::timed_request;
+use async_trait::async_trait;
+use polymarket_client_sdk::clob::Client;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+/// server time requests per measurement, the one with the shortest round trip counts
+const SAMPLES: usize = 3;
+
+#[async_trait]
+pub trait Clock: Send + Sync {
+    /// unix seconds
+    fn now(&self) -> i64;
+
+    /// waits until `duration` passed on this clock
+    async fn sleep(&self, duration: Duration);
+
+    /// milliseconds the host clock is behind the exchange, `None` while it wasn't measured.
+    /// Clocks that don't follow the host have nothing to be off by
+    fn skew(&self) -> Option<i64> {
+        Some(0)
+    }
+}
+
+fn host_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_millis() as i64
+}
+
+/// the host's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        host_millis().div_euclid(1_000)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// The host clock moved by its last measured offset to the exchange's server time, the
+/// grace windows are only a few seconds wide. Durations are slept on the host clock.
+#[derive(Debug, Default)]
+pub struct ExchangeClock {
+    /// milliseconds
+    offset: AtomicI64,
+    measured: AtomicBool,
+}
+
+impl ExchangeClock {
+    /// Measures the offset against `GET /time` and applies it. The server answers in whole
+    /// seconds, so the offset is only good to about half a second plus half the round trip.
+    pub async fn sync(&self, client: &Client) -> Result<i64, BotError> {
+        let (mut round_trip, mut offset) = sample(client).await?;
+        for _ in 1..SAMPLES {
+            let next = sample(client).await?;
+            if next.0 < round_trip {
+                (round_trip, offset) = next;
+            }
+        }
+
+        let previous = self.skew();
+        self.offset.store(offset, Ordering::SeqCst);
+        self.measured.store(true, Ordering::SeqCst);
+        CLOCK_SKEW_SECONDS.set(offset as f64 / 1_000.0);
+        if previous.is_none_or(|previous| (previous - offset).abs() >= 1_000) {
+            println!("🕰️ Host clock is {offset}ms behind the exchange (round trip {round_trip}ms)");
+        }
+        Ok(offset)
+    }
+}
+
+/// round trip and offset of one server time request, in milliseconds
+async fn sample(client: &Client) -> Result<(i64, i64), BotError> {
+    let sent = host_millis();
+    let server = timed_request("polymarket", "server_time", client.server_time()).await?;
+    let received = host_millis();
+    let round_trip = received - sent;
+    // the server time is truncated to the second, its middle is the best guess
+    Ok((round_trip, server * 1_000 + 500 - (sent + round_trip / 2)))
+}
+
+#[async_trait]
+impl Clock for ExchangeClock {
+    fn now(&self) -> i64 {
+        (host_millis() + self.offset.load(Ordering::SeqCst)).div_euclid(1_000)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    fn skew(&self) -> Option<i64> {
+        self.measured
+            .load(Ordering::SeqCst)
+            .then(|| self.offset.load(Ordering::SeqCst))
+    }
+}
+
+/// measures the offset of `clock` every `interval`, a failed measurement keeps the last one
+pub async fn run_clock_sync(clock: Arc<ExchangeClock>, client: Client, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(e) = clock.sync(&client).await {
+            eprintln!("🕰️ Failed to measure the clock skew: {e}, keeping the last offset");
+        }
+    }
+}
+
+/// always the same moment, sleeping returns right away
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub i64);
+
+#[async_trait]
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+
+    async fn sleep(&self, _duration: Duration) {
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Time that only moves when it is told to, sleepers wake once it was moved past their end.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    /// unix milliseconds
+    now: AtomicI64,
+    moved: Notify,
+}
+
+impl ManualClock {
+    pub fn new(now: i64) -> Self {
+        ManualClock {
+            now: AtomicI64::new(now * 1_000),
+            moved: Notify::new(),
+        }
+    }
+
+    /// jumps to `now`, also backwards
+    pub fn set(&self, now: i64) {
+        self.now.store(now * 1_000, Ordering::SeqCst);
+        self.moved.notify_waiters();
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.fetch_add(duration.as_millis() as i64, Ordering::SeqCst);
+        self.moved.notify_waiters();
+    }
+
+    fn millis(&self) -> i64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> i64 {
+        self.millis().div_euclid(1_000)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let until = self.millis() + duration.as_millis() as i64;
+        loop {
+            // registered before the check, a move in between is not missed
+            let moved = self.moved.notified();
+            if self.millis() >= until {
+                return;
+            }
+            moved.await;
+        }
+    }
+}
@@ -0,0 +1,740 @@
+use crate::asset::{self, Asset, AssetInfo};
+use crate::dto::TradingMode;
+use crate::market::{DEFAULT_MARKET, MarketFamily, preset};
+use crate::pricing::{EntryPricing, PricingMode};
+use crate::sizing::{Sizing, SizingMode};
+use crate::retry::RetryPolicy;
+use crate::settings::StrategySettings;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::{env, fmt, fs};
+
+/// Global settings plus one `[assets.<name>]` section per asset, the name is its key.
+/// Keys missing in an asset section fall back to `[strategy]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotConfig {
+    #[serde(default = "default_mode")]
+    pub mode: TradingMode,
+    #[serde(default = "default_metrics_port")]
+    pub metrics_port: u16,
+    /// SQLite file with cycles, orders and hedge state, see `Store`
+    #[serde(default = "default_store_path")]
+    pub store_path: String,
+    /// what startup reconciliation does with open orders no running cycle owns
+    #[serde(default)]
+    pub orphan_orders: OrphanPolicy,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub paper: PaperConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub clock: ClockConfig,
+    #[serde(default)]
+    pub funds: FundsConfig,
+    #[serde(default)]
+    pub strategy: StrategyOverrides,
+    #[serde(default)]
+    pub assets: BTreeMap<String, StrategyOverrides>,
+    /// market families besides the presets, one `[markets.<name>]` each
+    #[serde(default)]
+    pub markets: BTreeMap<String, MarketFamily>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanPolicy {
+    /// cancel them before trading starts
+    #[default]
+    Cancel,
+    /// leave them on the book and record them, their markets are not traded
+    Adopt,
+}
+
+/// Ctrl-C / SIGTERM handling
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// seconds an active cycle gets before its position is flattened
+    #[serde(default = "default_shutdown_deadline")]
+    pub deadline: u64,
+    #[serde(default)]
+    pub hedge: ShutdownHedge,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            deadline: default_shutdown_deadline(),
+            hedge: ShutdownHedge::default(),
+        }
+    }
+}
+
+/// what happens to a held leg when shutting down, unfilled entry orders are always cancelled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownHedge {
+    /// keep hedging and apply the stop-loss until the deadline, flatten after it
+    #[default]
+    Finish,
+    /// close the position right away
+    Flatten,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PaperConfig {
+    #[serde(default = "default_paper_balance")]
+    pub balance: Decimal,
+    /// `live` matches against the real book, `simulated` against a random walk
+    #[serde(default = "default_paper_book")]
+    pub book: String,
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        PaperConfig {
+            balance: default_paper_balance(),
+            book: default_paper_book(),
+        }
+    }
+}
+
+/// how the host clock is kept in line with the exchange's server time
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockConfig {
+    /// seconds between two measurements of the skew
+    #[serde(default = "default_clock_sync_interval")]
+    pub sync_interval: u64,
+    /// seconds the host clock may be off the exchange, beyond that no new cycle starts
+    #[serde(default = "default_max_clock_skew")]
+    pub max_skew: u64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            sync_interval: default_clock_sync_interval(),
+            max_skew: default_max_clock_skew(),
+        }
+    }
+}
+
+/// what the pre-trade check alerts on
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FundsConfig {
+    /// USDC balance below which an alert is raised, 0 never alerts
+    pub alert_below: Decimal,
+}
+
+/// Retry policy per exchange call. A table replaces the whole default policy of its call,
+/// calls without one keep theirs.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// order status polls, the stop-loss waits on them
+    pub get_order: RetryPolicy,
+    /// entry and hedge limit orders, kept short. Only connect errors and rate limits post
+    /// again right away, a timed out order is looked up among the open orders first
+    pub place_order: RetryPolicy,
+    pub cancel_order: RetryPolicy,
+    /// FOK market sells, an unfilled one is retried like a network error
+    pub close_position: RetryPolicy,
+    pub get_price: RetryPolicy,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            get_order: RetryPolicy::new(20, 250, 2_000, 20),
+            place_order: RetryPolicy::new(3, 250, 1_000, 5),
+            cancel_order: RetryPolicy::new(5, 250, 2_000, 10),
+            close_position: RetryPolicy::new(30, 500, 2_000, 30),
+            get_price: RetryPolicy::new(3, 250, 1_000, 5),
+        }
+    }
+}
+
+impl RetryConfig {
+    fn validate(&self) -> Vec<String> {
+        [
+            ("get_order", &self.get_order),
+            ("place_order", &self.place_order),
+            ("cancel_order", &self.cancel_order),
+            ("close_position", &self.close_position),
+            ("get_price", &self.get_price),
+        ]
+        .into_iter()
+        .flat_map(|(name, policy)| policy.validate(&format!("retry.{name}")))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StrategyOverrides {
+    pub order_size: Option<Decimal>,
+    pub limit_enter_price: Option<Decimal>,
+    pub hedge_enter_price: Option<Decimal>,
+    pub dont_allow_trade_before: Option<i64>,
+    pub dont_allow_holding_before: Option<i64>,
+    pub stop_loss_after: Option<i64>,
+    /// a `[markets.<name>]` section or a preset: 15m, 1h, 4h or daily
+    pub market: Option<String>,
+    /// fixed or book, see [`EntryPricing`]
+    pub entry_pricing: Option<PricingMode>,
+    pub entry_ticks_below_bid: Option<u32>,
+    /// 0.01 by default
+    pub min_enter_price: Option<Decimal>,
+    /// `limit_enter_price` by default
+    pub max_enter_price: Option<Decimal>,
+    /// twice `limit_enter_price` by default
+    pub max_entry_cost: Option<Decimal>,
+    pub max_entry_spread: Option<Decimal>,
+    pub min_entry_depth: Option<Decimal>,
+    /// fixed, fraction or kelly, see [`Sizing`]
+    pub sizing: Option<SizingMode>,
+    pub bankroll_fraction: Option<Decimal>,
+    /// USDC, no cap by default
+    pub max_cycle_notional: Option<Decimal>,
+    /// 0 by default, a stopped out leg is counted as a total loss
+    pub kelly_exit_price: Option<Decimal>,
+    /// a disabled asset keeps its section but isn't traded
+    pub enabled: Option<bool>,
+    /// only per asset: `{asset}` in slugs, the key by default
+    pub slug_prefix: Option<String>,
+    /// only per asset: `{name}` in slugs, bitcoin for btc, the key for unknown assets
+    pub name: Option<String>,
+    /// only per asset: the `asset` label of its metrics, the key by default
+    pub label: Option<String>,
+}
+
+/// every problem found in the config file, not only the first one
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid config ({} errors):", self.errors.len())?;
+        for error in &self.errors {
+            writeln!(f, "  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn default_mode() -> TradingMode {
+    TradingMode::Live
+}
+
+fn default_metrics_port() -> u16 {
+    9101
+}
+
+fn default_store_path() -> String {
+    "polymarket-bot.db".to_string()
+}
+
+fn default_shutdown_deadline() -> u64 {
+    60
+}
+
+fn default_clock_sync_interval() -> u64 {
+    300
+}
+
+fn default_max_clock_skew() -> u64 {
+    2
+}
+
+fn default_paper_balance() -> Decimal {
+    Decimal::ONE_THOUSAND
+}
+
+fn default_paper_book() -> String {
+    "live".to_string()
+}
+
+/// Serde stops at the first bad key, so a file that doesn't deserialize is read again one
+/// section at a time, `[assets.*]` and `[markets.*]` one entry at a time, and every section
+/// that fails is reported under its path. `None` when the file isn't even valid TOML
+fn section_errors(raw: &str) -> Option<Vec<String>> {
+    let table: toml::Table = raw.parse().ok()?;
+    let mut errors = vec![];
+    for (key, value) in table {
+        let entries = match value {
+            toml::Value::Table(entries) if key == "assets" || key == "markets" => entries,
+            value => {
+                let section = toml::Table::from_iter([(key.clone(), value)]);
+                if let Err(e) = toml::Value::Table(section).try_into::<BotConfig>() {
+                    errors.push(format!("{key}: {}", e.message()));
+                }
+                continue;
+            }
+        };
+        for (name, entry) in entries {
+            let entry = toml::Table::from_iter([(name.clone(), entry)]);
+            let section = toml::Table::from_iter([(key.clone(), toml::Value::Table(entry))]);
+            if let Err(e) = toml::Value::Table(section).try_into::<BotConfig>() {
+                errors.push(format!("{key}.{name}: {}", e.message()));
+            }
+        }
+    }
+    (!errors.is_empty()).then_some(errors)
+}
+
+/// CONFIG_PATH or ./config.toml
+pub fn config_path() -> String {
+    env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string())
+}
+
+impl BotConfig {
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let raw = fs::read_to_string(path).map_err(|e| ConfigError {
+            errors: vec![format!("{path}: {e}")],
+        })?;
+        Self::parse(&raw)
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, ConfigError> {
+        let config: BotConfig = toml::from_str(raw).map_err(|e| ConfigError {
+            errors: section_errors(raw).unwrap_or_else(|| vec![e.to_string()]),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// enabled assets, sorted by key
+    pub fn assets(&self) -> Vec<Asset> {
+        self.assets
+            .iter()
+            .filter(|(_, overrides)| overrides.enabled.or(self.strategy.enabled).unwrap_or(true))
+            .filter_map(|(key, _)| key.parse().ok())
+            .collect()
+    }
+
+    /// slug prefix, name and label of a configured asset
+    pub fn asset_info(&self, asset: &Asset) -> Option<AssetInfo> {
+        let overrides = self.assets.get(asset.key())?;
+        let default = AssetInfo::default_for(asset.key());
+        Some(AssetInfo {
+            slug_prefix: overrides.slug_prefix.clone().unwrap_or(default.slug_prefix),
+            name: overrides.name.clone().unwrap_or(default.name),
+            label: overrides.label.clone().unwrap_or(default.label),
+        })
+    }
+
+    /// every configured asset goes into the registry, enabled or not
+    pub fn register_assets(&self) {
+        for key in self.assets.keys() {
+            let Ok(asset) = key.parse::<Asset>() else {
+                continue;
+            };
+            if let Some(info) = self.asset_info(&asset) {
+                asset::register(asset, info);
+            }
+        }
+    }
+
+    /// `[assets.<asset>]` on top of `[strategy]`, only valid after `validate`
+    pub fn settings(&self, asset: &Asset) -> Option<StrategySettings> {
+        let overrides = self.assets.get(asset.key())?;
+        let (settings, missing) = self.merge(overrides);
+        missing.is_empty().then_some(settings)
+    }
+
+    /// `[markets.<name>]` or the preset of that name
+    pub fn market(&self, name: &str) -> Option<MarketFamily> {
+        match self.markets.get(name) {
+            Some(family) => Some(MarketFamily {
+                name: name.to_string(),
+                ..family.clone()
+            }),
+            None => preset(name),
+        }
+    }
+
+    fn market_name<'a>(&'a self, overrides: &'a StrategyOverrides) -> &'a str {
+        overrides
+            .market
+            .as_deref()
+            .or(self.strategy.market.as_deref())
+            .unwrap_or(DEFAULT_MARKET)
+    }
+
+    /// merged settings plus the names of keys set in neither section, those are zeroed.
+    /// An unknown market counts as missing
+    fn merge(&self, overrides: &StrategyOverrides) -> (StrategySettings, Vec<String>) {
+        let defaults = &self.strategy;
+        let mut missing = vec![];
+        let market = self.market(self.market_name(overrides)).unwrap_or_else(|| {
+            missing.push("market".to_string());
+            MarketFamily::default()
+        });
+        let mut pick = |name: &str, value: Option<Decimal>, default: Option<Decimal>| {
+            value.or(default).unwrap_or_else(|| {
+                missing.push(name.to_string());
+                Decimal::ZERO
+            })
+        };
+        let order_size = pick("order_size", overrides.order_size, defaults.order_size);
+        let limit_enter_price = pick(
+            "limit_enter_price",
+            overrides.limit_enter_price,
+            defaults.limit_enter_price,
+        );
+        let hedge_enter_price = pick(
+            "hedge_enter_price",
+            overrides.hedge_enter_price,
+            defaults.hedge_enter_price,
+        );
+        let mut pick = |name: &str, value: Option<i64>, default: Option<i64>| {
+            value.or(default).unwrap_or_else(|| {
+                missing.push(name.to_string());
+                0
+            })
+        };
+        let dont_allow_trade_before = pick(
+            "dont_allow_trade_before",
+            overrides.dont_allow_trade_before,
+            defaults.dont_allow_trade_before,
+        );
+        let dont_allow_holding_before = pick(
+            "dont_allow_holding_before",
+            overrides.dont_allow_holding_before,
+            defaults.dont_allow_holding_before,
+        );
+        let stop_loss_after = pick(
+            "stop_loss_after",
+            overrides.stop_loss_after,
+            defaults.stop_loss_after,
+        );
+
+        let entry = EntryPricing {
+            mode: overrides.entry_pricing.or(defaults.entry_pricing).unwrap_or_default(),
+            ticks_below_bid: overrides
+                .entry_ticks_below_bid
+                .or(defaults.entry_ticks_below_bid)
+                .unwrap_or(1),
+            min_price: overrides
+                .min_enter_price
+                .or(defaults.min_enter_price)
+                .unwrap_or(Decimal::new(1, 2)),
+            max_price: overrides
+                .max_enter_price
+                .or(defaults.max_enter_price)
+                .unwrap_or(limit_enter_price),
+            max_combined: overrides
+                .max_entry_cost
+                .or(defaults.max_entry_cost)
+                .unwrap_or(limit_enter_price * Decimal::TWO),
+            max_spread: overrides
+                .max_entry_spread
+                .or(defaults.max_entry_spread)
+                .unwrap_or(Decimal::new(10, 2)),
+            min_depth: overrides
+                .min_entry_depth
+                .or(defaults.min_entry_depth)
+                .unwrap_or_default(),
+        };
+
+        let sizing = Sizing {
+            mode: overrides.sizing.or(defaults.sizing).unwrap_or_default(),
+            fraction: overrides
+                .bankroll_fraction
+                .or(defaults.bankroll_fraction)
+                .unwrap_or_default(),
+            max_notional: overrides.max_cycle_notional.or(defaults.max_cycle_notional),
+            exit_price: overrides
+                .kelly_exit_price
+                .or(defaults.kelly_exit_price)
+                .unwrap_or_default(),
+        };
+
+        let settings = StrategySettings {
+            order_size,
+            limit_enter_price,
+            hedge_enter_price,
+            dont_allow_trade_before,
+            dont_allow_holding_before,
+            stop_loss_after,
+            market,
+            entry,
+            sizing,
+        };
+        (settings, missing)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = vec![];
+
+        if self.metrics_port == 0 {
+            errors.push("metrics_port: must not be 0".to_string());
+        }
+        if self.store_path.trim().is_empty() {
+            errors.push("store_path: must not be empty".to_string());
+        }
+        if self.shutdown.deadline == 0 {
+            errors.push("shutdown.deadline: must be positive".to_string());
+        }
+        if self.paper.balance <= Decimal::ZERO {
+            errors.push(format!(
+                "paper.balance: must be positive, got {}",
+                self.paper.balance
+            ));
+        }
+        if !["live", "simulated"].contains(&self.paper.book.as_str()) {
+            errors.push(format!(
+                "paper.book: must be live or simulated, got {}",
+                self.paper.book
+            ));
+        }
+        errors.extend(self.retry.validate());
+        if self.clock.sync_interval == 0 {
+            errors.push("clock.sync_interval: must be positive".to_string());
+        }
+        if self.clock.max_skew == 0 {
+            errors.push("clock.max_skew: must be positive".to_string());
+        }
+        if self.funds.alert_below < Decimal::ZERO {
+            errors.push(format!(
+                "funds.alert_below: must not be negative, got {}",
+                self.funds.alert_below
+            ));
+        }
+        for (name, family) in &self.markets {
+            errors.extend(family.validate(&format!("markets.{name}")));
+        }
+        if self.assets.is_empty() {
+            errors.push("assets: at least one [assets.<name>] section is required".to_string());
+        } else if self.assets().is_empty() {
+            errors.push("assets: every asset is disabled".to_string());
+        }
+        for (key, value) in [
+            ("slug_prefix", &self.strategy.slug_prefix),
+            ("name", &self.strategy.name),
+            ("label", &self.strategy.label),
+        ] {
+            if value.is_some() {
+                errors.push(format!("strategy.{key}: can only be set per asset"));
+            }
+        }
+
+        let mut labels = BTreeMap::new();
+        for (name, overrides) in &self.assets {
+            let section = format!("assets.{name}");
+            match name.parse::<Asset>() {
+                Ok(asset) => {
+                    let info = self.asset_info(&asset).unwrap_or_else(|| AssetInfo::default_for(name));
+                    for (key, value) in [
+                        ("slug_prefix", &info.slug_prefix),
+                        ("name", &info.name),
+                        ("label", &info.label),
+                    ] {
+                        if value.trim().is_empty() {
+                            errors.push(format!("{section}.{key}: must not be empty"));
+                        }
+                    }
+                    // metrics of both would add up
+                    if let Some(other) = labels.insert(info.label.clone(), name) {
+                        errors.push(format!(
+                            "{section}.label: {} is already the label of {other}",
+                            info.label
+                        ));
+                    }
+                }
+                Err(e) => errors.push(format!("{section}: {e}")),
+            }
+            let (settings, missing) = self.merge(overrides);
+            for key in &missing {
+                if key == "market" {
+                    errors.push(format!(
+                        "{section}.market: unknown market {}, expected a [markets.<name>] section or 15m, 1h, 4h, daily",
+                        self.market_name(overrides)
+                    ));
+                } else {
+                    errors.push(format!("{section}.{key}: not set here nor in [strategy]"));
+                }
+            }
+            // checks that read a zeroed key would only produce noise
+            errors.extend(
+                validate_settings(&settings)
+                    .into_iter()
+                    .filter(|e| !e.keys.iter().any(|key| missing.iter().any(|m| m == key)))
+                    .map(|e| format!("{section}.{e}")),
+            );
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+}
+
+/// a problem with one setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingError {
+    /// the setting at fault, then the ones its check also reads
+    pub keys: &'static [&'static str],
+    pub message: String,
+}
+
+impl SettingError {
+    fn new(keys: &'static [&'static str], message: String) -> Self {
+        SettingError { keys, message }
+    }
+
+    pub fn key(&self) -> &'static str {
+        self.keys[0]
+    }
+}
+
+impl Display for SettingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key(), self.message)
+    }
+}
+
+pub fn validate_settings(settings: &StrategySettings) -> Vec<SettingError> {
+    let mut errors = vec![];
+    let price_range = |p: Decimal| p > Decimal::ZERO && p < Decimal::ONE;
+
+    if settings.order_size <= Decimal::ZERO {
+        errors.push(SettingError::new(
+            &["order_size"],
+            format!("must be positive, got {}", settings.order_size),
+        ));
+    }
+    if !price_range(settings.limit_enter_price) {
+        errors.push(SettingError::new(
+            &["limit_enter_price"],
+            format!("must be within (0, 1), got {}", settings.limit_enter_price),
+        ));
+    }
+    if !price_range(settings.hedge_enter_price) {
+        errors.push(SettingError::new(
+            &["hedge_enter_price"],
+            format!("must be within (0, 1), got {}", settings.hedge_enter_price),
+        ));
+    }
+    // a hedged pair pays out exactly 1, paying 1 or more for it can only lose
+    if settings.limit_enter_price + settings.hedge_enter_price >= Decimal::ONE {
+        errors.push(SettingError::new(
+            &["hedge_enter_price", "limit_enter_price"],
+            format!(
+                "entry {} + hedge {} must be below 1",
+                settings.limit_enter_price, settings.hedge_enter_price
+            ),
+        ));
+    }
+    let entry = &settings.entry;
+    if entry.mode == PricingMode::Book {
+        if entry.min_price <= Decimal::ZERO || entry.min_price > entry.max_price {
+            errors.push(SettingError::new(
+                &["min_enter_price", "max_enter_price", "limit_enter_price"],
+                format!("must be within (0, max_enter_price], got {}", entry.min_price),
+            ));
+        }
+        // the dearest entry still has to leave room for the hedge
+        if entry.max_price + settings.hedge_enter_price >= Decimal::ONE {
+            errors.push(SettingError::new(
+                &["max_enter_price", "limit_enter_price", "hedge_enter_price"],
+                format!(
+                    "entry {} + hedge {} must be below 1",
+                    entry.max_price, settings.hedge_enter_price
+                ),
+            ));
+        }
+        if entry.max_combined < entry.min_price * Decimal::TWO
+            || entry.max_combined >= Decimal::TWO
+        {
+            errors.push(SettingError::new(
+                &["max_entry_cost", "min_enter_price", "limit_enter_price"],
+                format!(
+                    "must be within [2 * min_enter_price, 2), got {}",
+                    entry.max_combined
+                ),
+            ));
+        }
+        if entry.max_spread <= Decimal::ZERO {
+            errors.push(SettingError::new(
+                &["max_entry_spread"],
+                format!("must be positive, got {}", entry.max_spread),
+            ));
+        }
+        if entry.min_depth < Decimal::ZERO {
+            errors.push(SettingError::new(
+                &["min_entry_depth"],
+                format!("must not be negative, got {}", entry.min_depth),
+            ));
+        }
+    }
+    let sizing = &settings.sizing;
+    if sizing.mode != SizingMode::Fixed
+        && (sizing.fraction <= Decimal::ZERO || sizing.fraction > Decimal::ONE)
+    {
+        errors.push(SettingError::new(
+            &["bankroll_fraction"],
+            format!(
+                "must be within (0, 1] to size by the bankroll, got {}",
+                sizing.fraction
+            ),
+        ));
+    }
+    if let Some(cap) = sizing.max_notional
+        && cap <= Decimal::ZERO
+    {
+        errors.push(SettingError::new(
+            &["max_cycle_notional"],
+            format!("must be positive, got {cap}"),
+        ));
+    }
+    if sizing.exit_price < Decimal::ZERO || sizing.exit_price >= Decimal::ONE {
+        errors.push(SettingError::new(
+            &["kelly_exit_price"],
+            format!("must be within [0, 1), got {}", sizing.exit_price),
+        ));
+    }
+    // every grace window has to fit inside one market
+    let length = settings.market.interval;
+    if settings.dont_allow_trade_before <= 0 || settings.dont_allow_trade_before >= length {
+        errors.push(SettingError::new(
+            &["dont_allow_trade_before", "market"],
+            format!(
+                "must be within (0, {length}), got {}",
+                settings.dont_allow_trade_before
+            ),
+        ));
+    }
+    if settings.dont_allow_holding_before < 0
+        || settings.dont_allow_holding_before >= settings.dont_allow_trade_before
+    {
+        errors.push(SettingError::new(
+            &["dont_allow_holding_before", "dont_allow_trade_before"],
+            format!(
+                "must be within [0, dont_allow_trade_before), got {}",
+                settings.dont_allow_holding_before
+            ),
+        ));
+    }
+    if settings.stop_loss_after < 0 || settings.stop_loss_after >= length {
+        errors.push(SettingError::new(
+            &["stop_loss_after", "market"],
+            format!("must be within [0, {length}), got {}", settings.stop_loss_after),
+        ));
+    }
+    errors
+}
@@ -0,0 +1,190 @@
+use crate::clock::Clock;
+use crate::exchange::Exchange;
+use crate::funds::Reservations;
+use crate::metrics::TRADING_STATE;
+use crate::shutdown::Shutdown;
+use crate::store::Store;
+use crate::utils::sell_position;
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+
+const RUNNING: u8 = 0;
+const HALTED: u8 = 1;
+const FLATTENING: u8 = 2;
+
+/// Operator state shared by every asset task: running, halted (no new cycles,
+/// entries cancelled) or flattening (halted and every position sold), plus the shutdown,
+/// the clock the tasks go by and the collateral their cycles set aside.
+#[derive(Clone)]
+pub struct Control {
+    shutdown: Shutdown,
+    state: Arc<AtomicU8>,
+    exchange: Arc<dyn Exchange>,
+    store: Arc<Store>,
+    clock: Arc<dyn Clock>,
+    reservations: Reservations,
+}
+
+impl Control {
+    pub fn new(
+        shutdown: Shutdown,
+        exchange: Arc<dyn Exchange>,
+        store: Arc<Store>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        TRADING_STATE.set(RUNNING as i64);
+        Control {
+            shutdown,
+            state: Arc::new(AtomicU8::new(RUNNING)),
+            exchange,
+            store,
+            clock,
+            reservations: Reservations::default(),
+        }
+    }
+
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn reservations(&self) -> &Reservations {
+        &self.reservations
+    }
+
+    /// sleeps on the clock unless a shutdown comes first
+    pub async fn sleep(&self, duration: Duration) {
+        tokio::select! {
+            _ = self.clock.sleep(duration) => {}
+            _ = self.shutdown.wait() => {}
+        }
+    }
+
+    /// no new cycles, resting entry orders get cancelled
+    pub fn halted(&self) -> bool {
+        self.state.load(Ordering::SeqCst) != RUNNING
+    }
+
+    /// open positions have to be closed now instead of hedged
+    pub fn flatten_now(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == FLATTENING || self.shutdown.flatten_now()
+    }
+
+    /// running, halted or flattening
+    pub fn state(&self) -> &'static str {
+        match self.state.load(Ordering::SeqCst) {
+            RUNNING => "running",
+            HALTED => "halted",
+            _ => "flattening",
+        }
+    }
+
+    fn set(&self, state: u8) {
+        self.state.store(state, Ordering::SeqCst);
+        TRADING_STATE.set(state as i64);
+    }
+
+    pub fn halt(&self) {
+        println!("⏸️ Trading halted, no new cycles and resting entries get cancelled");
+        self.set(HALTED);
+    }
+
+    pub fn resume(&self) {
+        println!("▶️ Trading resumed");
+        self.set(RUNNING);
+    }
+
+    /// Halts and sells everything. Active cycles unwind themselves, shares left from
+    /// finished cycles in markets that are still open are sold here.
+    pub fn flatten(&self) {
+        println!("🧯 Flattening every position");
+        self.set(FLATTENING);
+
+        let control = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control.flatten_finished().await {
+                eprintln!("🧯 Failed to flatten positions of finished cycles: {e}");
+            }
+        });
+    }
+
+    async fn flatten_finished(&self) -> anyhow::Result<()> {
+        let now = self.clock.now();
+        let mut seen = HashSet::new();
+        for cycle in self.store.finished_in_open_markets(now)? {
+            let journal = self.store.journal(cycle.id);
+            for token_id in [&cycle.tokens.first_asset_id, &cycle.tokens.second_asset_id] {
+                if !seen.insert(token_id.clone()) {
+                    continue;
+                }
+                let balance = self.exchange.token_balance(token_id).await?;
+                if balance.is_zero() {
+                    continue;
+                }
+                println!("🧯 Selling {balance} of {token_id} left by {} cycle {}", cycle.asset, cycle.id);
+                // the live balance is what is left, whatever the cycle closed before
+                sell_position(self.exchange.as_ref(), &journal, &cycle.asset, token_id, balance)
+                    .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn halt_handler(State(control): State<Control>) -> &'static str {
+    control.halt();
+    "halted\n"
+}
+
+async fn resume_handler(State(control): State<Control>) -> (StatusCode, &'static str) {
+    if control.shutdown.requested() {
+        return (StatusCode::CONFLICT, "shutting down\n");
+    }
+    control.resume();
+    (StatusCode::OK, "resumed\n")
+}
+
+async fn flatten_handler(State(control): State<Control>) -> &'static str {
+    control.flatten();
+    "flattening\n"
+}
+
+/// `POST /halt`, `/resume` and `/flatten` on the metrics server
+pub fn routes(control: Control) -> Router {
+    Router::new()
+        .route("/halt", post(halt_handler))
+        .route("/resume", post(resume_handler))
+        .route("/flatten", post(flatten_handler))
+        .with_state(control)
+}
+
+/// every route of `router` requires `Authorization: Bearer <token>`, compared in constant time
+pub fn authenticated(router: Router, token: String) -> Router {
+    let expected = Arc::new(format!("Bearer {token}"));
+    router.layer(middleware::from_fn(move |request: Request, next: Next| {
+        let expected = expected.clone();
+        async move {
+            let authorized = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .is_some_and(|value| bool::from(value.as_bytes().ct_eq(expected.as_bytes())));
+            if authorized {
+                next.run(request).await
+            } else {
+                (StatusCode::UNAUTHORIZED, "unauthorized\n").into_response()
+            }
+        }
+    }))
+}
@@ -0,0 +1,977 @@
+//! One trade cycle as an explicit state machine.
+//!
+//! Nothing in here talks to the exchange or sleeps. The driver feeds order states, results of
+//! the actions it carried out and clock ticks into [`CycleMachine::step`] and does whatever
+//! [`CycleAction`]s come back, so any state can be set up with [`CycleMachine::with_state`]
+//! and stepped with hand-made [`OrderState`]s. Live and paper trading are driven from
+//! `runner`, the backtest from recorded books.
+
+use crate::asset::Asset;
+use crate::dto::{Fill, HedgeConfig, MarketResponse, OrderResponse, OrderState};
+use crate::metrics::{
+    HEDGE_ORDERS_CANCELLED_TOTAL, HEDGE_ORDERS_MATCHED_TOTAL, HEDGE_ORDERS_PARTIAL_TOTAL,
+    ORDERS_CANCELLED_TOTAL, ORDERS_MATCHED_TOTAL, ORDERS_PARTIAL_TOTAL, ORDERS_TOTAL,
+    STOP_LOSS_TOTAL,
+};
+use crate::outcome::{CycleFills, CycleOutcome, FillLog};
+use crate::settings::StrategySettings;
+use crate::store::{CycleRecord, CycleStage, StoredOrder};
+use crate::utils::{allow_stop_loss_at, allow_trade_at, floor_dp, normalized_size};
+use polymarket_client_sdk::clob::types::OrderStatusType;
+use prometheus::CounterVec;
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+/// time the entry and hedge orders get before the first poll
+const SETTLE: Duration = Duration::from_secs(10);
+/// time a cancelled hedge gets to report its last fill
+const CANCEL_SETTLE: Duration = Duration::from_secs(5);
+/// placements of the entries before the cycle gives up on the market
+const ENTRY_ATTEMPTS: u32 = 5;
+
+/// the market a cycle trades
+#[derive(Debug, Clone)]
+pub struct Market {
+    pub timestamp: i64,
+    pub tokens: MarketResponse,
+    /// entry prices of the first and second token
+    pub prices: [Decimal; 2],
+}
+
+/// why a cycle sells what it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// the hedge didn't match before `stop_loss_after`
+    StopLoss,
+    /// `/flatten` or a shutdown, positions are sold instead of hedged
+    Flatten,
+}
+
+impl CloseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::StopLoss => "stop-loss reached",
+            CloseReason::Flatten => "flatten",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CycleState {
+    /// nothing placed yet, `market` is set once it was picked. `failures` counts the
+    /// placements of the entries that failed
+    AwaitingMarket {
+        market: Option<Market>,
+        failures: u32,
+    },
+    /// both entry orders rest on the book, `stopping` once they were cancelled for a halt
+    EntryResting {
+        market: Market,
+        first: String,
+        second: String,
+        stopping: bool,
+    },
+    /// a leg is held, the other entry order (`hedge.second_order_id`) gets cancelled and its fill
+    /// counted against the hedge. `held_order` is set when the held order was cancelled mid-fill,
+    /// the sizes then follow its final fill
+    LegMatched {
+        hedge: HedgeConfig,
+        held_order: Option<String>,
+        cancel_sent: bool,
+    },
+    /// hedge on the other side, `order_id` is `None` until it is placed
+    Hedging {
+        hedge: HedgeConfig,
+        order_id: Option<String>,
+        size: Decimal,
+    },
+    /// hedge cancelled, waits for its last fill before everything is sold
+    StopLoss {
+        hedge: HedgeConfig,
+        order_id: String,
+        size: Decimal,
+        reason: CloseReason,
+    },
+    /// market-selling what is held, `failed` once `initial` could not be sold
+    Flattening {
+        reason: CloseReason,
+        initial: String,
+        pending: usize,
+        failed: bool,
+    },
+    Done(CycleOutcome),
+}
+
+#[derive(Debug, Clone)]
+pub enum CycleEvent {
+    /// the market to trade, checked and recorded by the driver
+    MarketOpened(Market),
+    EntriesPlaced([OrderResponse; 2]),
+    /// `retry` is false when placing them again can't help
+    EntriesFailed { retry: bool },
+    /// what failed placements left on the book was cancelled, `cancelled` are the final
+    /// states of those orders and `held` the shares still held of either token
+    EntriesCleared {
+        cancelled: Vec<OrderState>,
+        held: Vec<(String, Decimal)>,
+    },
+    HedgePlaced(OrderResponse),
+    /// latest state of the orders `watched` asked for
+    Orders(Vec<OrderState>),
+    /// outcome of a `Close`, `sold` is `None` when it failed
+    Closed {
+        token_id: String,
+        sold: Option<Fill>,
+    },
+    /// halt or shutdown: nothing new is entered, resting entries get cancelled
+    Halt,
+    /// held positions are sold instead of hedged, implies `Halt`
+    Flatten,
+    /// the clock moved on, flatten and the stop-loss are checked here
+    Tick,
+}
+
+/// what the driver has to do, results come back as events
+#[derive(Debug, Clone, PartialEq)]
+pub enum CycleAction {
+    /// answered with `EntriesPlaced` or `EntriesFailed`
+    PlaceEntries {
+        tokens: MarketResponse,
+        size: Decimal,
+        prices: [Decimal; 2],
+    },
+    /// cancels the open orders on both tokens before the entries are placed again,
+    /// answered with `EntriesCleared`
+    ClearEntries { tokens: MarketResponse },
+    Cancel(String),
+    /// answered with `HedgePlaced`
+    PlaceHedge {
+        token_id: String,
+        size: Decimal,
+        price: Decimal,
+    },
+    /// answered with `Closed`
+    Close { token_id: String, size: Decimal },
+    /// let the orders work before the next poll
+    Wait(Duration),
+}
+
+pub struct CycleMachine {
+    asset: Asset,
+    settings: StrategySettings,
+    state: CycleState,
+    halted: bool,
+    flatten: bool,
+    logging: bool,
+    fills: FillLog,
+    fee_rate_bps: u32,
+}
+
+impl CycleMachine {
+    /// a fresh cycle, the settings are kept until it is done
+    pub fn new(asset: Asset, settings: StrategySettings) -> Self {
+        Self::with_state(
+            asset,
+            settings,
+            CycleState::AwaitingMarket {
+                market: None,
+                failures: 0,
+            },
+        )
+    }
+
+    pub fn with_state(asset: Asset, settings: StrategySettings, state: CycleState) -> Self {
+        CycleMachine {
+            asset,
+            settings,
+            state,
+            halted: false,
+            flatten: false,
+            logging: true,
+            fills: FillLog::default(),
+            fee_rate_bps: 0,
+        }
+    }
+
+    /// picks up where a stored cycle stopped, `None` when not enough of it was stored.
+    /// `orders` are the stored orders of the cycle, their fills count towards the outcome.
+    /// What the cycle stored of its settings wins over `settings`
+    pub fn resume(
+        cycle: &CycleRecord,
+        orders: &[StoredOrder],
+        mut settings: StrategySettings,
+    ) -> Option<Self> {
+        if let Some(holding_before) = cycle.holding_before {
+            settings.dont_allow_holding_before = holding_before;
+        }
+        // a sized cycle hedges and closes the size it entered with
+        if let Some(entry) = orders.iter().find(|order| order.role == "entry") {
+            settings.order_size = entry.size;
+        }
+        let state = match (cycle.stage, &cycle.hedge, &cycle.hedge_order) {
+            (CycleStage::Entry, _, _) => match cycle.entry_orders.as_slice() {
+                [first, second] => CycleState::EntryResting {
+                    market: Market {
+                        timestamp: cycle.timestamp,
+                        tokens: cycle.tokens.clone(),
+                        prices: [&first.order_id, &second.order_id].map(|order_id| {
+                            orders
+                                .iter()
+                                .find(|o| &o.order_id == order_id)
+                                .map_or(settings.limit_enter_price, |o| o.price)
+                        }),
+                    },
+                    first: first.order_id.clone(),
+                    second: second.order_id.clone(),
+                    stopping: false,
+                },
+                _ => return None,
+            },
+            // stop-loss is time based, a cycle that was closing goes straight back into it
+            (CycleStage::Hedging | CycleStage::Closing, Some(hedge), Some((order_id, size))) => {
+                CycleState::Hedging {
+                    hedge: hedge.clone(),
+                    order_id: Some(order_id.clone()),
+                    size: *size,
+                }
+            }
+            (CycleStage::Hedging, Some(hedge), None) => CycleState::LegMatched {
+                hedge: hedge.clone(),
+                held_order: None,
+                cancel_sent: false,
+            },
+            // closing without a hedge order is a flatten, it sells the held leg and the other
+            // entry's fill again, whatever was already sold is skipped by the driver
+            (CycleStage::Closing, Some(hedge), None) => CycleState::LegMatched {
+                hedge: hedge.clone(),
+                held_order: None,
+                cancel_sent: true,
+            },
+            _ => return None,
+        };
+        let flattening = cycle.stage == CycleStage::Closing && cycle.hedge_order.is_none();
+        let mut machine = Self::with_state(cycle.asset, settings, state);
+        machine.halted = flattening;
+        machine.flatten = flattening;
+        for order in orders {
+            let fill = Fill {
+                size: order.size_matched,
+                price: order.price,
+            };
+            match order.role.as_str() {
+                "close" => machine.fills.sold(&order.token_id, fill),
+                role => machine
+                    .fills
+                    .order(&order.order_id, &order.token_id, role == "hedge", fill),
+            }
+        }
+        Some(machine)
+    }
+
+    pub fn with_logging(mut self, logging: bool) -> Self {
+        self.logging = logging;
+        self
+    }
+
+    /// fee rate of the market, charged on market sells
+    pub fn with_fee_rate_bps(mut self, fee_rate_bps: u32) -> Self {
+        self.fee_rate_bps = fee_rate_bps;
+        self
+    }
+
+    pub fn asset(&self) -> Asset {
+        self.asset
+    }
+
+    pub fn state(&self) -> &CycleState {
+        &self.state
+    }
+
+    /// where the store keeps a cycle in this state
+    pub fn stage(&self) -> CycleStage {
+        match self.state {
+            CycleState::AwaitingMarket { .. } | CycleState::EntryResting { .. } => {
+                CycleStage::Entry
+            }
+            CycleState::LegMatched { .. } | CycleState::Hedging { .. } => CycleStage::Hedging,
+            CycleState::StopLoss { .. } | CycleState::Flattening { .. } => CycleStage::Closing,
+            CycleState::Done(_) => CycleStage::Done,
+        }
+    }
+
+    /// the held leg and its hedge, from the moment a leg matched until the cycle is closing
+    pub fn hedge(&self) -> Option<&HedgeConfig> {
+        match &self.state {
+            CycleState::LegMatched { hedge, .. }
+            | CycleState::Hedging { hedge, .. }
+            | CycleState::StopLoss { hedge, .. } => Some(hedge),
+            _ => None,
+        }
+    }
+
+    pub fn result(&self) -> Option<&CycleOutcome> {
+        match &self.state {
+            CycleState::Done(outcome) => Some(outcome),
+            _ => None,
+        }
+    }
+
+    /// orders the driver polls and feeds back as `Orders`
+    pub fn watched(&self) -> Vec<String> {
+        match &self.state {
+            CycleState::EntryResting { first, second, .. } => vec![first.clone(), second.clone()],
+            CycleState::LegMatched {
+                hedge, held_order, ..
+            } => {
+                let mut ids = vec![hedge.second_order_id.clone()];
+                ids.extend(held_order.clone());
+                ids
+            }
+            CycleState::Hedging {
+                order_id: Some(order_id),
+                ..
+            }
+            | CycleState::StopLoss { order_id, .. } => vec![order_id.clone()],
+            _ => vec![],
+        }
+    }
+
+    /// moves on with `event` at `now` (unix seconds)
+    pub fn step(&mut self, event: CycleEvent, now: i64) -> Vec<CycleAction> {
+        match event {
+            CycleEvent::Halt => self.halted = true,
+            CycleEvent::Flatten => {
+                self.halted = true;
+                self.flatten = true;
+            }
+            CycleEvent::Orders(ref orders)
+            | CycleEvent::EntriesCleared {
+                cancelled: ref orders,
+                ..
+            } => self.record(orders),
+            CycleEvent::Closed {
+                ref token_id,
+                sold: Some(sold),
+            } => self.fills.sold(token_id, sold),
+            _ => {}
+        }
+
+        let state = std::mem::replace(&mut self.state, CycleState::Done(CycleOutcome::NoFill));
+        let (state, actions) = match state {
+            CycleState::AwaitingMarket { market, failures } => {
+                self.awaiting_market(market, failures, event, now)
+            }
+            CycleState::EntryResting {
+                market,
+                first,
+                second,
+                stopping,
+            } => self.entry_resting(market, first, second, stopping, event, now),
+            CycleState::LegMatched {
+                hedge,
+                held_order,
+                cancel_sent,
+            } => self.leg_matched(hedge, held_order, cancel_sent, event),
+            CycleState::Hedging {
+                hedge,
+                order_id,
+                size,
+            } => self.hedging(hedge, order_id, size, event, now),
+            CycleState::StopLoss {
+                hedge,
+                order_id,
+                size,
+                reason,
+            } => self.stop_loss(hedge, order_id, size, reason, event),
+            CycleState::Flattening {
+                reason,
+                initial,
+                pending,
+                failed,
+            } => self.flattening(reason, initial, pending, failed, event),
+            done @ CycleState::Done(_) => (done, vec![]),
+        };
+        self.state = state;
+        actions
+    }
+
+    fn log(&self, message: impl AsRef<str>) {
+        if self.logging {
+            println!("{}", message.as_ref());
+        }
+    }
+
+    fn count(&self, counter: &CounterVec) {
+        counter.with_label_values(&[self.asset.label()]).inc();
+    }
+
+    /// latest fills of the polled orders, the one the hedge watches is the hedge
+    fn record(&mut self, orders: &[OrderState]) {
+        let hedge_order = match &self.state {
+            CycleState::Hedging {
+                order_id: Some(order_id),
+                ..
+            }
+            | CycleState::StopLoss { order_id, .. } => Some(order_id.clone()),
+            _ => None,
+        };
+        for order in orders {
+            let fill = Fill {
+                size: order.size_matched,
+                price: order.price,
+            };
+            let hedge = hedge_order.as_deref() == Some(order.order_id.as_str());
+            self.fills.order(&order.order_id, &order.token_id, hedge, fill);
+        }
+    }
+
+    /// `outcome` with the fills seen so far, `NoFill` when nothing was bought
+    fn done(&self, outcome: fn(CycleFills) -> CycleOutcome) -> CycleState {
+        if self.fills.is_empty() {
+            return CycleState::Done(CycleOutcome::NoFill);
+        }
+        CycleState::Done(outcome(self.fills.fills(self.fee_rate_bps)))
+    }
+
+    fn awaiting_market(
+        &self,
+        market: Option<Market>,
+        failures: u32,
+        event: CycleEvent,
+        now: i64,
+    ) -> (CycleState, Vec<CycleAction>) {
+        match (market, event) {
+            (Some(market), CycleEvent::EntriesCleared { held, .. }) => {
+                if !held.is_empty() {
+                    self.log(format!("🛑 {} failed entries left {held:?}, selling", self.asset));
+                    let initial = held[0].0.clone();
+                    return Self::start_flattening(CloseReason::Flatten, &initial, held);
+                }
+                let trade_allowed =
+                    allow_trade_at(market.timestamp, self.settings.dont_allow_trade_before, now);
+                let stop = if self.halted {
+                    "halted before entry"
+                } else if failures >= ENTRY_ATTEMPTS {
+                    "gave up on entries that kept failing"
+                } else if !trade_allowed {
+                    "too close to the market start to enter again"
+                } else {
+                    let actions = vec![self.place_entries(&market)];
+                    let state = CycleState::AwaitingMarket {
+                        market: Some(market),
+                        failures,
+                    };
+                    return (state, actions);
+                };
+                self.log(format!("🛑 {} {stop}", self.asset));
+                (CycleState::Done(CycleOutcome::NoFill), vec![])
+            }
+            // a failed placement may have left an order on the book, or a filled one, so
+            // that is cleared before the next attempt and before the cycle stops
+            (Some(market), CycleEvent::Tick | CycleEvent::Halt | CycleEvent::Flatten)
+                if failures > 0 =>
+            {
+                let actions = vec![CycleAction::ClearEntries {
+                    tokens: market.tokens.clone(),
+                }];
+                let state = CycleState::AwaitingMarket {
+                    market: Some(market),
+                    failures,
+                };
+                (state, actions)
+            }
+            _ if self.halted => {
+                self.log(format!("🛑 {} halted before entry", self.asset));
+                (CycleState::Done(CycleOutcome::NoFill), vec![])
+            }
+            (_, CycleEvent::MarketOpened(market)) => {
+                let actions = vec![self.place_entries(&market)];
+                let state = CycleState::AwaitingMarket {
+                    market: Some(market),
+                    failures,
+                };
+                (state, actions)
+            }
+            (Some(market), CycleEvent::EntriesPlaced([first, second])) => (
+                CycleState::EntryResting {
+                    market,
+                    first: first.order_id,
+                    second: second.order_id,
+                    stopping: false,
+                },
+                vec![CycleAction::Wait(SETTLE)],
+            ),
+            (Some(market), CycleEvent::EntriesFailed { retry }) => (
+                CycleState::AwaitingMarket {
+                    market: Some(market),
+                    failures: if retry { failures + 1 } else { ENTRY_ATTEMPTS },
+                },
+                vec![],
+            ),
+            (market, _) => (CycleState::AwaitingMarket { market, failures }, vec![]),
+        }
+    }
+
+    fn place_entries(&self, market: &Market) -> CycleAction {
+        CycleAction::PlaceEntries {
+            tokens: market.tokens.clone(),
+            size: self.settings.order_size,
+            prices: market.prices,
+        }
+    }
+
+    fn entry_resting(
+        &self,
+        market: Market,
+        first: String,
+        second: String,
+        stopping: bool,
+        event: CycleEvent,
+        now: i64,
+    ) -> (CycleState, Vec<CycleAction>) {
+        let resting = |stopping| CycleState::EntryResting {
+            market: market.clone(),
+            first: first.clone(),
+            second: second.clone(),
+            stopping,
+        };
+        let CycleEvent::Orders(orders) = &event else {
+            return (resting(stopping), vec![]);
+        };
+        let (Some(first_order), Some(second_order)) = (find(orders, &first), find(orders, &second))
+        else {
+            return (resting(stopping), vec![]);
+        };
+
+        if stopping {
+            return self.entry_stopped(&market, first_order, second_order, orders);
+        }
+        if self.halted {
+            self.log(format!("🛑 Stopping, cancelling {} entry orders", self.asset));
+            let mut actions = vec![];
+            for order in [first_order, second_order] {
+                if order.status == OrderStatusType::Live {
+                    self.count(&ORDERS_CANCELLED_TOTAL);
+                    actions.push(CycleAction::Cancel(order.order_id.clone()));
+                }
+            }
+            return (resting(true), actions);
+        }
+
+        // if left lest than grace_seconds till market open we don't want to wait anymore to open positions
+        let is_holding_allowed =
+            allow_trade_at(market.timestamp, self.settings.dont_allow_holding_before, now);
+        self.log(format!(
+            "Holding allowed: {}, first: {}, second: {}",
+            is_holding_allowed, first_order.status, second_order.status
+        ));
+
+        let tokens = &market.tokens;
+        let legs = [
+            (first_order, &tokens.first_asset_id, second_order, &tokens.second_asset_id),
+            (second_order, &tokens.second_asset_id, first_order, &tokens.first_asset_id),
+        ];
+
+        for (leg, leg_token, other, other_token) in legs {
+            if leg.status == OrderStatusType::Matched {
+                self.log(format!("Entry order matched: {:?}", leg));
+                self.count(&ORDERS_TOTAL);
+                self.count(&ORDERS_TOTAL);
+                self.count(&ORDERS_MATCHED_TOTAL);
+
+                let close_size = normalized_size(leg.size_matched, self.settings.order_size);
+                let hedge = self.hedge_config(
+                    market.timestamp,
+                    leg_token,
+                    other_token,
+                    &other.order_id,
+                    self.settings.order_size,
+                    close_size,
+                );
+                self.log("Cancelling another order...");
+                return (
+                    CycleState::LegMatched {
+                        hedge,
+                        held_order: None,
+                        cancel_sent: true,
+                    },
+                    vec![CycleAction::Cancel(other.order_id.clone())],
+                );
+            }
+        }
+
+        if first_order.status == OrderStatusType::Canceled
+            && second_order.status == OrderStatusType::Canceled
+        {
+            self.log(format!(
+                "Orders were canceled: first: {:?}, second: {:?}",
+                first_order, second_order
+            ));
+            // a fill left on a cancelled entry is nobody's to manage anymore
+            return (self.done(CycleOutcome::CloseFailed), vec![]);
+        }
+
+        // --- PREVENT HOLDING ---
+        let mut actions = vec![];
+        if !is_holding_allowed {
+            for (leg, leg_token, other, other_token) in legs {
+                if leg.status != OrderStatusType::Live {
+                    continue;
+                }
+                self.count(&ORDERS_TOTAL);
+                self.count(&ORDERS_CANCELLED_TOTAL);
+                actions.push(CycleAction::Cancel(leg.order_id.clone()));
+
+                if leg.size_matched.is_zero() {
+                    self.log("No open position, going to cancel it");
+                    continue;
+                }
+
+                self.count(&ORDERS_PARTIAL_TOTAL);
+                self.log("Time's up to wait for the entry order, cancelling it and hedging its fill");
+                let size = normalized_size(leg.size_matched, self.settings.order_size);
+                let hedge = self.hedge_config(
+                    market.timestamp,
+                    leg_token,
+                    other_token,
+                    &other.order_id,
+                    size,
+                    size,
+                );
+                return (
+                    CycleState::LegMatched {
+                        hedge,
+                        held_order: Some(leg.order_id.clone()),
+                        cancel_sent: false,
+                    },
+                    actions,
+                );
+            }
+        }
+        (resting(false), actions)
+    }
+
+    /// entries were cancelled for a halt, the bigger fill is the position and a fill
+    /// on the other side counts against its hedge
+    fn entry_stopped(
+        &self,
+        market: &Market,
+        first: &OrderState,
+        second: &OrderState,
+        orders: &[OrderState],
+    ) -> (CycleState, Vec<CycleAction>) {
+        let tokens = &market.tokens;
+        let (held, other, held_token, other_token) = if first.size_matched >= second.size_matched {
+            (first, second, &tokens.first_asset_id, &tokens.second_asset_id)
+        } else {
+            (second, first, &tokens.second_asset_id, &tokens.first_asset_id)
+        };
+        if held.size_matched.is_zero() {
+            self.log("🛑 No entry order was filled, nothing to unwind");
+            return (CycleState::Done(CycleOutcome::NoFill), vec![]);
+        }
+
+        let size = normalized_size(held.size_matched, self.settings.order_size);
+        let hedge = self.hedge_config(
+            market.timestamp,
+            held_token,
+            other_token,
+            &other.order_id,
+            size,
+            size,
+        );
+        self.leg_matched(hedge, None, true, CycleEvent::Orders(orders.to_vec()))
+    }
+
+    fn hedge_config(
+        &self,
+        timestamp: i64,
+        held_token: &str,
+        other_token: &str,
+        other_order_id: &str,
+        hedge_size: Decimal,
+        close_size: Decimal,
+    ) -> HedgeConfig {
+        HedgeConfig {
+            stop_loss_after: self.settings.stop_loss_after,
+            asset: self.asset,
+            second_order_id: other_order_id.to_string(),
+            hedge_asset_id: other_token.to_string(),
+            initial_asset_id: held_token.to_string(),
+            hedge_size,
+            hedge_enter_price: self.settings.hedge_enter_price,
+            close_size,
+            timestamp,
+        }
+    }
+
+    fn leg_matched(
+        &self,
+        mut hedge: HedgeConfig,
+        held_order: Option<String>,
+        cancel_sent: bool,
+        event: CycleEvent,
+    ) -> (CycleState, Vec<CycleAction>) {
+        let waiting = |hedge, cancel_sent| CycleState::LegMatched {
+            hedge,
+            held_order: held_order.clone(),
+            cancel_sent,
+        };
+        let CycleEvent::Orders(orders) = &event else {
+            return (waiting(hedge, cancel_sent), vec![]);
+        };
+        let Some(other) = find(orders, &hedge.second_order_id) else {
+            return (waiting(hedge, cancel_sent), vec![]);
+        };
+
+        if other.status != OrderStatusType::Canceled && !cancel_sent {
+            self.log("Cancelling second order...");
+            self.count(&ORDERS_CANCELLED_TOTAL);
+            let cancel = CycleAction::Cancel(other.order_id.clone());
+            return (waiting(hedge, true), vec![cancel]);
+        }
+
+        if let Some(held_order) = &held_order {
+            let Some(held) = find(orders, held_order) else {
+                return (waiting(hedge, cancel_sent), vec![]);
+            };
+            let size = normalized_size(held.size_matched, hedge.hedge_size);
+            hedge.hedge_size = size;
+            hedge.close_size = size;
+        }
+
+        let mut hedge_size = hedge.hedge_size;
+        let mut closes = vec![];
+        if other.size_matched > Decimal::ZERO {
+            self.count(&ORDERS_PARTIAL_TOTAL);
+            let closing_second_size = floor_dp(other.size_matched, 2);
+            self.log(format!(
+                "Second order partially matched with size: {}",
+                closing_second_size
+            ));
+            hedge_size = (closing_second_size - hedge.hedge_size).abs();
+            closes.push((hedge.hedge_asset_id.clone(), closing_second_size));
+        }
+
+        if self.flatten {
+            self.log("🛑 Flattening instead of hedging");
+            closes.push((hedge.initial_asset_id.clone(), hedge.close_size));
+            return Self::start_flattening(CloseReason::Flatten, &hedge.initial_asset_id, closes);
+        }
+        if hedge_size.is_zero() {
+            self.log("Both legs filled, nothing left to hedge");
+            return (self.done(CycleOutcome::Hedged), vec![]);
+        }
+
+        self.log(format!("Opening hedge with size = {}", hedge_size));
+        let place = CycleAction::PlaceHedge {
+            token_id: hedge.hedge_asset_id.clone(),
+            size: hedge_size,
+            price: hedge.hedge_enter_price,
+        };
+        (
+            CycleState::Hedging {
+                hedge,
+                order_id: None,
+                size: hedge_size,
+            },
+            vec![place],
+        )
+    }
+
+    fn hedging(
+        &self,
+        hedge: HedgeConfig,
+        order_id: Option<String>,
+        size: Decimal,
+        event: CycleEvent,
+        now: i64,
+    ) -> (CycleState, Vec<CycleAction>) {
+        let Some(order_id) = order_id else {
+            if let CycleEvent::HedgePlaced(order) = event {
+                self.log(format!("Hedge order placed: {:?}", order));
+                let state = CycleState::Hedging {
+                    hedge,
+                    order_id: Some(order.order_id),
+                    size,
+                };
+                return (state, vec![CycleAction::Wait(SETTLE)]);
+            }
+            // the hedge never made it to the book, only the held leg is left to sell
+            if matches!(event, CycleEvent::Tick) && self.flatten {
+                self.log("🛑 Flattening position without a hedge order...");
+                let closes = vec![(hedge.initial_asset_id.clone(), hedge.close_size)];
+                return Self::start_flattening(CloseReason::Flatten, &hedge.initial_asset_id, closes);
+            }
+            return (
+                CycleState::Hedging {
+                    hedge,
+                    order_id: None,
+                    size,
+                },
+                vec![],
+            );
+        };
+
+        match &event {
+            CycleEvent::Orders(orders) => {
+                if let Some(order) = find(orders, &order_id) {
+                    self.log(format!("Hedge order status: {:?}", order.status));
+                    if order.status == OrderStatusType::Matched {
+                        self.count(&HEDGE_ORDERS_MATCHED_TOTAL);
+                        self.log("Hedge order matched");
+                        return (self.done(CycleOutcome::Hedged), vec![]);
+                    }
+                }
+            }
+            CycleEvent::Tick if self.flatten => {
+                self.log("🛑 Cancelling hedge order and flattening position...");
+                return self.cancel_hedge(hedge, order_id, size, CloseReason::Flatten);
+            }
+            CycleEvent::Tick
+                if allow_stop_loss_at(
+                    hedge.timestamp,
+                    self.settings.market.end(hedge.timestamp),
+                    hedge.stop_loss_after,
+                    now,
+                ) =>
+            {
+                self.count(&STOP_LOSS_TOTAL);
+                self.log("Stop loss reached, cancelling hedge order and closing position...");
+                return self.cancel_hedge(hedge, order_id, size, CloseReason::StopLoss);
+            }
+            _ => {}
+        }
+        (
+            CycleState::Hedging {
+                hedge,
+                order_id: Some(order_id),
+                size,
+            },
+            vec![],
+        )
+    }
+
+    fn cancel_hedge(
+        &self,
+        hedge: HedgeConfig,
+        order_id: String,
+        size: Decimal,
+        reason: CloseReason,
+    ) -> (CycleState, Vec<CycleAction>) {
+        self.count(&HEDGE_ORDERS_CANCELLED_TOTAL);
+        let actions = vec![
+            CycleAction::Cancel(order_id.clone()),
+            CycleAction::Wait(CANCEL_SETTLE),
+        ];
+        (
+            CycleState::StopLoss {
+                hedge,
+                order_id,
+                size,
+                reason,
+            },
+            actions,
+        )
+    }
+
+    fn stop_loss(
+        &self,
+        hedge: HedgeConfig,
+        order_id: String,
+        size: Decimal,
+        reason: CloseReason,
+        event: CycleEvent,
+    ) -> (CycleState, Vec<CycleAction>) {
+        let order = match &event {
+            CycleEvent::Orders(orders) => find(orders, &order_id),
+            _ => None,
+        };
+        let Some(order) = order else {
+            return (
+                CycleState::StopLoss {
+                    hedge,
+                    order_id,
+                    size,
+                    reason,
+                },
+                vec![],
+            );
+        };
+
+        let mut closes = vec![];
+        if order.size_matched > Decimal::ZERO && order.size_matched != size {
+            self.count(&HEDGE_ORDERS_PARTIAL_TOTAL);
+            self.log("Hedge order partially matched, closing it...");
+            let closing_hedge_size = normalized_size(order.size_matched, size);
+            closes.push((hedge.hedge_asset_id.clone(), closing_hedge_size));
+        }
+        closes.push((hedge.initial_asset_id.clone(), hedge.close_size));
+        Self::start_flattening(reason, &hedge.initial_asset_id, closes)
+    }
+
+    fn start_flattening(
+        reason: CloseReason,
+        initial: &str,
+        closes: Vec<(String, Decimal)>,
+    ) -> (CycleState, Vec<CycleAction>) {
+        let state = CycleState::Flattening {
+            reason,
+            initial: initial.to_string(),
+            pending: closes.len(),
+            failed: false,
+        };
+        let actions = closes
+            .into_iter()
+            .map(|(token_id, size)| CycleAction::Close { token_id, size })
+            .collect();
+        (state, actions)
+    }
+
+    fn flattening(
+        &self,
+        reason: CloseReason,
+        initial: String,
+        pending: usize,
+        mut failed: bool,
+        event: CycleEvent,
+    ) -> (CycleState, Vec<CycleAction>) {
+        if let CycleEvent::Closed { token_id, sold } = event {
+            // a failed close of the hedge side doesn't change the outcome, the held leg does
+            if token_id == initial && sold.is_none() {
+                failed = true;
+            }
+            let pending = pending.saturating_sub(1);
+            if pending == 0 {
+                let state = match (failed, reason) {
+                    (true, _) => self.done(CycleOutcome::CloseFailed),
+                    (false, CloseReason::StopLoss) => self.done(CycleOutcome::StopLoss),
+                    (false, CloseReason::Flatten) => self.done(CycleOutcome::Flattened),
+                };
+                return (state, vec![]);
+            }
+            return (
+                CycleState::Flattening {
+                    reason,
+                    initial,
+                    pending,
+                    failed,
+                },
+                vec![],
+            );
+        }
+        (
+            CycleState::Flattening {
+                reason,
+                initial,
+                pending,
+                failed,
+            },
+            vec![],
+        )
+    }
+}
+
+fn find<'a>(orders: &'a [OrderState], order_id: &str) -> Option<&'a OrderState> {
+    orders.iter().find(|o| o.order_id == order_id)
+}
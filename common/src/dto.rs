@@ -1,16 +1,12 @@
+use crate::asset::Asset;
+use polymarket_client_sdk::clob::types::OrderStatusType;
 use rust_decimal::Decimal;
-use serde;
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::fmt::{Display, Formatter, write};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize)]
-pub struct MarketApiResponse {
-    #[serde(rename = "clobTokenIds")]
-    pub clob_token_ids: String,
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MarketResponse {
     pub first_asset_id: String,
     pub second_asset_id: String,
@@ -22,7 +18,103 @@ pub struct OrderResponse {
     pub order_id: String,
 }
 
+/// exchange-agnostic view of an order, what the strategy polls for
+#[derive(Debug, Clone)]
+pub struct OrderState {
+    pub order_id: String,
+    pub token_id: String,
+    pub status: OrderStatusType,
+    pub original_size: Decimal,
+    pub size_matched: Decimal,
+    pub price: Decimal,
+}
+
+/// USDC of the account and how much of it the exchange contract may move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collateral {
+    pub balance: Decimal,
+    /// `Decimal::MAX` for an unlimited approval
+    pub allowance: Decimal,
+}
+
+/// result of an immediate (FOK) order, `error_msg` is set when it was not filled
 #[derive(Debug, Clone)]
+pub struct MarketOrderResponse {
+    pub order_id: String,
+    pub error_msg: Option<String>,
+    pub making_amount: Decimal,
+    pub taking_amount: Decimal,
+}
+
+impl MarketOrderResponse {
+    /// shares and average price of a filled market sell, which makes shares and takes USDC
+    pub fn sold(&self) -> Fill {
+        Fill {
+            size: self.making_amount,
+            price: if self.making_amount.is_zero() {
+                Decimal::ZERO
+            } else {
+                self.taking_amount / self.making_amount
+            },
+        }
+    }
+}
+
+/// shares and the average price they traded at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub size: Decimal,
+    pub price: Decimal,
+}
+
+impl Fill {
+    pub fn notional(&self) -> Decimal {
+        self.size * self.price
+    }
+
+    /// adds `other`, the price stays the average
+    pub fn add(&mut self, other: Fill) {
+        let size = self.size + other.size;
+        if !size.is_zero() {
+            self.price = (self.notional() + other.notional()) / size;
+        }
+        self.size = size;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// L2 book of one token, bids best (highest) first, asks best (lowest) first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+impl BookSnapshot {
+    pub fn best_bid(&self) -> Option<Decimal> {
+        self.bids.first().map(|l| l.price)
+    }
+
+    pub fn best_ask(&self) -> Option<Decimal> {
+        self.asks.first().map(|l| l.price)
+    }
+
+    /// shares resting on the bid side
+    pub fn bid_depth(&self) -> Decimal {
+        self.bids.iter().map(|l| l.size).sum()
+    }
+
+    pub fn ask_depth(&self) -> Decimal {
+        self.asks.iter().map(|l| l.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HedgeConfig {
     pub stop_loss_after: i64,
     pub asset: Asset,
@@ -35,31 +127,32 @@ pub struct HedgeConfig {
     pub timestamp: i64,
 }
 
-#[derive(Debug, Clone)]
-pub struct PreventHoldingConfig {
-    pub hedge_config: HedgeConfig,
-    pub order_id: String,
+/// live sends real orders, paper fills them against a simulated book with fake balances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradingMode {
+    Live,
+    Paper,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Asset {
-    BTC,
-    ETH,
-    SOL,
-    XRP,
-    #[serde(other)]
-    Unknown,
-}
-
-impl Display for Asset {
+impl Display for TradingMode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let s = match self {
-            Asset::BTC => "btc",
-            Asset::ETH => "eth",
-            Asset::SOL => "sol",
-            Asset::XRP => "xrp",
-            Asset::Unknown => "UNKNOWN",
+            TradingMode::Live => "live",
+            TradingMode::Paper => "paper",
         };
         write!(f, "{s}")
     }
 }
+
+impl FromStr for TradingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "live" => Ok(TradingMode::Live),
+            "paper" => Ok(TradingMode::Paper),
+            other => Err(format!("unknown trading mode: {other}")),
+        }
+    }
+}
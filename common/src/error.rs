@@ -0,0 +1,113 @@
+//! What went wrong and what the bot does about it.
+
+use reqwest::StatusCode;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// what happens after a [`BotError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// the step is tried again, nothing is lost by waiting
+    Retry,
+    /// the cycle is wound down: resting orders cancelled, held shares sold
+    AbortCycle,
+    /// nothing works until someone looks at it, the process exits
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum BotError {
+    /// network trouble, timeouts, rate limits and 5xx, also a market Gamma doesn't list yet
+    Transient(String),
+    /// the exchange refused the request, e.g. not enough balance or a bad price
+    Rejected(String),
+    /// data that must not look like this, e.g. a market without two tokens
+    Invariant(String),
+    /// missing or bad keys and credentials, a store that can't be read or written
+    Fatal(String),
+}
+
+impl BotError {
+    pub fn policy(&self) -> ErrorPolicy {
+        match self {
+            BotError::Transient(_) => ErrorPolicy::Retry,
+            BotError::Rejected(_) | BotError::Invariant(_) => ErrorPolicy::AbortCycle,
+            BotError::Fatal(_) => ErrorPolicy::Stop,
+        }
+    }
+
+    fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => BotError::Fatal(message),
+            StatusCode::NOT_FOUND
+            | StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS => BotError::Transient(message),
+            s if s.is_server_error() => BotError::Transient(message),
+            _ => BotError::Rejected(message),
+        }
+    }
+}
+
+impl Display for BotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Transient(message) => write!(f, "transient: {message}"),
+            BotError::Rejected(message) => write!(f, "rejected: {message}"),
+            BotError::Invariant(message) => write!(f, "invariant violated: {message}"),
+            BotError::Fatal(message) => write!(f, "fatal: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+impl From<polymarket_client_sdk::error::Error> for BotError {
+    fn from(e: polymarket_client_sdk::error::Error) -> Self {
+        use polymarket_client_sdk::error::{Kind, Status};
+
+        let message = e.to_string();
+        if let Some(status) = e.downcast_ref::<Status>() {
+            return BotError::from_status(status.status_code, message);
+        }
+        if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+            return classify_reqwest(e, message);
+        }
+        match e.kind() {
+            Kind::Synchronization | Kind::WebSocket => BotError::Transient(message),
+            // checked by the client before anything is sent, e.g. a price off the tick size
+            Kind::Validation => BotError::Rejected(message),
+            _ => BotError::Invariant(message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for BotError {
+    fn from(e: reqwest::Error) -> Self {
+        let message = e.to_string();
+        classify_reqwest(&e, message)
+    }
+}
+
+/// whether the request that failed with `e` surely never reached the exchange: no connection
+/// was made or the rate limit turned it away. After anything else it may have gone through
+pub fn never_sent(e: &polymarket_client_sdk::error::Error) -> bool {
+    if let Some(status) = e.downcast_ref::<polymarket_client_sdk::error::Status>() {
+        return status.status_code == StatusCode::TOO_MANY_REQUESTS;
+    }
+    e.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_connect())
+}
+
+fn classify_reqwest(e: &reqwest::Error, message: String) -> BotError {
+    match e.status() {
+        Some(status) => BotError::from_status(status, message),
+        // a body that doesn't parse is not going to parse on the next try
+        None if e.is_decode() => BotError::Invariant(message),
+        None => BotError::Transient(message),
+    }
+}
+
+impl From<rusqlite::Error> for BotError {
+    fn from(e: rusqlite::Error) -> Self {
+        BotError::Fatal(format!("store: {e}"))
+    }
+}
@@ -0,0 +1,260 @@
+use crate::dto::{Collateral, MarketOrderResponse, OrderResponse, OrderState};
+use crate::utils::timed_request;
+use alloy::signers::k256::ecdsa::SigningKey;
+use alloy::signers::local::LocalSigner;
+use alloy_primitives::U256;
+use async_trait::async_trait;
+use polymarket_client_sdk::auth::Normal;
+use polymarket_client_sdk::auth::state::Authenticated;
+use polymarket_client_sdk::clob::Client;
+use polymarket_client_sdk::clob::types::{
+    Amount, AssetType, BalanceAllowanceRequest, OrderType, OrdersRequest, PriceRequest, Side,
+};
+use polymarket_client_sdk::error::Error;
+use polymarket_client_sdk::{POLYGON, contract_config};
+use rust_decimal::Decimal;
+use std::str::FromStr as _;
+use std::sync::Arc;
+
+/// Everything the strategy needs from a venue. The live CLOB is one implementation,
+/// fakes and simulators are others.
+#[async_trait]
+pub trait Exchange: Send + Sync {
+    /// GTC limit order, rests on the book until matched or cancelled
+    async fn place_limit_order(
+        &self,
+        token_id: &str,
+        size: Decimal,
+        price: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<OrderResponse>;
+
+    /// FOK market order for `shares`, either fully filled or rejected
+    async fn place_market_order(
+        &self,
+        token_id: &str,
+        shares: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<MarketOrderResponse>;
+
+    async fn get_order(&self, order_id: &str) -> polymarket_client_sdk::Result<OrderState>;
+
+    async fn cancel_order(&self, order_id: &str) -> polymarket_client_sdk::Result<()>;
+
+    /// best price we would get if we were to trade on `side`
+    async fn get_price(&self, token_id: &str, side: Side) -> polymarket_client_sdk::Result<Decimal>;
+
+    /// every order of the account that still rests on the book
+    async fn open_orders(&self) -> polymarket_client_sdk::Result<Vec<OrderState>>;
+
+    /// shares of `token_id` the account holds
+    async fn token_balance(&self, token_id: &str) -> polymarket_client_sdk::Result<Decimal>;
+
+    /// USDC the funder address holds and the approval of the exchange contract of a regular
+    /// or a neg risk market
+    async fn collateral(&self, neg_risk: bool) -> polymarket_client_sdk::Result<Collateral>;
+
+    /// fee rate of the token's market in basis points, charged to takers
+    async fn fee_rate_bps(&self, token_id: &str) -> polymarket_client_sdk::Result<u32>;
+}
+
+/// end of the paginated endpoints, base64 of -1
+const TERMINAL_CURSOR: &str = "LTE=";
+
+/// USDC and outcome tokens both have 6 decimals, balances come in base units
+const TOKEN_DECIMALS: u32 = 6;
+
+pub struct PolymarketExchange {
+    client: Arc<Client<Authenticated<Normal>>>,
+    signer: LocalSigner<SigningKey>,
+}
+
+impl PolymarketExchange {
+    pub fn new(client: Arc<Client<Authenticated<Normal>>>, signer: LocalSigner<SigningKey>) -> Self {
+        PolymarketExchange { client, signer }
+    }
+
+    pub fn client(&self) -> &Arc<Client<Authenticated<Normal>>> {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl Exchange for PolymarketExchange {
+    async fn place_limit_order(
+        &self,
+        token_id: &str,
+        size: Decimal,
+        price: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<OrderResponse> {
+        let order = self
+            .client
+            .limit_order()
+            .token_id(token_id)
+            .size(size)
+            .price(price)
+            .side(side)
+            .order_type(OrderType::GTC)
+            .build()
+            .await?;
+
+        let signed_order = self.client.sign(&self.signer, order).await?;
+        let response = timed_request(
+            "polymarket",
+            "place_limit_order",
+            self.client.post_order(signed_order),
+        )
+        .await?;
+        let response = response
+            .first()
+            .ok_or_else(|| Error::validation("empty response on limit order"))?;
+
+        Ok(OrderResponse {
+            token_id: token_id.to_string(),
+            order_id: response.order_id.clone(),
+        })
+    }
+
+    async fn place_market_order(
+        &self,
+        token_id: &str,
+        shares: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<MarketOrderResponse> {
+        let market_order = self
+            .client
+            .market_order()
+            .token_id(token_id)
+            .amount(Amount::shares(shares)?)
+            .side(side)
+            .order_type(OrderType::FOK)
+            .build()
+            .await?;
+        let signed_order = self.client.sign(&self.signer, market_order).await?;
+        let response = timed_request(
+            "polymarket",
+            "place_market_order",
+            self.client.post_order(signed_order),
+        )
+        .await?;
+        let response = response
+            .first()
+            .ok_or_else(|| Error::validation("empty response on market order"))?;
+
+        Ok(MarketOrderResponse {
+            order_id: response.order_id.clone(),
+            error_msg: response.error_msg.clone(),
+            making_amount: response.making_amount,
+            taking_amount: response.taking_amount,
+        })
+    }
+
+    async fn get_order(&self, order_id: &str) -> polymarket_client_sdk::Result<OrderState> {
+        let order = timed_request("polymarket", "get_order", self.client.order(order_id)).await?;
+
+        Ok(OrderState {
+            order_id: order.id,
+            token_id: order.asset_id,
+            status: order.status,
+            original_size: order.original_size,
+            size_matched: order.size_matched,
+            price: order.price,
+        })
+    }
+
+    async fn cancel_order(&self, order_id: &str) -> polymarket_client_sdk::Result<()> {
+        timed_request(
+            "polymarket",
+            "cancel_order",
+            self.client.cancel_order(order_id),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_price(&self, token_id: &str, side: Side) -> polymarket_client_sdk::Result<Decimal> {
+        let price_request = PriceRequest::builder().token_id(token_id).side(side).build();
+
+        let response = timed_request("polymarket", "price", self.client.price(&price_request)).await?;
+        Ok(response.price)
+    }
+
+    async fn open_orders(&self) -> polymarket_client_sdk::Result<Vec<OrderState>> {
+        let request = OrdersRequest::default();
+        let mut orders = vec![];
+        let mut cursor = None;
+        loop {
+            let page = timed_request(
+                "polymarket",
+                "orders",
+                self.client.orders(&request, cursor.clone()),
+            )
+            .await?;
+            orders.extend(page.data.into_iter().map(|order| OrderState {
+                order_id: order.id,
+                token_id: order.asset_id,
+                status: order.status,
+                original_size: order.original_size,
+                size_matched: order.size_matched,
+                price: order.price,
+            }));
+            if page.next_cursor.is_empty() || page.next_cursor == TERMINAL_CURSOR {
+                return Ok(orders);
+            }
+            cursor = Some(page.next_cursor);
+        }
+    }
+
+    async fn token_balance(&self, token_id: &str) -> polymarket_client_sdk::Result<Decimal> {
+        let request = BalanceAllowanceRequest::builder()
+            .asset_type(AssetType::Conditional)
+            .token_id(token_id)
+            .build();
+        let response = timed_request(
+            "polymarket",
+            "balance_allowance",
+            self.client.balance_allowance(&request),
+        )
+        .await?;
+        Ok(Decimal::new(1, TOKEN_DECIMALS) * response.balance)
+    }
+
+    async fn collateral(&self, neg_risk: bool) -> polymarket_client_sdk::Result<Collateral> {
+        let request = BalanceAllowanceRequest::builder()
+            .asset_type(AssetType::Collateral)
+            .build();
+        let response = timed_request(
+            "polymarket",
+            "balance_allowance",
+            self.client.balance_allowance(&request),
+        )
+        .await?;
+        let exchange = contract_config(POLYGON, neg_risk).map(|config| config.exchange);
+        // no approval for the contract is none at all, a max uint256 one doesn't fit a Decimal.
+        // An allowance that doesn't parse counts as none, the cycle is skipped rather than
+        // trading on an approval nobody could read
+        let allowance = exchange
+            .and_then(|exchange| response.allowances.get(&exchange))
+            .map_or(Decimal::ZERO, |allowance| match U256::from_str(allowance) {
+                Ok(base) => i128::try_from(base)
+                    .ok()
+                    .and_then(|base| Decimal::try_from_i128_with_scale(base, TOKEN_DECIMALS).ok())
+                    .unwrap_or(Decimal::MAX),
+                Err(e) => {
+                    eprintln!("Failed to parse the allowance {allowance:?}: {e}, counting none");
+                    Decimal::ZERO
+                }
+            });
+        Ok(Collateral {
+            balance: Decimal::new(1, TOKEN_DECIMALS) * response.balance,
+            allowance,
+        })
+    }
+
+    async fn fee_rate_bps(&self, token_id: &str) -> polymarket_client_sdk::Result<u32> {
+        let response =
+            timed_request("polymarket", "fee_rate", self.client.fee_rate_bps(token_id)).await?;
+        Ok(response.base_fee)
+    }
+}
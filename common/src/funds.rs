@@ -0,0 +1,143 @@
+//! Whether the account can pay for a cycle before it starts. Both entries rest at once and
+//! the hedge is bought while they do, all with USDC the exchange contract must be approved
+//! to move, an account short of either only gets its orders rejected.
+
+use crate::Asset;
+use crate::dto::Collateral;
+use crate::metrics::{BALANCE_LOW, COLLATERAL_BALANCE};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive as _;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// whether the last balance seen was below the alert threshold
+static LOW: AtomicBool = AtomicBool::new(false);
+
+/// what a cycle is short of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortfall {
+    Balance { have: Decimal, need: Decimal },
+    Allowance { have: Decimal, need: Decimal },
+}
+
+impl Shortfall {
+    /// `reason` label of the skipped cycle
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Shortfall::Balance { .. } => "insufficient_balance",
+            Shortfall::Allowance { .. } => "insufficient_allowance",
+        }
+    }
+}
+
+impl Display for Shortfall {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Shortfall::Balance { have, need } => {
+                write!(f, "the balance of {have} USDC is short of the {need} the cycle needs")
+            }
+            Shortfall::Allowance { have, need } => write!(
+                f,
+                "the exchange may only move {have} USDC of the {need} the cycle needs, approve it again"
+            ),
+        }
+    }
+}
+
+/// USDC one share of a cycle may tie up: a partly filled entry is still held while the
+/// hedge is bought for the whole size
+pub fn collateral_per_share(prices: [Decimal; 2], hedge_price: Decimal) -> Decimal {
+    prices[0] + prices[1] + hedge_price
+}
+
+/// USDC for `size` shares of both entries and the hedge
+pub fn required_collateral(size: Decimal, prices: [Decimal; 2], hedge_price: Decimal) -> Decimal {
+    size * collateral_per_share(prices, hedge_price)
+}
+
+/// what is left of `collateral` once `reserved` is set aside
+pub fn available(collateral: &Collateral, reserved: Decimal) -> Collateral {
+    Collateral {
+        balance: (collateral.balance - reserved).max(Decimal::ZERO),
+        allowance: (collateral.allowance - reserved).max(Decimal::ZERO),
+    }
+}
+
+/// Collateral the running cycle of each asset set aside. The balance only drops once orders
+/// fill, what the resting entries and the hedge of another asset will spend is still in it.
+/// Filled shares stay set aside until their cycle ends, which errs on the safe side
+#[derive(Clone, Default)]
+pub struct Reservations {
+    reserved: Arc<Mutex<HashMap<Asset, Decimal>>>,
+}
+
+impl Reservations {
+    fn reserved(&self) -> MutexGuard<'_, HashMap<Asset, Decimal>> {
+        self.reserved.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// set aside by the cycles of every asset but `asset`
+    pub fn others(&self, asset: Asset) -> Decimal {
+        self.reserved()
+            .iter()
+            .filter(|(other, _)| **other != asset)
+            .map(|(_, amount)| *amount)
+            .sum()
+    }
+
+    /// sets `amount` aside for the cycle of `asset` until the returned guard is dropped
+    pub fn reserve(&self, asset: Asset, amount: Decimal) -> Reservation {
+        self.reserved().insert(asset, amount);
+        Reservation {
+            reservations: self.clone(),
+            asset,
+        }
+    }
+}
+
+/// collateral of a running cycle, see [`Reservations::reserve`]
+pub struct Reservation {
+    reservations: Reservations,
+    asset: Asset,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.reservations.reserved().remove(&self.asset);
+    }
+}
+
+/// what the account lacks to pay `need`, `None` when it can
+pub fn shortfall(collateral: &Collateral, need: Decimal) -> Option<Shortfall> {
+    if collateral.balance < need {
+        return Some(Shortfall::Balance {
+            have: collateral.balance,
+            need,
+        });
+    }
+    if collateral.allowance < need {
+        return Some(Shortfall::Allowance {
+            have: collateral.allowance,
+            need,
+        });
+    }
+    None
+}
+
+/// Exports `balance` and alerts once when it drops below `alert_below`, and once more when it
+/// is back. A zero threshold never alerts
+pub fn watch_balance(balance: Decimal, alert_below: Decimal) {
+    COLLATERAL_BALANCE.set(balance.to_f64().unwrap_or_default());
+    let low = balance < alert_below;
+    BALANCE_LOW.set(low as i64);
+    if LOW.swap(low, Ordering::SeqCst) == low {
+        return;
+    }
+    if low {
+        eprintln!("🚨 Balance is down to {balance} USDC, below the alert threshold of {alert_below}");
+    } else {
+        println!("💰 Balance is back at {balance} USDC, the alert threshold is {alert_below}");
+    }
+}
@@ -0,0 +1,203 @@
+//! Market discovery on the Gamma API: the market of a family starting at a timestamp, with
+//! its outcomes, tokens, schedule and trading rules checked before a cycle trades it.
+
+use crate::asset::Asset;
+use crate::dto::MarketResponse;
+use crate::error::BotError;
+use crate::market::MarketFamily;
+use crate::settings::StrategySettings;
+use crate::utils::timed_request;
+use chrono::{DateTime, Utc};
+use reqwest::{Client as http_client, StatusCode};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+pub const GAMMA_URL: &str = "https://gamma-api.polymarket.com";
+
+/// a market as Gamma lists it, lists of outcomes and token ids come as JSON in a string
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMarket {
+    slug: String,
+    #[serde(default)]
+    outcomes: String,
+    #[serde(default)]
+    clob_token_ids: String,
+    /// start of the up/down window, `startDate` is when the market was listed
+    event_start_time: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+    order_price_min_tick_size: Option<Decimal>,
+    order_min_size: Option<Decimal>,
+    #[serde(default)]
+    neg_risk: bool,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    closed: bool,
+    #[serde(default)]
+    accepting_orders: bool,
+    #[serde(default)]
+    enable_order_book: bool,
+}
+
+/// where a listed market stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketStatus {
+    /// orders go on the book
+    Open,
+    /// listed but no orders are taken yet
+    Pending,
+    Closed,
+}
+
+/// a market checked against the family it was looked up for
+#[derive(Debug, Clone)]
+pub struct GammaMarket {
+    pub slug: String,
+    /// up token first, down second
+    pub tokens: MarketResponse,
+    /// outcome labels in the order of `tokens`
+    pub outcomes: [String; 2],
+    /// unix seconds, the start is `None` when Gamma doesn't tell
+    pub start: Option<i64>,
+    pub end: i64,
+    pub tick_size: Decimal,
+    pub min_order_size: Decimal,
+    pub neg_risk: bool,
+    pub status: MarketStatus,
+}
+
+impl GammaMarket {
+    /// why the prices of the settings can't trade the market, `None` when they can. The size
+    /// is checked against the minimum once the cycle was sized
+    pub fn unfit(&self, settings: &StrategySettings) -> Option<String> {
+        for (name, price) in [
+            ("limit_enter_price", settings.limit_enter_price),
+            ("hedge_enter_price", settings.hedge_enter_price),
+        ] {
+            if !(price % self.tick_size).is_zero() {
+                return Some(format!("{name} {price} is off the tick size {}", self.tick_size));
+            }
+        }
+        None
+    }
+}
+
+/// JSON list in a string, `"[\"Up\", \"Down\"]"`
+fn string_list(field: &str, raw: &str) -> Result<Vec<String>, BotError> {
+    serde_json::from_str(raw)
+        .map_err(|e| BotError::Invariant(format!("{field} is not a list of strings: {raw}: {e}")))
+}
+
+impl RawMarket {
+    fn check(self, start: i64, end: i64) -> Result<GammaMarket, BotError> {
+        let slug = self.slug;
+        let invariant = |message: String| BotError::Invariant(format!("{slug}: {message}"));
+
+        let outcomes = string_list("outcomes", &self.outcomes)?;
+        let tokens = string_list("clobTokenIds", &self.clob_token_ids)?;
+        let [first, second] = <[String; 2]>::try_from(outcomes).map_err(|outcomes| {
+            invariant(format!("{} outcomes instead of 2: {outcomes:?}", outcomes.len()))
+        })?;
+        let [first_token, second_token] = <[String; 2]>::try_from(tokens).map_err(|tokens| {
+            invariant(format!("{} tokens instead of 2: {tokens:?}", tokens.len()))
+        })?;
+        // the cycle keeps the up token first, whatever order Gamma lists them in
+        let labels = (first.to_lowercase(), second.to_lowercase());
+        let (outcomes, tokens) = match (labels.0.as_str(), labels.1.as_str()) {
+            ("up", "down") => ([first, second], [first_token, second_token]),
+            ("down", "up") => ([second, first], [second_token, first_token]),
+            _ => {
+                return Err(invariant(format!("outcomes {first} / {second} are not up / down")));
+            }
+        };
+        let [first_asset_id, second_asset_id] = tokens;
+
+        let listed_start = self.event_start_time.map(|t| t.timestamp());
+        if let Some(listed) = listed_start.filter(|s| *s != start) {
+            return Err(invariant(format!("starts at {listed}, expected {start}")));
+        }
+        let Some(listed_end) = self.end_date.map(|t| t.timestamp()) else {
+            return Err(invariant("no end date".to_string()));
+        };
+        if listed_end != end {
+            return Err(invariant(format!("ends at {listed_end}, expected {end}")));
+        }
+
+        let status = if self.closed {
+            MarketStatus::Closed
+        } else if self.active && self.accepting_orders && self.enable_order_book {
+            MarketStatus::Open
+        } else {
+            MarketStatus::Pending
+        };
+        let tick_size = self.order_price_min_tick_size.filter(|t| *t > Decimal::ZERO);
+        let Some(tick_size) = tick_size else {
+            return Err(invariant("no tick size".to_string()));
+        };
+
+        Ok(GammaMarket {
+            slug,
+            tokens: MarketResponse {
+                first_asset_id,
+                second_asset_id,
+            },
+            outcomes,
+            start: listed_start,
+            end: listed_end,
+            tick_size,
+            min_order_size: self.order_min_size.unwrap_or_default(),
+            neg_risk: self.neg_risk,
+            status,
+        })
+    }
+}
+
+/// read-only Gamma client, nothing in there needs a key
+#[derive(Clone)]
+pub struct GammaClient {
+    http: http_client,
+    host: String,
+}
+
+impl GammaClient {
+    pub fn new(http: http_client) -> Self {
+        Self::with_host(http, GAMMA_URL)
+    }
+
+    pub fn with_host(http: http_client, host: &str) -> Self {
+        GammaClient {
+            http,
+            host: host.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The `asset` market of the family starting at `timestamp`, ready to take orders. One
+    /// that isn't listed yet or doesn't take orders yet is a [`BotError::Transient`], the
+    /// caller waits and asks again. A closed one or one that doesn't look like an up/down
+    /// market of that window is a [`BotError::Invariant`]
+    pub async fn market(
+        &self,
+        family: &MarketFamily,
+        timestamp: i64,
+        asset: Asset,
+    ) -> Result<GammaMarket, BotError> {
+        let slug = family.slug(asset, timestamp);
+        let url = format!("{}/markets/slug/{slug}", self.host);
+        let response =
+            timed_request("gamma", "market_by_slug", self.http.get(&url).send()).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(BotError::Transient(format!("{slug} is not listed yet")));
+        }
+        let raw: RawMarket = response.error_for_status()?.json().await?;
+
+        let market = raw.check(timestamp, family.end(timestamp))?;
+        match market.status {
+            MarketStatus::Open => Ok(market),
+            MarketStatus::Pending => {
+                Err(BotError::Transient(format!("{slug} doesn't take orders yet")))
+            }
+            MarketStatus::Closed => Err(BotError::Invariant(format!("{slug} is closed"))),
+        }
+    }
+}
@@ -1,6 +1,43 @@
+pub mod asset;
+pub mod backtest;
+pub mod clock;
+pub mod config;
+pub mod control;
+pub mod cycle;
 pub mod dto;
+pub mod error;
+pub mod exchange;
+pub mod funds;
+pub mod gamma;
+pub mod market;
+pub mod market_data;
+pub mod outcome;
+pub mod pricing;
+pub mod paper;
+pub mod reconcile;
+pub mod reload;
+pub mod retry;
+pub mod runner;
+pub mod settings;
+pub mod shutdown;
+pub mod sizing;
+pub mod status;
+pub mod store;
+pub mod user_channel;
 pub mod utils;
 mod metrics;
 
+pub use asset::Asset;
+pub use config::*;
+pub use control::Control;
 pub use dto::*;
+pub use error::{BotError, ErrorPolicy};
+pub use exchange::*;
+pub use metrics::{set_mode, start_metrics_server};
+pub use paper::*;
+pub use reload::ConfigReloader;
+pub use runner::*;
+pub use settings::*;
+pub use shutdown::Shutdown;
+pub use store::{CycleJournal, Store};
 pub use utils::*;
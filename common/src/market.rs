@@ -0,0 +1,257 @@
+//! Families of recurring up/down markets: how their slug is built, how long one runs and
+//! where the runs are aligned in which timezone. The 15-minute markets start every quarter
+//! hour in UTC, the hourly, 4-hour and daily ones follow Eastern time.
+
+use crate::asset::Asset;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Weekday};
+use serde::Deserialize;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+const DAY: i64 = 86_400;
+const HOUR: i64 = 3_600;
+
+/// the family cycles trade unless a config picks another one
+pub const DEFAULT_MARKET: &str = "15m";
+
+/// what the slugs of a family can be made of, dates and hours are local to its timezone
+const PLACEHOLDERS: &[&str] = &[
+    "asset", "name", "timestamp", "year", "month", "day", "hour", "end_year", "end_month",
+    "end_day", "end_hour",
+];
+
+/// where the start and end of a market are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Timezone {
+    Utc,
+    /// seconds east of UTC, no daylight saving time
+    Fixed(i64),
+    /// America/New_York, EST or EDT by the US rules since 2007
+    Eastern,
+}
+
+impl Timezone {
+    /// seconds east of UTC at `utc`
+    pub fn offset(self, utc: i64) -> i64 {
+        match self {
+            Timezone::Utc => 0,
+            Timezone::Fixed(offset) => offset,
+            // the switch happens at 2:00 standard time in spring and 1:00 standard time in fall
+            Timezone::Eastern => eastern_offset(utc - 5 * HOUR),
+        }
+    }
+
+    /// seconds east of UTC of the local wall clock time `local`, a skipped spring hour
+    /// counts as standard time
+    fn local_offset(self, local: i64) -> i64 {
+        match self {
+            Timezone::Eastern => eastern_offset(local - HOUR),
+            timezone => timezone.offset(local),
+        }
+    }
+
+    pub fn local(self, utc: i64) -> i64 {
+        utc + self.offset(utc)
+    }
+
+    pub fn utc(self, local: i64) -> i64 {
+        local - self.local_offset(local)
+    }
+}
+
+/// EDT from the second Sunday of March to the first Sunday of November, `standard` is
+/// the wall clock time without daylight saving
+fn eastern_offset(standard: i64) -> i64 {
+    let Some(date) = DateTime::from_timestamp(standard, 0) else {
+        return -5 * HOUR;
+    };
+    let switch = |month, n| {
+        NaiveDate::from_weekday_of_month_opt(date.year(), month, Weekday::Sun, n)
+            .and_then(|day| day.and_hms_opt(2, 0, 0))
+            .map(|at| at.and_utc().timestamp())
+    };
+    match (switch(3, 2), switch(11, 1)) {
+        // the fall switch is at 2:00 daylight time, which is 1:00 standard time
+        (Some(spring), Some(fall)) if standard >= spring && standard < fall - HOUR => -4 * HOUR,
+        _ => -5 * HOUR,
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    /// `UTC`, `ET` / `America/New_York` or a fixed offset like `+05:30`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "UTC" | "utc" | "Z" => return Ok(Timezone::Utc),
+            "ET" | "America/New_York" => return Ok(Timezone::Eastern),
+            _ => {}
+        }
+        let invalid = || format!("unknown timezone {s}, expected UTC, ET or an offset like +05:30");
+        let s = s.trim();
+        let (sign, rest) = match s.split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let (Ok(hours), Ok(minutes)) = (hours.parse::<i64>(), minutes.parse::<i64>()) else {
+            return Err(invalid());
+        };
+        if hours > 14 || minutes >= 60 {
+            return Err(invalid());
+        }
+        Ok(Timezone::Fixed(sign * (hours * HOUR + minutes * 60)))
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for Timezone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Timezone::Utc => write!(f, "UTC"),
+            Timezone::Eastern => write!(f, "America/New_York"),
+            Timezone::Fixed(offset) => {
+                let sign = if *offset < 0 { '-' } else { '+' };
+                let offset = offset.abs();
+                write!(f, "{sign}{:02}:{:02}", offset / HOUR, offset % HOUR / 60)
+            }
+        }
+    }
+}
+
+/// One kind of recurring market, `[markets.<name>]` in the config or one of [`preset`].
+/// Markets of a family run back to back, each one starts where the previous ended. They
+/// follow the local clock, so the hour repeated in fall belongs to the market before it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketFamily {
+    /// the key it is configured under
+    #[serde(skip)]
+    pub name: String,
+    /// gamma slug of one market, `{asset}`, `{name}` (bitcoin), `{timestamp}` of the start,
+    /// `{year}`, `{month}` (october), `{day}`, `{hour}` (3pm) of the start in the timezone
+    /// and the same with `end_` for the end
+    pub slug: String,
+    /// seconds one market runs
+    pub interval: i64,
+    /// seconds after local midnight one of the markets starts
+    #[serde(default)]
+    pub align: i64,
+    #[serde(default = "default_timezone")]
+    pub timezone: Timezone,
+}
+
+fn default_timezone() -> Timezone {
+    Timezone::Utc
+}
+
+/// the families Polymarket lists for every asset
+pub fn preset(name: &str) -> Option<MarketFamily> {
+    let (slug, interval, align, timezone) = match name {
+        "15m" => ("{asset}-updown-15m-{timestamp}", 900, 0, Timezone::Utc),
+        "1h" => ("{name}-up-or-down-{month}-{day}-{hour}-et", HOUR, 0, Timezone::Eastern),
+        "4h" => ("{asset}-updown-4h-{timestamp}", 4 * HOUR, 0, Timezone::Eastern),
+        // resolves at noon ET and is named after that day
+        "daily" => ("{name}-up-or-down-on-{end_month}-{end_day}", DAY, 12 * HOUR, Timezone::Eastern),
+        _ => return None,
+    };
+    Some(MarketFamily {
+        name: name.to_string(),
+        slug: slug.to_string(),
+        interval,
+        align,
+        timezone,
+    })
+}
+
+impl Default for MarketFamily {
+    fn default() -> Self {
+        preset(DEFAULT_MARKET).expect("the default market is a preset")
+    }
+}
+
+impl MarketFamily {
+    /// problems with the family itself, `section` is where it sits in the config
+    pub fn validate(&self, section: &str) -> Vec<String> {
+        let mut errors = vec![];
+        if self.interval <= 0 || DAY % self.interval != 0 {
+            errors.push(format!(
+                "{section}.interval: must divide a day ({DAY} seconds), got {}",
+                self.interval
+            ));
+        }
+        if !(0..DAY).contains(&self.align) {
+            errors.push(format!("{section}.align: must be within [0, {DAY}), got {}", self.align));
+        }
+        let mut rest = self.slug.as_str();
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}') else {
+                errors.push(format!("{section}.slug: unclosed {{ in {}", self.slug));
+                break;
+            };
+            let placeholder = &rest[open + 1..open + close];
+            if !PLACEHOLDERS.contains(&placeholder) {
+                errors.push(format!("{section}.slug: unknown placeholder {{{placeholder}}}"));
+            }
+            rest = &rest[open + close + 1..];
+        }
+        errors
+    }
+
+    /// start of the market running at `now`
+    pub fn current(&self, now: i64) -> i64 {
+        let timezone = self.timezone;
+        let local = timezone.local(now);
+        let start = (local - self.align).div_euclid(self.interval) * self.interval + self.align;
+        let utc = timezone.utc(start);
+        // the local clock went back, the floor is an hour late
+        if utc > now { timezone.utc(start - self.interval) } else { utc }
+    }
+
+    /// end of the market starting at `start`, where the next one starts
+    pub fn end(&self, start: i64) -> i64 {
+        let timezone = self.timezone;
+        timezone.utc(timezone.local(start) + self.interval)
+    }
+
+    /// The market a new cycle enters at `now`: the one after the next, so the entry orders
+    /// rest for at least a whole market before it starts.
+    pub fn target(&self, now: i64) -> i64 {
+        self.end(self.end(self.current(now)))
+    }
+
+    /// gamma slug of the `asset` market starting at `start`
+    pub fn slug(&self, asset: Asset, start: i64) -> String {
+        let timezone = self.timezone;
+        let end = self.end(start);
+        let mut slug = self.slug.clone();
+        for (prefix, at) in [("", start), ("end_", end)] {
+            let Some(local) = DateTime::from_timestamp(timezone.local(at), 0) else {
+                continue;
+            };
+            let (pm, hour) = local.hour12();
+            let hour = format!("{hour}{}", if pm { "pm" } else { "am" });
+            let month = local.format("%B").to_string().to_lowercase();
+            for (key, value) in [
+                ("year", local.year().to_string()),
+                ("month", month),
+                ("day", local.day().to_string()),
+                ("hour", hour),
+            ] {
+                slug = slug.replace(&format!("{{{prefix}{key}}}"), &value);
+            }
+        }
+        slug.replace("{asset}", asset.slug_prefix())
+            .replace("{name}", asset.name())
+            .replace("{timestamp}", &start.to_string())
+    }
+}
@@ -0,0 +1,349 @@
+//! Local L2 books of the tokens cycles trade, kept from the CLOB market channel. While
+//! the socket is down, or before it sent a token's book, the books are polled over REST.
+
+use crate::clock::Clock;
+use crate::dto::{BookLevel, BookSnapshot};
+use crate::metrics::MARKET_CHANNEL_UP;
+use crate::paper::BookSource;
+use crate::user_channel::{Events, channel, connection_change, next_event};
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::clob::ws::WsMessage;
+use polymarket_client_sdk::clob::ws::types::OrderBookLevel;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+pub const MARKET_CHANNEL_URL: &str = "wss://ws-subscriptions-clob.polymarket.com/ws/market";
+
+/// how often books the socket doesn't deliver are polled
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// best bid and ask, depth and the last trade of one token
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenBook {
+    pub book: BookSnapshot,
+    pub last_trade: Option<Decimal>,
+    /// unix seconds of the latest change, on the synced clock
+    pub updated_at: i64,
+    /// kept from the market channel, polled otherwise
+    pub streamed: bool,
+}
+
+impl TokenBook {
+    pub fn best_bid(&self) -> Option<Decimal> {
+        self.book.best_bid()
+    }
+
+    pub fn best_ask(&self) -> Option<Decimal> {
+        self.book.best_ask()
+    }
+
+    /// a streamed book is current while the channel's heartbeat is answered however quiet
+    /// the market, a polled one is as old as its last poll
+    pub fn fresh(&self, now: i64, max_age: i64) -> bool {
+        self.streamed || now - self.updated_at <= max_age
+    }
+}
+
+fn levels(levels: Vec<OrderBookLevel>) -> Vec<BookLevel> {
+    levels
+        .into_iter()
+        .filter(|l| !l.size.is_zero())
+        .map(|l| BookLevel { price: l.price, size: l.size })
+        .collect()
+}
+
+/// sets the size at `price` in levels kept best first
+fn set_level(levels: &mut Vec<BookLevel>, price: Decimal, size: Decimal, bids: bool) {
+    let position = if bids {
+        levels.binary_search_by_key(&Reverse(price), |l| Reverse(l.price))
+    } else {
+        levels.binary_search_by_key(&price, |l| l.price)
+    };
+    match (position, size.is_zero()) {
+        (Ok(i), true) => {
+            levels.remove(i);
+        }
+        (Ok(i), false) => levels[i].size = size,
+        (Err(_), true) => {}
+        (Err(i), false) => levels.insert(i, BookLevel { price, size }),
+    }
+}
+
+struct MarketInner {
+    books: Mutex<HashMap<String, TokenBook>>,
+    /// token to the number of cycles watching it
+    watched: Mutex<HashMap<String, usize>>,
+    /// what the market channel subscribes to
+    tokens: watch::Sender<Vec<String>>,
+    rest: Arc<dyn BookSource>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Books of the watched tokens, fed by [`run_market_channel`] and [`run_book_poller`].
+/// Without the market channel every book is polled.
+#[derive(Clone)]
+pub struct MarketData {
+    inner: Arc<MarketInner>,
+}
+
+impl MarketData {
+    pub fn new(rest: Arc<dyn BookSource>, clock: Arc<dyn Clock>) -> Self {
+        MarketData {
+            inner: Arc::new(MarketInner {
+                books: Mutex::default(),
+                watched: Mutex::default(),
+                tokens: watch::Sender::new(vec![]),
+                rest,
+                clock,
+            }),
+        }
+    }
+
+    /// books of `tokens` are kept until the returned guard is dropped
+    pub fn watch(&self, tokens: &[&str]) -> BookWatch {
+        let mut watched = self.watched();
+        for token in tokens {
+            *watched.entry(token.to_string()).or_default() += 1;
+        }
+        self.publish(&watched);
+        BookWatch {
+            data: self.clone(),
+            tokens: tokens.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    /// the latest book of a watched token, `None` until it was first received or polled
+    pub fn book(&self, token_id: &str) -> Option<TokenBook> {
+        self.books().get(token_id).cloned()
+    }
+
+    fn books(&self) -> MutexGuard<'_, HashMap<String, TokenBook>> {
+        self.inner.books.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn watched(&self) -> MutexGuard<'_, HashMap<String, usize>> {
+        self.inner.watched.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// the market channel resubscribes whenever the set of tokens changes
+    fn publish(&self, watched: &HashMap<String, usize>) {
+        let mut tokens: Vec<String> = watched.keys().cloned().collect();
+        tokens.sort();
+        self.inner.tokens.send_if_modified(|current| {
+            let changed = *current != tokens;
+            *current = tokens;
+            changed
+        });
+    }
+
+    fn unwatch(&self, tokens: &[String]) {
+        let mut watched = self.watched();
+        for token in tokens {
+            if let Some(count) = watched.get_mut(token) {
+                *count -= 1;
+                if *count == 0 {
+                    watched.remove(token);
+                    self.books().remove(token);
+                }
+            }
+        }
+        self.publish(&watched);
+    }
+
+    /// books the socket stops delivering go back to being polled
+    fn set_connected(&self, connected: bool) {
+        if !connected {
+            self.missed();
+        }
+        MARKET_CHANNEL_UP.set(connected as i64);
+    }
+
+    /// changes may have been missed, books are polled until the next snapshot
+    fn missed(&self) {
+        self.books().values_mut().for_each(|book| book.streamed = false);
+    }
+
+    fn receive(&self, message: WsMessage) {
+        let now = self.inner.clock.now();
+        let watched = self.watched();
+        let mut books = self.books();
+        match message {
+            WsMessage::Book(event) => {
+                if !watched.contains_key(&event.asset_id) {
+                    return;
+                }
+                let mut bids = levels(event.bids);
+                let mut asks = levels(event.asks);
+                bids.sort_by_key(|l| Reverse(l.price));
+                asks.sort_by_key(|l| l.price);
+                let book = books.entry(event.asset_id).or_default();
+                book.book = BookSnapshot { bids, asks };
+                book.updated_at = now;
+                book.streamed = true;
+            }
+            WsMessage::PriceChange(event) => {
+                for change in event.price_changes {
+                    // a change only applies on top of a book of this connection
+                    let Some(book) = books.get_mut(&change.asset_id).filter(|b| b.streamed) else {
+                        continue;
+                    };
+                    let Some(size) = change.size else {
+                        continue;
+                    };
+                    match change.side {
+                        Side::Buy => set_level(&mut book.book.bids, change.price, size, true),
+                        Side::Sell => set_level(&mut book.book.asks, change.price, size, false),
+                        _ => continue,
+                    }
+                    book.updated_at = now;
+                }
+            }
+            WsMessage::LastTradePrice(trade) => {
+                if let Some(book) = books.get_mut(&trade.asset_id) {
+                    book.last_trade = Some(trade.price);
+                    book.updated_at = now;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// polls every watched token the socket doesn't deliver
+    async fn poll(&self) {
+        let tokens: Vec<String> = self.watched().keys().cloned().collect();
+        for token in tokens {
+            if self.book(&token).is_some_and(|b| b.streamed) {
+                continue;
+            }
+            let (book, last_trade) = tokio::join!(
+                self.inner.rest.book(&token),
+                self.inner.rest.last_trade(&token)
+            );
+            let book = match book {
+                Ok(book) => book,
+                Err(e) => {
+                    eprintln!("Failed to poll the book of {token}: {e}");
+                    continue;
+                }
+            };
+
+            // unwatched in the meantime or the socket got there first
+            let watched = self.watched();
+            if !watched.contains_key(&token) {
+                continue;
+            }
+            let mut books = self.books();
+            let entry = books.entry(token).or_default();
+            if entry.streamed {
+                continue;
+            }
+            entry.book = book;
+            entry.last_trade = last_trade.ok().flatten().or(entry.last_trade);
+            entry.updated_at = self.inner.clock.now();
+        }
+    }
+}
+
+/// keeps the books of some tokens, see [`MarketData::watch`]
+pub struct BookWatch {
+    data: MarketData,
+    tokens: Vec<String>,
+}
+
+impl Drop for BookWatch {
+    fn drop(&mut self) {
+        self.data.unwatch(&self.tokens);
+    }
+}
+
+/// Polls the books the market channel doesn't deliver, for good.
+pub async fn run_book_poller(data: MarketData) {
+    loop {
+        data.poll().await;
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Keeps the market channel of `url` subscribed to the watched tokens for good, connected
+/// once there are any. The SDK reconnects and subscribes again, books are polled meanwhile.
+/// It never unsubscribes, events of tokens no longer watched are dropped
+pub async fn run_market_channel(url: String, data: MarketData) {
+    let mut tokens = data.inner.tokens.subscribe();
+    while tokens.borrow_and_update().is_empty() {
+        if tokens.changed().await.is_err() {
+            return;
+        }
+    }
+    let (connection, subscriptions) = match channel(&url) {
+        Ok(channel) => channel,
+        Err(e) => {
+            eprintln!("📉 Market channel unavailable: {e}, books are polled");
+            return;
+        }
+    };
+    // only tokens new to the SDK are sent, a stream yields the ones it was asked for
+    let subscribe = |tokens: Vec<String>| {
+        if tokens.is_empty() {
+            return None;
+        }
+        match subscriptions.subscribe_market(tokens) {
+            Ok(events) => Some(Box::pin(events) as Events),
+            Err(e) => {
+                eprintln!("📉 Failed to subscribe to the market channel: {e}, books are polled");
+                None
+            }
+        }
+    };
+    let mut state = connection.state_receiver();
+    let mut since = None;
+    let mut events = subscribe(tokens.borrow().clone());
+    if connection_change(&mut state, &mut since) == Some(true) {
+        data.set_connected(true);
+    }
+    loop {
+        tokio::select! {
+            biased;
+            changed = state.changed() => {
+                if changed.is_err() {
+                    data.set_connected(false);
+                    return;
+                }
+                match connection_change(&mut state, &mut since) {
+                    Some(true) => {
+                        data.set_connected(true);
+                        println!("📈 Market channel connected, books come from the socket");
+                    }
+                    Some(false) => {
+                        data.set_connected(false);
+                        eprintln!("📉 Market channel down, polling books until it reconnects");
+                    }
+                    None => {}
+                }
+            }
+            changed = tokens.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                events = subscribe(tokens.borrow_and_update().clone());
+            }
+            event = next_event(&mut events) => match event {
+                Some(Ok(message)) => data.receive(message),
+                Some(Err(e)) => {
+                    eprintln!("📉 Market channel fell behind: {e}, polling books");
+                    data.missed();
+                    events = subscribe(tokens.borrow().clone());
+                }
+                None => {
+                    data.set_connected(false);
+                    return;
+                }
+            },
+        }
+    }
+}
@@ -1,8 +1,14 @@
+use crate::dto::TradingMode;
 use lazy_static::lazy_static;
+use prometheus::proto::LabelPair;
 use prometheus::{
-    CounterVec, HistogramVec, IntCounterVec, IntGaugeVec, register_counter_vec,
-    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec,
+    CounterVec, Encoder, Gauge, GaugeVec, HistogramVec, IntCounterVec, IntGauge, TextEncoder,
+    register_counter_vec, register_gauge, register_gauge_vec, register_histogram_vec, register_int_counter_vec,
+    register_int_gauge,
 };
+use std::sync::OnceLock;
+
+static MODE: OnceLock<TradingMode> = OnceLock::new();
 
 lazy_static! {
     // 🔹 HTTP / API latency
@@ -89,15 +95,133 @@ lazy_static! {
     pub static ref RETRIES_TOTAL: IntCounterVec =
         register_int_counter_vec!(
             "retries_total",
-            "Total retries",
-            &["asset", "operation"]
+            "Retries by how the call finally ended: success, exhausted, deadline or non_retryable",
+            &["asset", "operation", "outcome"]
+        ).unwrap();
+
+    // 🔹 Config
+    pub static ref CONFIG_RELOADS_TOTAL: IntCounterVec =
+        register_int_counter_vec!(
+            "config_reloads_total",
+            "Config reloads by trigger and result",
+            &["trigger", "result"]
+        ).unwrap();
+
+    // 🔹 Control
+    /// 0 running, 1 halted, 2 flattening
+    pub static ref TRADING_STATE: IntGauge =
+        register_int_gauge!(
+            "pm_trading_state",
+            "Trading state: 0 running, 1 halted, 2 flattening"
         ).unwrap();
 
     // 🔹 PnL
-    pub static ref PNL: IntGaugeVec =
-        register_int_gauge_vec!(
+    /// realized PnL в USDC по всем завершённым циклам, с учётом комиссий
+    pub static ref PNL: GaugeVec =
+        register_gauge_vec!(
             "bot_pnl",
-            "Current PnL",
+            "Realized PnL in USDC after fees",
             &["asset"]
         ).unwrap();
+
+    // 🔹 Entry pricing
+    /// цены входа последнего цикла по сторонам up / down
+    pub static ref ENTRY_PRICE: GaugeVec =
+        register_gauge_vec!(
+            "pm_entry_price",
+            "Entry price chosen for the latest cycle per side",
+            &["asset", "side"]
+        ).unwrap();
+
+    // 🔹 User channel
+    /// 1 пока fills приходят по websocket, 0 пока ордера опрашиваются
+    pub static ref USER_CHANNEL_UP: IntGauge =
+        register_int_gauge!(
+            "pm_user_channel_up",
+            "1 while order updates come from the user channel websocket, 0 while orders are polled"
+        ).unwrap();
+
+    // 🔹 Market data
+    /// 1 пока книги приходят по websocket, 0 пока они опрашиваются
+    pub static ref MARKET_CHANNEL_UP: IntGauge =
+        register_int_gauge!(
+            "pm_market_channel_up",
+            "1 while order books come from the market channel websocket, 0 while they are polled"
+        ).unwrap();
+
+    // 🔹 Funds
+    /// USDC на счёте по последней проверке перед циклом
+    pub static ref COLLATERAL_BALANCE: Gauge =
+        register_gauge!(
+            "pm_collateral_balance",
+            "USDC balance of the account as of the latest pre-trade check"
+        ).unwrap();
+
+    /// 1 пока баланс ниже порога алерта
+    pub static ref BALANCE_LOW: IntGauge =
+        register_int_gauge!(
+            "pm_balance_low",
+            "1 while the USDC balance is below funds.alert_below"
+        ).unwrap();
+
+    /// циклы, пропущенные из-за нехватки баланса или allowance
+    pub static ref CYCLES_SKIPPED_TOTAL: IntCounterVec =
+        register_int_counter_vec!(
+            "pm_cycles_skipped_total",
+            "Cycles skipped by the pre-trade check",
+            &["asset", "reason"]
+        ).unwrap();
+
+    // 🔹 Clock
+    /// насколько часы хоста отстают от биржи, по последнему замеру
+    pub static ref CLOCK_SKEW_SECONDS: Gauge =
+        register_gauge!(
+            "pm_clock_skew_seconds",
+            "Exchange server time minus host time in seconds, as last measured"
+        ).unwrap();
+}
+
+/// every exported series gets a `mode` label, so paper and live bots can share dashboards
+pub fn set_mode(mode: TradingMode) {
+    MODE.set(mode).ok();
+}
+
+async fn metrics_handler() -> String {
+    let encoder = TextEncoder::new();
+    let mut metric_families = prometheus::gather();
+
+    let mode = MODE.get().copied().unwrap_or(TradingMode::Live).to_string();
+    for family in metric_families.iter_mut() {
+        for metric in family.mut_metric().iter_mut() {
+            let mut label = LabelPair::new();
+            label.set_name("mode".to_string());
+            label.set_value(mode.clone());
+            metric.mut_label().push(label);
+        }
+    }
+
+    let mut buffer = Vec::new();
+    encoder.encode(&metric_families, &mut buffer).unwrap();
+
+    String::from_utf8(buffer).unwrap()
+}
+
+/// one server per process, every asset task reports into the same registry,
+/// `routes` are extra endpoints served next to `/metrics`
+pub fn start_metrics_server(port: u16, routes: axum::Router) {
+    tokio::spawn(async move {
+        let app = axum::Router::new()
+            .route("/metrics", axum::routing::get(metrics_handler))
+            .merge(routes);
+
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+        println!("📊 Metrics server started on {}", addr);
+
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .expect("Failed to bind metrics port");
+        axum::serve(listener, app)
+            .await
+            .expect("Metrics server crashed");
+    });
 }
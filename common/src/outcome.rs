@@ -0,0 +1,190 @@
+//! What a finished cycle traded and what it made.
+
+use crate::dto::Fill;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// USDC has 6 decimals, fees and PnL are rounded to it
+const USDC_DP: u32 = 6;
+
+/// everything one token of the market was traded at
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LegFills {
+    pub token_id: String,
+    /// bought by the entry order
+    pub entry: Fill,
+    /// bought by the hedge order
+    pub hedge: Fill,
+    /// market-sold by the stop-loss or a flatten
+    pub sold: Fill,
+}
+
+impl LegFills {
+    /// shares left once the cycle is done
+    pub fn held(&self) -> Decimal {
+        (self.entry.size + self.hedge.size - self.sold.size).max(Decimal::ZERO)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CycleFills {
+    /// in the order the tokens were first filled
+    pub legs: Vec<LegFills>,
+    /// taker fees of the market sells, in USDC
+    pub fees: Decimal,
+    /// sell proceeds plus 1 per complete pair, one side of it pays out, minus what was
+    /// paid and the fees. Shares held on one side only are worth nothing here
+    pub pnl: Decimal,
+}
+
+/// how a cycle ended, `Display` is the short name used in logs and the store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum CycleOutcome {
+    /// both tokens are held, one of them pays out
+    Hedged(CycleFills),
+    /// the hedge didn't match in time, what was held got sold
+    StopLoss(CycleFills),
+    /// sold for `/flatten` or a shutdown
+    Flattened(CycleFills),
+    /// what was held could not be sold and is still open
+    CloseFailed(CycleFills),
+    /// halted before entry or no entry order was filled
+    NoFill,
+}
+
+impl CycleOutcome {
+    pub fn fills(&self) -> Option<&CycleFills> {
+        match self {
+            CycleOutcome::Hedged(fills)
+            | CycleOutcome::StopLoss(fills)
+            | CycleOutcome::Flattened(fills)
+            | CycleOutcome::CloseFailed(fills) => Some(fills),
+            CycleOutcome::NoFill => None,
+        }
+    }
+
+    pub fn pnl(&self) -> Decimal {
+        self.fills().map_or(Decimal::ZERO, |fills| fills.pnl)
+    }
+
+    pub fn fees(&self) -> Decimal {
+        self.fills().map_or(Decimal::ZERO, |fills| fills.fees)
+    }
+
+    /// what wins and losses are counted from: 1 hedged, -1 stopped out, 0 anything else
+    pub fn result(&self) -> i8 {
+        match self {
+            CycleOutcome::Hedged(_) => 1,
+            CycleOutcome::StopLoss(_) => -1,
+            _ => 0,
+        }
+    }
+}
+
+impl Display for CycleOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CycleOutcome::Hedged(_) => "hedged",
+            CycleOutcome::StopLoss(_) => "stop-loss",
+            CycleOutcome::Flattened(_) => "flattened",
+            CycleOutcome::CloseFailed(_) => "close failed",
+            CycleOutcome::NoFill => "no fill",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug, Clone)]
+struct OrderFill {
+    order_id: String,
+    token_id: String,
+    hedge: bool,
+    fill: Fill,
+}
+
+/// fills of a cycle as they are seen, turned into [`CycleFills`] once it is done
+#[derive(Debug, Clone, Default)]
+pub struct FillLog {
+    orders: Vec<OrderFill>,
+    sells: Vec<(String, Fill)>,
+}
+
+impl FillLog {
+    /// latest fill of a limit order, replaces what was seen of it before
+    pub fn order(&mut self, order_id: &str, token_id: &str, hedge: bool, fill: Fill) {
+        match self.orders.iter_mut().find(|o| o.order_id == order_id) {
+            Some(order) => {
+                order.hedge |= hedge;
+                order.fill = fill;
+            }
+            None => self.orders.push(OrderFill {
+                order_id: order_id.to_string(),
+                token_id: token_id.to_string(),
+                hedge,
+                fill,
+            }),
+        }
+    }
+
+    pub fn sold(&mut self, token_id: &str, fill: Fill) {
+        if !fill.size.is_zero() {
+            self.sells.push((token_id.to_string(), fill));
+        }
+    }
+
+    /// nothing was bought
+    pub fn is_empty(&self) -> bool {
+        self.orders.iter().all(|o| o.fill.size.is_zero())
+    }
+
+    /// Limit orders rest on the book and pay no fee, market sells pay
+    /// `fee_rate_bps / 10000 * min(p, 1 - p)` per share.
+    pub fn fills(&self, fee_rate_bps: u32) -> CycleFills {
+        let mut legs: Vec<LegFills> = vec![];
+        for order in self.orders.iter().filter(|o| !o.fill.size.is_zero()) {
+            let leg = leg(&mut legs, &order.token_id);
+            if order.hedge {
+                leg.hedge.add(order.fill);
+            } else {
+                leg.entry.add(order.fill);
+            }
+        }
+
+        let rate = Decimal::from(fee_rate_bps) / Decimal::from(10_000);
+        let mut fees = Decimal::ZERO;
+        for (token_id, fill) in &self.sells {
+            fees += rate * fill.price.min(Decimal::ONE - fill.price) * fill.size;
+            leg(&mut legs, token_id).sold.add(*fill);
+        }
+
+        let paid: Decimal = legs.iter().map(|l| l.entry.notional() + l.hedge.notional()).sum();
+        let proceeds: Decimal = legs.iter().map(|l| l.sold.notional()).sum();
+        let pairs = match legs.as_slice() {
+            [first, second] => first.held().min(second.held()),
+            _ => Decimal::ZERO,
+        };
+
+        let fees = fees.round_dp(USDC_DP);
+        CycleFills {
+            pnl: (proceeds + pairs - paid - fees).round_dp(USDC_DP),
+            fees,
+            legs,
+        }
+    }
+}
+
+fn leg<'a>(legs: &'a mut Vec<LegFills>, token_id: &str) -> &'a mut LegFills {
+    match legs.iter().position(|l| l.token_id == token_id) {
+        Some(i) => &mut legs[i],
+        None => {
+            legs.push(LegFills {
+                token_id: token_id.to_string(),
+                ..Default::default()
+            });
+            legs.last_mut().unwrap()
+        }
+    }
+}
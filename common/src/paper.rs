@@ -0,0 +1,535 @@
+use crate::dto::{BookLevel, BookSnapshot, Collateral, MarketOrderResponse, OrderResponse, OrderState};
+use crate::exchange::Exchange;
+use crate::utils::timed_request;
+use async_trait::async_trait;
+use polymarket_client_sdk::auth::state::Unauthenticated;
+use polymarket_client_sdk::clob::Client;
+use polymarket_client_sdk::clob::types::{
+    LastTradePriceRequest, OrderBookSummaryRequest, OrderStatusType, Side,
+};
+use polymarket_client_sdk::error::Error;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::prelude::Zero;
+use rust_decimal::{Decimal, dec};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Where the paper exchange gets the book it matches against, and market data its books
+/// while the market channel is down.
+#[async_trait]
+pub trait BookSource: Send + Sync {
+    async fn book(&self, token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot>;
+
+    /// price of the latest trade, `None` where there are no trades
+    async fn last_trade(&self, _token_id: &str) -> polymarket_client_sdk::Result<Option<Decimal>> {
+        Ok(None)
+    }
+}
+
+/// one source shared by the paper exchange and market data
+#[async_trait]
+impl<T: BookSource + ?Sized> BookSource for Arc<T> {
+    async fn book(&self, token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        (**self).book(token_id).await
+    }
+
+    async fn last_trade(&self, token_id: &str) -> polymarket_client_sdk::Result<Option<Decimal>> {
+        (**self).last_trade(token_id).await
+    }
+}
+
+/// real Polymarket book over the public REST endpoint, no auth needed
+pub struct LiveBookSource {
+    client: Client<Unauthenticated>,
+}
+
+impl LiveBookSource {
+    pub fn new(client: Client<Unauthenticated>) -> Self {
+        LiveBookSource { client }
+    }
+}
+
+#[async_trait]
+impl BookSource for LiveBookSource {
+    async fn book(&self, token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        let request = OrderBookSummaryRequest::builder().token_id(token_id).build();
+        let book = timed_request("polymarket", "order_book", self.client.order_book(&request)).await?;
+
+        let mut bids: Vec<BookLevel> = book
+            .bids
+            .iter()
+            .map(|l| BookLevel { price: l.price, size: l.size })
+            .collect();
+        let mut asks: Vec<BookLevel> = book
+            .asks
+            .iter()
+            .map(|l| BookLevel { price: l.price, size: l.size })
+            .collect();
+        // the API doesn't promise any order
+        bids.sort_by_key(|l| std::cmp::Reverse(l.price));
+        asks.sort_by_key(|l| l.price);
+
+        Ok(BookSnapshot { bids, asks })
+    }
+
+    async fn last_trade(&self, token_id: &str) -> polymarket_client_sdk::Result<Option<Decimal>> {
+        let request = LastTradePriceRequest::builder().token_id(token_id).build();
+        let trade = timed_request(
+            "polymarket",
+            "last_trade_price",
+            self.client.last_trade_price(&request),
+        )
+        .await?;
+        Ok(Some(trade.price))
+    }
+}
+
+/// Random walk around 0.5 per token with a one-tick spread and random depth, for running offline.
+/// Tokens walk independently, so up + down does not have to add up to 1.
+pub struct SimulatedBookSource {
+    state: Mutex<(StdRng, HashMap<String, Decimal>)>,
+}
+
+impl Default for SimulatedBookSource {
+    fn default() -> Self {
+        SimulatedBookSource {
+            state: Mutex::new((StdRng::from_entropy(), HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl BookSource for SimulatedBookSource {
+    async fn book(&self, token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        let mut state = self.state.lock().unwrap();
+        let (rng, mids) = &mut *state;
+        let tick = dec!(0.01);
+
+        let step = Decimal::from(rng.gen_range(-2..=2)) * tick;
+        let mid = mids.entry(token_id.to_string()).or_insert(dec!(0.5));
+        *mid = (*mid + step).clamp(dec!(0.02), dec!(0.98));
+        let mid = *mid;
+
+        let mut bids = vec![];
+        let mut asks = vec![];
+        for level in 0..5 {
+            let offset = tick * Decimal::from(level);
+            let bid = mid - tick - offset;
+            let ask = mid + offset;
+            if bid > Decimal::zero() {
+                bids.push(BookLevel {
+                    price: bid,
+                    size: Decimal::from(rng.gen_range(5..=100)),
+                });
+            }
+            if ask < Decimal::ONE {
+                asks.push(BookLevel {
+                    price: ask,
+                    size: Decimal::from(rng.gen_range(5..=100)),
+                });
+            }
+        }
+
+        Ok(BookSnapshot { bids, asks })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PaperOrder {
+    state: OrderState,
+    side: Side,
+}
+
+#[derive(Debug, Default)]
+struct PaperBook {
+    next_id: u64,
+    orders: HashMap<String, PaperOrder>,
+    /// free USDC, collateral of resting buys is already taken out
+    cash: Decimal,
+    /// shares per token, shares of resting sells are already taken out
+    positions: HashMap<String, Decimal>,
+    /// what our fills took from the levels of each book
+    taken: HashMap<LevelKey, TakenLevel>,
+}
+
+/// a price level of one side of a token's book
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LevelKey {
+    token_id: String,
+    bids: bool,
+    price: Decimal,
+}
+
+/// Our fills at a level while it shows the same size. The books don't see paper fills, so
+/// a level that didn't change still holds what was taken from it, one that did is new
+/// liquidity.
+#[derive(Debug, Clone, Copy)]
+struct TakenLevel {
+    shown: Decimal,
+    taken: Decimal,
+}
+
+impl PaperBook {
+    /// `levels` of one side of the book less what our fills already took from them
+    fn available(&mut self, token_id: &str, bids: bool, levels: &[BookLevel]) -> Vec<BookLevel> {
+        self.taken.retain(|key, taken| {
+            key.token_id != token_id
+                || key.bids != bids
+                || levels
+                    .iter()
+                    .any(|level| level.price == key.price && level.size == taken.shown)
+        });
+        levels
+            .iter()
+            .map(|level| {
+                let key = LevelKey {
+                    token_id: token_id.to_string(),
+                    bids,
+                    price: level.price,
+                };
+                let taken = self.taken.get(&key).map_or(Decimal::zero(), |t| t.taken);
+                BookLevel {
+                    price: level.price,
+                    size: level.size - taken,
+                }
+            })
+            .collect()
+    }
+
+    /// books `filled` as taken off the front of `levels`, the ones `available` returned
+    fn consume(&mut self, token_id: &str, bids: bool, levels: &[BookLevel], filled: Decimal) {
+        let mut left = filled;
+        for level in levels {
+            if left.is_zero() {
+                break;
+            }
+            let take = level.size.min(left);
+            left -= take;
+            let key = LevelKey {
+                token_id: token_id.to_string(),
+                bids,
+                price: level.price,
+            };
+            let taken = self.taken.entry(key).or_insert(TakenLevel {
+                shown: level.size,
+                taken: Decimal::zero(),
+            });
+            taken.taken += take;
+        }
+    }
+}
+
+/// Exchange that never touches the network with orders. Limit orders rest until the book
+/// crosses them and fill up to the depth that is visible at that moment and not taken by an
+/// earlier fill, so partial fills happen the same way they do on the real CLOB. Market
+/// orders are FOK against the book.
+pub struct PaperExchange {
+    books: Box<dyn BookSource>,
+    inner: Mutex<PaperBook>,
+    logging: bool,
+}
+
+impl PaperExchange {
+    pub fn new(books: Box<dyn BookSource>, balance: Decimal) -> Self {
+        PaperExchange {
+            books,
+            inner: Mutex::new(PaperBook {
+                cash: balance,
+                ..PaperBook::default()
+            }),
+            logging: true,
+        }
+    }
+
+    /// the backtester replays thousands of fills, it turns the per-fill output off
+    pub fn with_logging(mut self, logging: bool) -> Self {
+        self.logging = logging;
+        self
+    }
+
+    pub fn balance(&self) -> Decimal {
+        self.inner.lock().unwrap().cash
+    }
+
+    pub fn position(&self, token_id: &str) -> Decimal {
+        let inner = self.inner.lock().unwrap();
+        inner.positions.get(token_id).copied().unwrap_or_default()
+    }
+
+    /// fills what the book allows, at our limit price when resting and at book prices when
+    /// crossing on entry. Liquidity a fill took stays taken until its level changes
+    fn try_fill(&self, inner: &mut PaperBook, order_id: &str, book: &BookSnapshot, is_taker: bool) {
+        let Some(order) = inner.orders.get(order_id) else {
+            return;
+        };
+        if order.state.status != OrderStatusType::Live {
+            return;
+        }
+
+        let remaining = order.state.original_size - order.state.size_matched;
+        let limit = order.state.price;
+        let side = order.side;
+        let token_id = order.state.token_id.clone();
+        let is_buy = side == Side::Buy;
+        let levels = inner.available(&token_id, !is_buy, if is_buy { &book.asks } else { &book.bids });
+        let (filled, notional) = take_liquidity(&levels, remaining, |p| {
+            if is_buy { p <= limit } else { p >= limit }
+        });
+        if filled.is_zero() {
+            return;
+        }
+        inner.consume(&token_id, !is_buy, &levels, filled);
+        let notional = if is_taker { notional } else { filled * limit };
+
+        let order = inner.orders.get_mut(order_id).expect("order checked above");
+        order.state.size_matched += filled;
+        if order.state.size_matched >= order.state.original_size {
+            order.state.status = OrderStatusType::Matched;
+        }
+        if self.logging {
+            println!(
+                "[paper] {} filled {} of {} @ {} ({:?})",
+                order_id, filled, order.state.original_size, limit, side
+            );
+        }
+
+        match side {
+            Side::Buy => {
+                // collateral was reserved at the limit price, give back the price improvement
+                inner.cash += filled * limit - notional;
+                *inner.positions.entry(token_id).or_default() += filled;
+            }
+            _ => inner.cash += notional,
+        }
+    }
+}
+
+/// walks levels while `crosses` holds, returns filled size and its notional
+fn take_liquidity(
+    levels: &[BookLevel],
+    size: Decimal,
+    crosses: impl Fn(Decimal) -> bool,
+) -> (Decimal, Decimal) {
+    let mut filled = Decimal::zero();
+    let mut notional = Decimal::zero();
+    for level in levels.iter().take_while(|l| crosses(l.price)) {
+        let take = level.size.min(size - filled);
+        filled += take;
+        notional += take * level.price;
+        if filled >= size {
+            break;
+        }
+    }
+    (filled, notional)
+}
+
+#[async_trait]
+impl Exchange for PaperExchange {
+    async fn place_limit_order(
+        &self,
+        token_id: &str,
+        size: Decimal,
+        price: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<OrderResponse> {
+        if size <= Decimal::zero() || price <= Decimal::zero() || price >= Decimal::ONE {
+            return Err(Error::validation(format!(
+                "invalid order: size {size}, price {price}"
+            )));
+        }
+        let book = self.books.book(token_id).await?;
+        let mut inner = self.inner.lock().unwrap();
+
+        match side {
+            Side::Buy => {
+                let collateral = size * price;
+                if collateral > inner.cash {
+                    return Err(Error::validation(format!(
+                        "not enough balance: need {collateral}, have {}",
+                        inner.cash
+                    )));
+                }
+                inner.cash -= collateral;
+            }
+            _ => {
+                let position = inner.positions.entry(token_id.to_string()).or_default();
+                if size > *position {
+                    return Err(Error::validation(format!(
+                        "not enough shares: need {size}, have {position}"
+                    )));
+                }
+                *position -= size;
+            }
+        }
+
+        inner.next_id += 1;
+        let order_id = format!("paper-{}", inner.next_id);
+        inner.orders.insert(
+            order_id.clone(),
+            PaperOrder {
+                state: OrderState {
+                    order_id: order_id.clone(),
+                    token_id: token_id.to_string(),
+                    status: OrderStatusType::Live,
+                    original_size: size,
+                    size_matched: Decimal::zero(),
+                    price,
+                },
+                side,
+            },
+        );
+        self.try_fill(&mut inner, &order_id, &book, true);
+
+        Ok(OrderResponse {
+            token_id: token_id.to_string(),
+            order_id,
+        })
+    }
+
+    async fn place_market_order(
+        &self,
+        token_id: &str,
+        shares: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<MarketOrderResponse> {
+        let book = self.books.book(token_id).await?;
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let order_id = format!("paper-{}", inner.next_id);
+        let rejected = |msg: String| MarketOrderResponse {
+            order_id: order_id.clone(),
+            error_msg: Some(msg),
+            making_amount: Decimal::zero(),
+            taking_amount: Decimal::zero(),
+        };
+
+        let bids = side != Side::Buy;
+        let levels = inner.available(token_id, bids, if bids { &book.bids } else { &book.asks });
+        let (filled, notional) = take_liquidity(&levels, shares, |_| true);
+        if filled < shares {
+            return Ok(rejected(format!(
+                "order couldn't be fully filled, FOK orders are fully filled or killed (book has {filled})"
+            )));
+        }
+
+        let position = inner.positions.get(token_id).copied().unwrap_or_default();
+        let response = match side {
+            Side::Buy => {
+                if notional > inner.cash {
+                    return Ok(rejected(format!("not enough balance: need {notional}")));
+                }
+                inner.cash -= notional;
+                *inner.positions.entry(token_id.to_string()).or_default() += shares;
+                MarketOrderResponse {
+                    order_id,
+                    error_msg: None,
+                    making_amount: notional,
+                    taking_amount: shares,
+                }
+            }
+            _ => {
+                if shares > position {
+                    return Ok(rejected(format!(
+                        "not enough shares: need {shares}, have {position}"
+                    )));
+                }
+                inner.cash += notional;
+                inner.positions.insert(token_id.to_string(), position - shares);
+                MarketOrderResponse {
+                    order_id,
+                    error_msg: None,
+                    making_amount: shares,
+                    taking_amount: notional,
+                }
+            }
+        };
+        inner.consume(token_id, bids, &levels, shares);
+        if self.logging {
+            println!(
+                "[paper] market {:?} {} of {} for {}, cash {}",
+                side, shares, token_id, notional, inner.cash
+            );
+        }
+        Ok(response)
+    }
+
+    async fn get_order(&self, order_id: &str) -> polymarket_client_sdk::Result<OrderState> {
+        let (token_id, live) = {
+            let inner = self.inner.lock().unwrap();
+            let order = inner
+                .orders
+                .get(order_id)
+                .ok_or_else(|| Error::validation(format!("unknown order {order_id}")))?;
+            (order.state.token_id.clone(), order.state.status == OrderStatusType::Live)
+        };
+
+        let book = if live {
+            Some(self.books.book(&token_id).await?)
+        } else {
+            None
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(book) = book {
+            self.try_fill(&mut inner, order_id, &book, false);
+        }
+        Ok(inner.orders[order_id].state.clone())
+    }
+
+    async fn cancel_order(&self, order_id: &str) -> polymarket_client_sdk::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(order) = inner.orders.get_mut(order_id) else {
+            return Err(Error::validation(format!("unknown order {order_id}")));
+        };
+        if order.state.status != OrderStatusType::Live {
+            return Ok(());
+        }
+
+        order.state.status = OrderStatusType::Canceled;
+        let remaining = order.state.original_size - order.state.size_matched;
+        let (side, price, token_id) = (order.side, order.state.price, order.state.token_id.clone());
+        match side {
+            Side::Buy => inner.cash += remaining * price,
+            _ => *inner.positions.entry(token_id).or_default() += remaining,
+        }
+        Ok(())
+    }
+
+    async fn get_price(&self, token_id: &str, side: Side) -> polymarket_client_sdk::Result<Decimal> {
+        let book = self.books.book(token_id).await?;
+        let price = match side {
+            Side::Buy => book.best_ask(),
+            _ => book.best_bid(),
+        };
+        price.ok_or_else(|| Error::validation(format!("empty book for {token_id}")))
+    }
+
+    async fn open_orders(&self) -> polymarket_client_sdk::Result<Vec<OrderState>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .orders
+            .values()
+            .filter(|o| o.state.status == OrderStatusType::Live)
+            .map(|o| o.state.clone())
+            .collect())
+    }
+
+    async fn token_balance(&self, token_id: &str) -> polymarket_client_sdk::Result<Decimal> {
+        Ok(self.position(token_id))
+    }
+
+    /// cash left after what resting buys hold back, paper cash needs no approval
+    async fn collateral(&self, _neg_risk: bool) -> polymarket_client_sdk::Result<Collateral> {
+        Ok(Collateral {
+            balance: self.balance(),
+            allowance: Decimal::MAX,
+        })
+    }
+
+    /// fills are simulated without fees
+    async fn fee_rate_bps(&self, _token_id: &str) -> polymarket_client_sdk::Result<u32> {
+        Ok(0)
+    }
+}
+
@@ -0,0 +1,150 @@
+//! Entry prices of a cycle. The fixed policy bids `limit_enter_price` on both outcomes, the
+//! book policy joins each book a few ticks under its best bid, within bounds per side and a
+//! cap on what both entries cost together.
+
+use crate::dto::BookSnapshot;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PricingMode {
+    /// `limit_enter_price` on both sides
+    #[default]
+    Fixed,
+    /// priced off the order books
+    Book,
+}
+
+/// how the entry orders are priced, see [`entry_prices`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryPricing {
+    pub mode: PricingMode,
+    /// ticks under the best bid an entry rests at
+    pub ticks_below_bid: u32,
+    /// bounds of one entry price
+    pub min_price: Decimal,
+    pub max_price: Decimal,
+    /// most both entries may cost together
+    pub max_combined: Decimal,
+    /// a side with a wider spread or less on its bids gets the fixed price
+    pub max_spread: Decimal,
+    pub min_depth: Decimal,
+}
+
+/// where the price of one side came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceSource {
+    Fixed,
+    Book,
+    /// book pricing fell back to the fixed price, the book was missing, empty, too wide
+    /// or too thin
+    Fallback(&'static str),
+}
+
+impl Display for PriceSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceSource::Fixed => write!(f, "fixed"),
+            PriceSource::Book => write!(f, "book"),
+            PriceSource::Fallback(reason) => write!(f, "fixed, {reason}"),
+        }
+    }
+}
+
+/// entry prices of the first (up) and second (down) token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryQuote {
+    pub prices: [Decimal; 2],
+    pub sources: [PriceSource; 2],
+}
+
+impl Display for EntryQuote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "up {} ({}), down {} ({})",
+            self.prices[0], self.sources[0], self.prices[1], self.sources[1]
+        )
+    }
+}
+
+/// `price` down to the tick
+fn on_tick(price: Decimal, tick: Decimal) -> Decimal {
+    (price / tick).floor() * tick
+}
+
+/// price of one side before the combined cap
+fn side_price(
+    pricing: &EntryPricing,
+    fixed: Decimal,
+    tick: Decimal,
+    book: Option<&BookSnapshot>,
+) -> (Decimal, PriceSource) {
+    let Some(book) = book else {
+        return (fixed, PriceSource::Fallback("no book"));
+    };
+    let Some(bid) = book.best_bid() else {
+        return (fixed, PriceSource::Fallback("no bids"));
+    };
+    if let Some(ask) = book.best_ask()
+        && ask - bid > pricing.max_spread
+    {
+        return (fixed, PriceSource::Fallback("spread too wide"));
+    }
+    if book.bid_depth() < pricing.min_depth {
+        return (fixed, PriceSource::Fallback("bids too thin"));
+    }
+    let mut price = bid - tick * Decimal::from(pricing.ticks_below_bid);
+    // a resting entry must not take
+    if let Some(ask) = book.best_ask() {
+        price = price.min(ask - tick);
+    }
+    (price, PriceSource::Book)
+}
+
+/// Prices both entries, `fixed` is `limit_enter_price` and `tick` the market's tick size.
+/// Book prices are kept on the tick within the bounds, what both cost over the cap comes
+/// off the pricier side first. `Err` when even the lowest prices are over the cap.
+pub fn entry_prices(
+    pricing: &EntryPricing,
+    fixed: Decimal,
+    tick: Decimal,
+    books: [Option<&BookSnapshot>; 2],
+) -> Result<EntryQuote, String> {
+    if pricing.mode == PricingMode::Fixed {
+        return Ok(EntryQuote {
+            prices: [fixed; 2],
+            sources: [PriceSource::Fixed; 2],
+        });
+    }
+
+    let min_price = (pricing.min_price / tick).ceil() * tick;
+    let mut prices = [Decimal::ZERO; 2];
+    let mut sources = [PriceSource::Book; 2];
+    for (side, book) in books.into_iter().enumerate() {
+        let (price, source) = side_price(pricing, fixed, tick, book);
+        prices[side] = on_tick(price.min(pricing.max_price), tick).max(min_price);
+        sources[side] = source;
+    }
+
+    let total = prices[0] + prices[1];
+    if total > pricing.max_combined {
+        let mut excess = ((total - pricing.max_combined) / tick).ceil() * tick;
+        let pricier = if prices[0] >= prices[1] { [0, 1] } else { [1, 0] };
+        for side in pricier {
+            let cut = excess.min(prices[side] - min_price);
+            prices[side] -= cut;
+            excess -= cut;
+        }
+        if excess > Decimal::ZERO {
+            return Err(format!(
+                "entries cost at least {} together, over max_entry_cost {}",
+                min_price * Decimal::TWO,
+                pricing.max_combined
+            ));
+        }
+    }
+    Ok(EntryQuote { prices, sources })
+}
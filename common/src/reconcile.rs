@@ -0,0 +1,149 @@
+use crate::config::OrphanPolicy;
+use crate::asset::Asset;
+use crate::dto::MarketResponse;
+use crate::exchange::Exchange;
+use crate::store::{CycleStage, Store, StoredOrder};
+use crate::utils::floor_dp;
+use polymarket_client_sdk::clob::types::OrderStatusType;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+
+/// what the startup reconciliation found on the exchange
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    /// open orders of unfinished cycles, the resumed cycle takes care of them
+    pub known: usize,
+    pub cancelled: usize,
+    pub adopted: usize,
+    /// open orders outside the markets of the traded assets, left alone
+    pub foreign: usize,
+    /// unfinished cycles whose shares don't match their orders, abandoned
+    pub mismatched: usize,
+}
+
+/// Matches the account's open orders and positions to the cycles in the store before
+/// anything is traded. Only markets a cycle of `assets` traded are touched, in those the
+/// orders no unfinished cycle owns are cancelled or adopted per `policy`. Other assets,
+/// other bots and manual orders are left alone.
+pub async fn reconcile(
+    exchange: &dyn Exchange,
+    store: &Arc<Store>,
+    policy: OrphanPolicy,
+    assets: &[Asset],
+) -> anyhow::Result<ReconcileReport> {
+    let mut report = ReconcileReport::default();
+
+    for order in exchange.open_orders().await? {
+        if !store.token_asset(&order.token_id)?.is_some_and(|asset| assets.contains(&asset)) {
+            report.foreign += 1;
+            continue;
+        }
+        match store.order_stage(&order.order_id)? {
+            Some(CycleStage::Entry | CycleStage::Hedging | CycleStage::Closing) => {
+                report.known += 1;
+            }
+            stage => {
+                let owner = match stage {
+                    Some(stage) => format!("a {stage} cycle"),
+                    None => "no cycle".to_string(),
+                };
+                match policy {
+                    OrphanPolicy::Cancel => {
+                        println!(
+                            "🧹 Cancelling order {} on {} owned by {owner}",
+                            order.order_id, order.token_id
+                        );
+                        exchange.cancel_order(&order.order_id).await?;
+                        report.cancelled += 1;
+                    }
+                    OrphanPolicy::Adopt => {
+                        println!(
+                            "🧹 Adopting order {} on {} owned by {owner}, its market will not be traded",
+                            order.order_id, order.token_id
+                        );
+                        store.adopt_order(&order)?;
+                        report.adopted += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    for asset in assets {
+        let Some(cycle) = store.unfinished_cycle(*asset)? else {
+            continue;
+        };
+        let orders = store.cycle_orders(cycle.id)?;
+        for token_id in [&cycle.tokens.first_asset_id, &cycle.tokens.second_asset_id] {
+            let held = exchange.token_balance(token_id).await?;
+            let (least, most) = expected_shares(&orders, token_id);
+            println!(
+                "🧹 {asset} cycle {} ({}) holds {held} of {token_id}, its orders account for {least} to {most}",
+                cycle.id, cycle.stage
+            );
+            if floor_dp(held, 2) < floor_dp(least, 2) || floor_dp(held, 2) > most {
+                println!(
+                    "⛔ {asset} cycle {} doesn't match the account, abandoning it, what it holds is left for /flatten",
+                    cycle.id
+                );
+                store.journal(cycle.id).abandon(&format!(
+                    "holds {held} of {token_id}, its orders account for {least} to {most}"
+                ));
+                report.mismatched += 1;
+                break;
+            }
+        }
+    }
+
+    println!(
+        "🧹 Reconciled: {} open orders of unfinished cycles, {} cancelled, {} adopted, {} of other markets left alone, {} cycles abandoned",
+        report.known, report.cancelled, report.adopted, report.foreign, report.mismatched
+    );
+    Ok(report)
+}
+
+/// Shares of `token_id` the stored orders of a cycle leave it with: what its buys matched
+/// less what it sold, up to what its live buys may still have matched while the bot was down
+pub fn expected_shares(orders: &[StoredOrder], token_id: &str) -> (Decimal, Decimal) {
+    let live = OrderStatusType::Live.to_string();
+    let mut least = Decimal::ZERO;
+    let mut pending = Decimal::ZERO;
+    for order in orders.iter().filter(|o| o.token_id == token_id) {
+        match order.role.as_str() {
+            "close" => least -= order.size_matched,
+            _ => {
+                least += order.size_matched;
+                if order.status == live {
+                    pending += order.size - order.size_matched;
+                }
+            }
+        }
+    }
+    (least, least + pending)
+}
+
+/// why the market must not be entered, `None` when the account holds nothing in it
+pub async fn market_exposure(
+    exchange: &dyn Exchange,
+    tokens: &MarketResponse,
+) -> polymarket_client_sdk::Result<Option<String>> {
+    let token_ids = [&tokens.first_asset_id, &tokens.second_asset_id];
+
+    let resting = exchange
+        .open_orders()
+        .await?
+        .into_iter()
+        .filter(|o| token_ids.contains(&&o.token_id))
+        .count();
+    if resting > 0 {
+        return Ok(Some(format!("{resting} open orders already rest in it")));
+    }
+
+    for token_id in token_ids {
+        let balance = exchange.token_balance(token_id).await?;
+        if balance > Decimal::ZERO {
+            return Ok(Some(format!("already holding {balance} shares of {token_id}")));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,122 @@
+use crate::config::{BotConfig, ConfigError};
+use crate::metrics::CONFIG_RELOADS_TOTAL;
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+/// Re-reads the config file and publishes it to the asset tasks. Tasks pick the new
+/// parameters up at the start of their next cycle, a running cycle keeps its `HedgeConfig`.
+pub struct ConfigReloader {
+    path: String,
+    tx: watch::Sender<Arc<BotConfig>>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: String, config: BotConfig) -> (Arc<Self>, watch::Receiver<Arc<BotConfig>>) {
+        let (tx, rx) = watch::channel(Arc::new(config));
+        (Arc::new(ConfigReloader { path, tx }), rx)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<BotConfig>> {
+        self.tx.subscribe()
+    }
+
+    /// an invalid file is rejected as a whole and the running config stays
+    pub fn reload(&self, trigger: &str) -> Result<(), ConfigError> {
+        let config = match BotConfig::load(&self.path) {
+            Ok(config) => config,
+            Err(e) => {
+                CONFIG_RELOADS_TOTAL
+                    .with_label_values(&[trigger, "error"])
+                    .inc();
+                eprintln!("🔁 Config reload ({trigger}) rejected, keeping the current one: {e}");
+                return Err(e);
+            }
+        };
+
+        let current = self.tx.borrow().clone();
+        if current.mode != config.mode
+            || current.metrics_port != config.metrics_port
+            || current.store_path != config.store_path
+            || current.orphan_orders != config.orphan_orders
+            || current.shutdown != config.shutdown
+            || current.retry != config.retry
+            || current.clock.sync_interval != config.clock.sync_interval
+        {
+            println!(
+                "🔁 mode, metrics_port, store_path, orphan_orders, shutdown, retry and clock.sync_interval changes need a restart, ignoring them"
+            );
+        }
+        let running = current.assets();
+        for asset in config.assets() {
+            if !running.contains(&asset) {
+                println!("🔁 {asset} is new or enabled in the config, it starts trading after a restart");
+            }
+        }
+
+        for asset in running {
+            if !config.assets().contains(&asset) && config.settings(&asset).is_some() {
+                println!("🔁 {asset} was disabled, it stops trading after a restart");
+            }
+            if config.asset_info(&asset).is_some_and(|info| Some(info) != current.asset_info(&asset)) {
+                println!("🔁 {asset} slug_prefix, name and label changes need a restart, ignoring them");
+            }
+            match config.settings(&asset) {
+                Some(settings) => println!("🔁 {asset} from next cycle: {:?}", settings),
+                None => println!("🔁 {asset} was removed, it keeps its last settings"),
+            }
+        }
+        self.tx.send_replace(Arc::new(config));
+        CONFIG_RELOADS_TOTAL.with_label_values(&[trigger, "ok"]).inc();
+        Ok(())
+    }
+
+    /// reloads on SIGHUP and whenever the file's mtime changes
+    pub fn spawn_watchers(self: &Arc<Self>) {
+        #[cfg(unix)]
+        {
+            let reloader = self.clone();
+            tokio::spawn(async move {
+                use tokio::signal::unix::{SignalKind, signal};
+                let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+                while hangup.recv().await.is_some() {
+                    reloader.reload("sighup").ok();
+                }
+            });
+        }
+
+        let reloader = self.clone();
+        tokio::spawn(async move {
+            let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+            let mut last: Option<SystemTime> = modified(&reloader.path);
+            loop {
+                sleep(Duration::from_secs(5)).await;
+                let current = modified(&reloader.path);
+                if current.is_some() && current != last {
+                    last = current;
+                    reloader.reload("file").ok();
+                }
+            }
+        });
+    }
+}
+
+async fn reload_handler(State(reloader): State<Arc<ConfigReloader>>) -> (StatusCode, String) {
+    match reloader.reload("http") {
+        Ok(()) => (StatusCode::OK, "reloaded\n".to_string()),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+    }
+}
+
+/// `POST /reload` on the metrics server
+pub fn routes(reloader: Arc<ConfigReloader>) -> Router {
+    Router::new()
+        .route("/reload", post(reload_handler))
+        .with_state(reloader)
+}
@@ -0,0 +1,145 @@
+//! Retries of exchange calls: exponential backoff with jitter, bounded by attempts and a deadline.
+
+use crate::config::RetryConfig;
+use crate::asset::Asset;
+use crate::error::{BotError, ErrorPolicy};
+use crate::metrics::RETRIES_TOTAL;
+use rand::Rng;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+
+static POLICIES: OnceLock<RetryConfig> = OnceLock::new();
+
+/// per-operation policies from the config, set once at startup like the trading mode
+pub fn set_policies(config: RetryConfig) {
+    POLICIES.set(config).ok();
+}
+
+/// the configured policies, the defaults until `set_policies` was called
+pub fn policies() -> &'static RetryConfig {
+    POLICIES.get_or_init(RetryConfig::default)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// first try included
+    pub max_attempts: u32,
+    /// delay before the first retry, doubled for every next one
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// up to this fraction of a delay is taken off at random, so retries of
+    /// several assets don't hit the API at the same moment
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+    /// seconds after the first try no retry is started anymore
+    pub deadline: Option<u64>,
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay_ms: u64, max_delay_ms: u64, deadline: u64) -> Self {
+        RetryPolicy {
+            max_attempts,
+            base_delay_ms,
+            max_delay_ms,
+            jitter: default_jitter(),
+            deadline: Some(deadline),
+        }
+    }
+
+    /// problems with the policy itself, `name` is where it sits in the config
+    pub fn validate(&self, name: &str) -> Vec<String> {
+        let mut errors = vec![];
+        if self.max_attempts == 0 {
+            errors.push(format!("{name}.max_attempts: must be positive"));
+        }
+        if self.base_delay_ms > self.max_delay_ms {
+            errors.push(format!(
+                "{name}.base_delay_ms: must not exceed max_delay_ms ({}), got {}",
+                self.max_delay_ms, self.base_delay_ms
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            errors.push(format!("{name}.jitter: must be within [0, 1], got {}", self.jitter));
+        }
+        if self.deadline == Some(0) {
+            errors.push(format!("{name}.deadline: must be positive"));
+        }
+        errors
+    }
+
+    /// wait before retry number `retry` (1 for the first), without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(u64::MAX);
+        Duration::from_millis(self.base_delay_ms.saturating_mul(factor).min(self.max_delay_ms))
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        let cut = rand::thread_rng().gen_range(0.0..=self.jitter);
+        backoff.mul_f64(1.0 - cut)
+    }
+
+    /// Runs `op` until it succeeds, fails with an error that retrying won't fix, runs out
+    /// of attempts or passes the deadline. Retries are counted in `retries_total` by how
+    /// the call ended.
+    pub async fn run<T, F, Fut>(
+        &self,
+        asset: &Asset,
+        operation: &str,
+        mut op: F,
+    ) -> Result<T, BotError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, BotError>>,
+    {
+        let deadline = self.deadline.map(|s| Instant::now() + Duration::from_secs(s));
+        let mut retries = 0;
+
+        loop {
+            let err = match op().await {
+                Ok(value) => {
+                    count(asset, operation, retries, "success");
+                    return Ok(value);
+                }
+                Err(err) => err,
+            };
+            if err.policy() != ErrorPolicy::Retry {
+                count(asset, operation, retries, "non_retryable");
+                return Err(err);
+            }
+            if retries + 1 >= self.max_attempts {
+                count(asset, operation, retries, "exhausted");
+                return Err(err);
+            }
+
+            let delay = self.delay(retries + 1);
+            if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+                count(asset, operation, retries, "deadline");
+                return Err(err);
+            }
+            retries += 1;
+            println!(
+                "{operation} failed (attempt {}/{}): {err}, retrying in {}ms",
+                retries,
+                self.max_attempts,
+                delay.as_millis()
+            );
+            sleep(delay).await;
+        }
+    }
+}
+
+fn count(asset: &Asset, operation: &str, retries: u32, outcome: &str) {
+    if retries > 0 {
+        RETRIES_TOTAL
+            .with_label_values(&[asset.label(), operation, outcome])
+            .inc_by(retries as u64);
+    }
+}
@@ -0,0 +1,830 @@
+use crate::clock::{Clock, ExchangeClock, SystemClock, run_clock_sync};
+use crate::config::{BotConfig, PaperConfig, config_path};
+use crate::control::{self, Control};
+use crate::cycle::{CycleAction, CycleEvent, CycleMachine, CycleState, Market};
+use crate::asset::Asset;
+use crate::dto::{MarketResponse, TradingMode};
+use crate::error::{BotError, ErrorPolicy};
+use crate::gamma::GammaClient;
+use crate::market_data::{MARKET_CHANNEL_URL, MarketData, run_book_poller, run_market_channel};
+use crate::metrics::{CYCLES_SKIPPED_TOTAL, ENTRY_PRICE, PNL, set_mode, start_metrics_server};
+use crate::outcome::CycleOutcome;
+use crate::paper::{BookSource, LiveBookSource, PaperExchange, SimulatedBookSource};
+use crate::pricing::{EntryQuote, PricingMode, entry_prices};
+use crate::reconcile::{market_exposure, reconcile};
+use crate::reload::{self, ConfigReloader};
+use crate::retry::set_policies;
+use crate::settings::StrategySettings;
+use crate::shutdown::Shutdown;
+use crate::sizing::{SizingInput, SizingMode, order_size};
+use crate::status::{self, StatusSource};
+use crate::store::{CycleJournal, CycleRecord, CycleStage, Store};
+use crate::user_channel::{
+    ApiCreds, OrderFeed, OrderSubscription, USER_CHANNEL_URL, run_user_channel,
+};
+use crate::utils::{
+    allow_trade, cancel_order_with_retry, clear_entries, flatten_position, get_order_with_retry,
+    open_start_positions, place_hedge_order,
+};
+use crate::exchange::{Exchange, PolymarketExchange};
+use crate::funds::{available, required_collateral, shortfall, watch_balance};
+use alloy::signers::Signer as _;
+use alloy::signers::local::LocalSigner;
+use alloy_primitives::Address;
+use axum::Router;
+use polymarket_client_sdk::clob::types::SignatureType;
+use polymarket_client_sdk::clob::{Client, Config};
+use polymarket_client_sdk::{POLYGON, PRIVATE_KEY_VAR};
+use reqwest::Client as http_client;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive as _;
+use std::collections::VecDeque;
+use std::env;
+use std::mem::discriminant;
+use std::str::FromStr as _;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+/// seconds a polled book may be old and still price an entry
+const BOOK_MAX_AGE: i64 = 10;
+/// time past the shutdown deadline to flatten what is still open
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(60);
+
+/// what the websockets deliver, each polled while its socket is down
+#[derive(Clone)]
+pub struct Feeds {
+    pub orders: OrderFeed,
+    pub books: MarketData,
+}
+
+/// paper mode needs no keys, it only reads public order books
+fn paper_exchange(config: &PaperConfig, books: Arc<dyn BookSource>) -> PaperExchange {
+    println!("📝 Paper trading with balance {}", config.balance);
+
+    PaperExchange::new(Box::new(books), config.balance)
+}
+
+/// the live client and the API credentials it was given, `None` when they are derived
+async fn live_exchange() -> Result<(PolymarketExchange, Option<ApiCreds>), BotError> {
+    let private_key = env::var(PRIVATE_KEY_VAR)
+        .map_err(|_| BotError::Fatal(format!("{PRIVATE_KEY_VAR} is not set")))?;
+    let funder_addr =
+        env::var("PM_ADDRESS").map_err(|_| BotError::Fatal("PM_ADDRESS is not set".into()))?;
+    let address = Address::parse_checksummed(funder_addr, None)
+        .map_err(|e| BotError::Fatal(format!("PM_ADDRESS is not a checksummed address: {e}")))?;
+    let signer = LocalSigner::from_str(&private_key)
+        .map_err(|e| BotError::Fatal(format!("{PRIVATE_KEY_VAR} is not a valid key: {e}")))?
+        .with_chain_id(Some(POLYGON));
+    // the user channel needs the secret, which the client does not hand out once derived
+    let creds = ApiCreds::from_env();
+    let client = Client::new("https://clob.polymarket.com", Config::default())?;
+    let mut builder = client
+        .authentication_builder(&signer)
+        .funder(address)
+        .signature_type(SignatureType::GnosisSafe);
+    if let Some(creds) = &creds {
+        builder = builder.credentials(creds.credentials()?);
+    }
+    let client = Arc::new(builder.authenticate().await?);
+
+    let ok = client.ok().await?;
+    println!("Client setup ok?: {ok}");
+
+    Ok((PolymarketExchange::new(client, signer), creds))
+}
+
+/// authenticates once and drives every asset in its own task,
+/// `None` runs every enabled asset of the config
+pub async fn run(assets: Option<Vec<Asset>>) -> anyhow::Result<()> {
+    dotenvy::dotenv().ok();
+
+    let path = config_path();
+    let config = BotConfig::load(&path)?;
+    let enabled = config.assets();
+    let assets = assets.unwrap_or_else(|| enabled.clone());
+    for asset in &assets {
+        if config.settings(asset).is_none() {
+            anyhow::bail!("no [assets.{asset}] section in {path}");
+        }
+        if !enabled.contains(asset) {
+            anyhow::bail!("{asset} is disabled in {path}");
+        }
+    }
+
+    config.register_assets();
+    set_mode(config.mode);
+    set_policies(config.retry.clone());
+    let shutdown = Shutdown::listen(&config.shutdown);
+    let (reloader, _) = ConfigReloader::new(path, config.clone());
+    reloader.spawn_watchers();
+
+    let store = Store::open(&config.store_path)?;
+    let mut paper = None;
+    // simulated books move on every read, the real market channel has nothing to say about them
+    let simulated = config.mode == TradingMode::Paper && config.paper.book == "simulated";
+    let books: Arc<dyn BookSource> = if simulated {
+        Arc::new(SimulatedBookSource::default())
+    } else {
+        Arc::new(LiveBookSource::new(Client::default()))
+    };
+    // the grace windows are counted on the exchange's clock, not the host's
+    let clock = Arc::new(ExchangeClock::default());
+    let time_client = Client::default();
+    if let Err(e) = clock.sync(&time_client).await {
+        eprintln!("🕰️ Failed to measure the clock skew: {e}, no cycle starts until it is");
+    }
+    tokio::spawn(run_clock_sync(
+        clock.clone(),
+        time_client,
+        Duration::from_secs(config.clock.sync_interval),
+    ));
+    let feeds = Feeds {
+        orders: OrderFeed::default(),
+        books: MarketData::new(books.clone(), clock.clone()),
+    };
+    tokio::spawn(run_book_poller(feeds.books.clone()));
+    if !simulated {
+        tokio::spawn(run_market_channel(MARKET_CHANNEL_URL.into(), feeds.books.clone()));
+    }
+    let exchange: Arc<dyn Exchange> = match config.mode {
+        TradingMode::Live => {
+            let (exchange, creds) = live_exchange().await?;
+            match creds {
+                Some(creds) => {
+                    tokio::spawn(run_user_channel(
+                        USER_CHANNEL_URL.into(),
+                        creds.credentials()?,
+                        feeds.orders.clone(),
+                    ));
+                }
+                None => println!(
+                    "CLOB_API_KEY, CLOB_SECRET and CLOB_PASS_PHRASE are not set, orders are polled"
+                ),
+            }
+            Arc::new(exchange)
+        }
+        TradingMode::Paper => {
+            // paper orders only live in memory, there is nothing to resume
+            let abandoned = store.abandon_unfinished("paper exchange restarted")?;
+            if abandoned > 0 {
+                println!("📝 Dropped {abandoned} unfinished paper cycles");
+            }
+            let exchange = Arc::new(paper_exchange(&config.paper, books));
+            paper = Some(exchange.clone());
+            exchange
+        }
+    };
+    reconcile(exchange.as_ref(), &store, config.orphan_orders, &assets).await?;
+
+    let control = Control::new(shutdown.clone(), exchange.clone(), store.clone(), clock);
+    // the store stamps rows on the host clock, the session starts on the same one
+    let started_at = SystemClock.now();
+    let routes = match env::var("CONTROL_TOKEN") {
+        Ok(token) if !token.is_empty() => control::authenticated(
+            reload::routes(reloader.clone()).merge(control::routes(control.clone())),
+            token,
+        ),
+        _ => {
+            println!("CONTROL_TOKEN is not set, /halt, /resume, /flatten and /reload are disabled");
+            Router::new()
+        }
+    };
+    let status = StatusSource {
+        mode: config.mode,
+        assets: assets.clone(),
+        started_at,
+        store: store.clone(),
+        config: reloader.subscribe(),
+        control: control.clone(),
+    };
+    start_metrics_server(config.metrics_port, routes.merge(status::routes(status)));
+    let gamma = GammaClient::new(http_client::new());
+    let mut tasks = JoinSet::new();
+    for asset in assets {
+        println!("Starting {asset}");
+
+        let exchange = exchange.clone();
+        let gamma = gamma.clone();
+        let config = reloader.subscribe();
+        let store = store.clone();
+        let control = control.clone();
+        let feeds = feeds.clone();
+        tasks.spawn(async move {
+            // one asset failing must not take the others down, unless nothing can work anymore.
+            // During a shutdown the restart resumes and winds down the open cycle
+            loop {
+                match run_asset(
+                    exchange.as_ref(),
+                    &gamma,
+                    asset,
+                    config.clone(),
+                    store.clone(),
+                    control.clone(),
+                    &feeds,
+                )
+                .await
+                {
+                    Ok(()) => return Ok(()),
+                    Err(e) if e.policy() == ErrorPolicy::Stop => {
+                        eprintln!("💀 {asset} failed for good, stopping the bot: {e}");
+                        return Err(e);
+                    }
+                    Err(e) => eprintln!("{asset} loop failed: {e}, restarting in 5 seconds"),
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+
+    let finished = async {
+        while let Some(task) = tasks.join_next().await {
+            task??;
+        }
+        anyhow::Ok(())
+    };
+    tokio::select! {
+        result = finished => result?,
+        _ = shutdown.expired(SHUTDOWN_GRACE) => {
+            eprintln!("🛑 Deadline passed, exiting with cycles still open, they are resumed on the next start");
+        }
+    }
+
+    println!("📊 Session summary:");
+    for line in store.session_summary(started_at)? {
+        println!(
+            "📊 {}: {} cycles, {} wins, {} losses, {} left open",
+            line.asset, line.cycles, line.wins, line.losses, line.unfinished
+        );
+    }
+    if let Some(paper) = paper {
+        println!("📊 Paper balance: {}", paper.balance());
+    }
+    Ok(())
+}
+
+/// wins, losses and realized PnL of one asset, kept in the store across restarts
+struct Tally {
+    asset: Asset,
+    wins: u32,
+    losses: u32,
+    pnl: Decimal,
+}
+
+impl Tally {
+    fn load(store: &Store, asset: Asset) -> Result<Self, BotError> {
+        let (wins, losses) = store.results(asset)?;
+        let tally = Tally {
+            asset,
+            wins,
+            losses,
+            pnl: store.realized_pnl(asset)?,
+        };
+        tally.publish();
+        Ok(tally)
+    }
+
+    /// `None` is a cycle that was abandoned, it counts for nothing
+    fn add(&mut self, outcome: Option<&CycleOutcome>) {
+        let Some(outcome) = outcome else {
+            return;
+        };
+        match outcome.result() {
+            1 => self.wins += 1,
+            -1 => self.losses += 1,
+            _ => {}
+        }
+        self.pnl += outcome.pnl();
+        self.publish();
+    }
+
+    fn publish(&self) {
+        PNL.with_label_values(&[self.asset.label()])
+            .set(self.pnl.to_f64().unwrap_or_default());
+    }
+}
+
+/// fee rate of the market, a failed lookup counts as no fee
+async fn fee_rate_bps(exchange: &dyn Exchange, token_id: &str) -> u32 {
+    exchange.fee_rate_bps(token_id).await.unwrap_or_else(|e| {
+        eprintln!("Failed to get the fee rate of {token_id}: {e}, PnL is counted without fees");
+        0
+    })
+}
+
+pub async fn run_asset(
+    exchange: &dyn Exchange,
+    gamma: &GammaClient,
+    asset: Asset,
+    config: watch::Receiver<Arc<BotConfig>>,
+    store: Arc<Store>,
+    control: Control,
+    feeds: &Feeds,
+) -> Result<(), BotError> {
+    let Some(mut settings) = config.borrow().settings(&asset) else {
+        return Err(BotError::Fatal(format!("no [assets.{asset}] section in the config")));
+    };
+    let mut tally = Tally::load(&store, asset)?;
+    let mut refused_market = None;
+
+    if let Some(cycle) = store.unfinished_cycle(asset)? {
+        let _books = feeds.books.watch(&[
+            &cycle.tokens.first_asset_id,
+            &cycle.tokens.second_asset_id,
+        ]);
+        let outcome =
+            resume_cycle(exchange, &store, &control, &feeds.orders, cycle, &settings).await?;
+        tally.add(outcome.as_ref());
+    }
+
+    loop {
+        let shutdown = control.shutdown();
+        if shutdown.requested() {
+            println!(
+                "🛑 {asset} stopped, win count: {}, loss count: {}, pnl: {}",
+                tally.wins, tally.losses, tally.pnl
+            );
+            return Ok(());
+        }
+        if control.halted() {
+            control.sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+        // a reload lands here, the cycle below keeps what it started with
+        if let Some(reloaded) = config.borrow().settings(&asset) {
+            settings = reloaded;
+        }
+        let max_skew = config.borrow().clock.max_skew;
+        match control.clock().skew() {
+            Some(skew) if skew.unsigned_abs() <= max_skew * 1_000 => {}
+            Some(skew) => {
+                println!("⛔ Not trading {asset}, the clock is {skew}ms off the exchange, over {max_skew}s");
+                control.sleep(Duration::from_secs(30)).await;
+                continue;
+            }
+            None => {
+                println!("⛔ Not trading {asset} until the clock skew was measured");
+                control.sleep(Duration::from_secs(30)).await;
+                continue;
+            }
+        }
+
+        let timestamp = settings.market.target(control.clock().now());
+        if !allow_trade(timestamp, &settings.dont_allow_trade_before, control.clock()) {
+            println!("Not time to trade already, sleeping for 30 seconds");
+            control.sleep(Duration::from_secs(30)).await;
+            continue;
+        }
+        if refused_market == Some(timestamp) {
+            control.sleep(Duration::from_secs(30)).await;
+            continue;
+        }
+        let market = match gamma.market(&settings.market, timestamp, asset).await {
+            Ok(market) => market,
+            Err(e) => match e.policy() {
+                ErrorPolicy::Retry => {
+                    eprintln!("{asset} market {timestamp} not ready: {e}, retrying");
+                    control.sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+                ErrorPolicy::AbortCycle => {
+                    eprintln!("⛔ Not trading {asset} market {timestamp}: {e}");
+                    refused_market = Some(timestamp);
+                    continue;
+                }
+                ErrorPolicy::Stop => return Err(e),
+            },
+        };
+        if let Some(reason) = market.unfit(&settings) {
+            println!("⛔ Not trading {asset} market {}: {reason}", market.slug);
+            refused_market = Some(timestamp);
+            continue;
+        }
+        let tick_size = market.tick_size;
+        let min_order_size = market.min_order_size;
+        let neg_risk = market.neg_risk;
+        let tokens = market.tokens;
+
+        match market_exposure(exchange, &tokens).await.map_err(BotError::from) {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                println!("⛔ Not trading {asset} market {timestamp}: {reason}");
+                refused_market = Some(timestamp);
+                continue;
+            }
+            Err(e) if e.policy() == ErrorPolicy::Stop => return Err(e),
+            Err(e) => {
+                eprintln!("Failed to check exposure in {asset} market {timestamp}: {e}, retrying");
+                control.sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        }
+
+        println!(
+            "win count: {}, loss count: {}, pnl: {} | {}",
+            tally.wins, tally.losses, tally.pnl, asset
+        );
+
+        let _books = feeds.books.watch(&[&tokens.first_asset_id, &tokens.second_asset_id]);
+        let quote = match price_entries(&feeds.books, &settings, tick_size, &tokens, &control).await {
+            Ok(quote) => quote,
+            Err(reason) => {
+                println!("⛔ Not trading {asset} market {timestamp}: {reason}");
+                refused_market = Some(timestamp);
+                continue;
+            }
+        };
+        println!("💲 {asset} market {timestamp} entries: {quote}");
+        for (side, price) in ["up", "down"].into_iter().zip(quote.prices) {
+            ENTRY_PRICE
+                .with_label_values(&[asset.label(), side])
+                .set(price.to_f64().unwrap_or_default());
+        }
+        let collateral = match exchange.collateral(neg_risk).await.map_err(BotError::from) {
+            Ok(collateral) => collateral,
+            Err(e) if e.policy() == ErrorPolicy::Stop => return Err(e),
+            Err(e) => {
+                eprintln!("Failed to read the balance for {asset} market {timestamp}: {e}, retrying");
+                control.sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        watch_balance(collateral.balance, config.borrow().funds.alert_below);
+        // running cycles of the other assets are yet to spend what they set aside
+        let collateral = available(&collateral, control.reservations().others(asset));
+        let sizing = SizingInput {
+            bankroll: collateral.balance,
+            prices: quote.prices,
+            hedge_price: settings.hedge_enter_price,
+            wins: tally.wins,
+            losses: tally.losses,
+            min_order_size,
+        };
+        let size = match order_size(&settings.sizing, settings.order_size, &sizing) {
+            Ok(size) => size,
+            Err(reason) => {
+                println!("⛔ Not trading {asset} market {timestamp}: {reason}");
+                refused_market = Some(timestamp);
+                continue;
+            }
+        };
+        if settings.sizing.mode != SizingMode::Fixed {
+            println!(
+                "📐 {asset} market {timestamp} size {size} off a bankroll of {}",
+                collateral.balance
+            );
+        }
+        let need = required_collateral(size, quote.prices, settings.hedge_enter_price);
+        if let Some(shortfall) = shortfall(&collateral, need) {
+            println!("⛔ Not trading {asset} market {timestamp}: {shortfall}");
+            CYCLES_SKIPPED_TOTAL
+                .with_label_values(&[asset.label(), shortfall.reason()])
+                .inc();
+            refused_market = Some(timestamp);
+            continue;
+        }
+        let _reserved = control.reservations().reserve(asset, need);
+        // the cycle keeps its size, the next one is sized again
+        let cycle_settings = StrategySettings {
+            order_size: size,
+            ..settings.clone()
+        };
+
+        let fee_rate_bps = fee_rate_bps(exchange, &tokens.first_asset_id).await;
+        let journal = store.start_cycle(
+            asset,
+            timestamp,
+            settings.market.end(timestamp),
+            settings.dont_allow_holding_before,
+            &tokens,
+        )?;
+        let mut machine = CycleMachine::new(asset, cycle_settings).with_fee_rate_bps(fee_rate_bps);
+        let market = CycleEvent::MarketOpened(Market {
+            timestamp,
+            tokens,
+            prices: quote.prices,
+        });
+        let outcome =
+            drive_cycle(exchange, &journal, &control, &feeds.orders, &mut machine, Some(market))
+                .await?;
+        tally.add(outcome.as_ref());
+    }
+}
+
+/// Entry prices of the market by the settings. Book pricing waits a few seconds for the
+/// books of both tokens, a side whose book doesn't come or is stale gets the fixed price
+async fn price_entries(
+    books: &MarketData,
+    settings: &StrategySettings,
+    tick_size: Decimal,
+    tokens: &MarketResponse,
+    control: &Control,
+) -> Result<EntryQuote, String> {
+    let fresh = |token_id: &str| {
+        books
+            .book(token_id)
+            .filter(|book| book.fresh(control.clock().now(), BOOK_MAX_AGE))
+            .map(|book| book.book)
+    };
+    let mut snapshots = [None, None];
+    if settings.entry.mode == PricingMode::Book {
+        for _ in 0..10 {
+            snapshots = [fresh(&tokens.first_asset_id), fresh(&tokens.second_asset_id)];
+            if snapshots.iter().all(Option::is_some) {
+                break;
+            }
+            control.sleep(Duration::from_millis(500)).await;
+        }
+    }
+    let [first, second] = &snapshots;
+    entry_prices(
+        &settings.entry,
+        settings.limit_enter_price,
+        tick_size,
+        [first.as_ref(), second.as_ref()],
+    )
+}
+
+/// picks up a cycle the previous run left unfinished, returns its outcome like `drive_cycle`
+async fn resume_cycle(
+    exchange: &dyn Exchange,
+    store: &Arc<Store>,
+    control: &Control,
+    feed: &OrderFeed,
+    cycle: CycleRecord,
+    settings: &StrategySettings,
+) -> Result<Option<CycleOutcome>, BotError> {
+    let journal = store.journal(cycle.id);
+    println!(
+        "♻️ Resuming {} cycle {} of market {} at stage {}",
+        cycle.asset, cycle.id, cycle.timestamp, cycle.stage
+    );
+
+    if control.clock().now() >= cycle.market_end {
+        println!("♻️ Market {} has already ended, nothing left to manage", cycle.timestamp);
+        journal.abandon("market ended while the bot was down");
+        return Ok(None);
+    }
+
+    let orders = store.cycle_orders(cycle.id)?;
+    let Some(machine) = CycleMachine::resume(&cycle, &orders, settings.clone()) else {
+        println!(
+            "♻️ Cycle {} is {} without enough stored state, nothing to resume",
+            cycle.id, cycle.stage
+        );
+        journal.abandon("inconsistent stored state");
+        return Ok(None);
+    };
+    let entries: Vec<_> = orders.iter().filter(|order| order.role == "entry").collect();
+    let _reserved = match entries.as_slice() {
+        [first, second] => {
+            let hedge_price = cycle
+                .hedge
+                .as_ref()
+                .map_or(settings.hedge_enter_price, |hedge| hedge.hedge_enter_price);
+            let need = required_collateral(first.size, [first.price, second.price], hedge_price);
+            Some(control.reservations().reserve(cycle.asset, need))
+        }
+        _ => None,
+    };
+    let fee_rate_bps = fee_rate_bps(exchange, &cycle.tokens.first_asset_id).await;
+    let mut machine = machine.with_fee_rate_bps(fee_rate_bps);
+    drive_cycle(exchange, &journal, control, feed, &mut machine, None).await
+}
+
+/// Runs one cycle to the end: halt and flatten, order updates and clock ticks go into the
+/// machine, its orders, cancels and closes go out. A failing step is retried or winds the
+/// cycle down per [`BotError::policy`], `None` when the cycle had to be abandoned
+async fn drive_cycle(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    control: &Control,
+    feed: &OrderFeed,
+    machine: &mut CycleMachine,
+    mut next: Option<CycleEvent>,
+) -> Result<Option<CycleOutcome>, BotError> {
+    let asset = machine.asset();
+    let mut entered = !matches!(machine.state(), CycleState::AwaitingMarket { .. });
+    let mut aborting = false;
+    let updates = feed.subscribe();
+
+    loop {
+        let round = match drive_round(
+            exchange,
+            journal,
+            control,
+            machine,
+            next.take(),
+            aborting,
+            &mut entered,
+        )
+        .await
+        {
+            Ok(()) => feed_orders(exchange, journal, control.clock(), &updates, machine).await,
+            Err(e) => Err(e),
+        };
+
+        if let Some(outcome) = machine.result() {
+            if !entered {
+                journal.abandon("stopped before entry");
+                return Ok(None);
+            }
+            println!(
+                "🏁 {asset} cycle {} {outcome}, pnl: {}, fees: {}",
+                journal.id,
+                outcome.pnl(),
+                outcome.fees()
+            );
+            journal.finish(outcome);
+            return Ok(Some(outcome.clone()));
+        }
+
+        match round {
+            // whatever was just cancelled or placed is polled again right away
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => match e.policy() {
+                ErrorPolicy::Retry => eprintln!("{asset} cycle {}: {e}, retrying", journal.id),
+                ErrorPolicy::AbortCycle if !aborting => {
+                    eprintln!("⛔ {asset} cycle {}: {e}, winding the cycle down", journal.id);
+                    aborting = true;
+                }
+                ErrorPolicy::AbortCycle => {
+                    eprintln!(
+                        "⛔ {asset} cycle {}: {e} while winding down, abandoning it, /flatten sells what is left",
+                        journal.id
+                    );
+                    journal.abandon(&format!("aborted: {e}"));
+                    return Ok(None);
+                }
+                ErrorPolicy::Stop => return Err(e),
+            },
+        }
+        // an order update wakes the cycle early, the clock still ticks every second
+        updates.changed(Duration::from_secs(1)).await;
+        next = Some(CycleEvent::Tick);
+    }
+}
+
+/// halt or flatten, then `next`
+async fn drive_round(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    control: &Control,
+    machine: &mut CycleMachine,
+    next: Option<CycleEvent>,
+    aborting: bool,
+    entered: &mut bool,
+) -> Result<(), BotError> {
+    if aborting || control.flatten_now() {
+        feed(exchange, journal, control.clock(), machine, CycleEvent::Flatten).await?;
+    } else if control.halted() || control.shutdown().requested() {
+        feed(exchange, journal, control.clock(), machine, CycleEvent::Halt).await?;
+    }
+    if let Some(event) = next {
+        feed(exchange, journal, control.clock(), machine, event).await?;
+    }
+    *entered |= !matches!(machine.state(), CycleState::AwaitingMarket { .. } | CycleState::Done(_));
+    Ok(())
+}
+
+/// the watched orders as the user channel reported them, polled when it can't tell.
+/// `true` when they were acted on
+async fn feed_orders(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    clock: &dyn Clock,
+    updates: &OrderSubscription,
+    machine: &mut CycleMachine,
+) -> Result<bool, BotError> {
+    let asset = machine.asset();
+    let watched = machine.watched();
+    if watched.is_empty() {
+        return Ok(false);
+    }
+    let mut orders = Vec::with_capacity(watched.len());
+    for order_id in &watched {
+        updates.track(order_id);
+        let order = match updates.latest(order_id) {
+            Some(order) => order,
+            None => {
+                let order = get_order_with_retry(exchange, order_id, &asset).await?;
+                updates.polled(&order);
+                order
+            }
+        };
+        journal.order_status(&order);
+        orders.push(order);
+    }
+    feed(exchange, journal, clock, machine, CycleEvent::Orders(orders)).await
+}
+
+/// steps the machine and carries out its actions, their results are fed straight back.
+/// `true` when anything was done
+async fn feed(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    clock: &dyn Clock,
+    machine: &mut CycleMachine,
+    event: CycleEvent,
+) -> Result<bool, BotError> {
+    let asset = machine.asset();
+    let mut events = VecDeque::from([event]);
+    let mut acted = false;
+
+    while let Some(event) = events.pop_front() {
+        let before = (discriminant(machine.state()), machine.stage());
+        let actions = machine.step(event, clock.now());
+        if discriminant(machine.state()) != before.0 {
+            match machine.state() {
+                CycleState::LegMatched { hedge, .. } | CycleState::Hedging { hedge, .. } => {
+                    journal.hedging(hedge)
+                }
+                CycleState::StopLoss { reason, .. } | CycleState::Flattening { reason, .. }
+                    if before.1 != CycleStage::Closing =>
+                {
+                    journal.closing(reason.as_str())
+                }
+                _ => {}
+            }
+        }
+
+        for action in actions {
+            acted = true;
+            // the machine has already moved on as if the action went through,
+            // so it is retried until it does or fails for good
+            loop {
+                match act(exchange, journal, clock, &asset, &action).await {
+                    Ok(event) => {
+                        events.extend(event);
+                        break;
+                    }
+                    Err(e) if e.policy() == ErrorPolicy::Retry => {
+                        eprintln!("{asset} {action:?} failed: {e}, retrying");
+                        clock.sleep(Duration::from_secs(1)).await;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+    Ok(acted)
+}
+
+/// carries out one action, the event is its result for the machine
+async fn act(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    clock: &dyn Clock,
+    asset: &Asset,
+    action: &CycleAction,
+) -> Result<Option<CycleEvent>, BotError> {
+    match action {
+        CycleAction::PlaceEntries { tokens, size, prices } => {
+            match open_start_positions(exchange, *size, *prices, tokens, asset).await {
+                Ok(orders) => {
+                    println!("Opened positions: {:?}", orders);
+                    journal.entry_placed(&orders, *size, *prices);
+                    Ok(Some(CycleEvent::EntriesPlaced(orders)))
+                }
+                Err(e) => {
+                    eprintln!("Error opening positions: {e}");
+                    // the machine clears what is left on the next tick and places them again
+                    // unless that can't help
+                    let retry = match e.policy() {
+                        ErrorPolicy::Retry => true,
+                        ErrorPolicy::AbortCycle => false,
+                        ErrorPolicy::Stop => return Err(e),
+                    };
+                    Ok(Some(CycleEvent::EntriesFailed { retry }))
+                }
+            }
+        }
+        CycleAction::ClearEntries { tokens } => {
+            let (cancelled, held) = clear_entries(exchange, tokens, asset).await?;
+            Ok(Some(CycleEvent::EntriesCleared { cancelled, held }))
+        }
+        CycleAction::Cancel(order_id) => {
+            cancel_order_with_retry(exchange, order_id, asset).await?;
+            Ok(None)
+        }
+        CycleAction::PlaceHedge { token_id, size, price } => {
+            let order = place_hedge_order(exchange, token_id.clone(), *size, *price, asset).await?;
+            journal.hedge_placed(&order, *size, *price);
+            Ok(Some(CycleEvent::HedgePlaced(order)))
+        }
+        CycleAction::Close { token_id, size } => {
+            let sold = flatten_position(exchange, journal, asset, token_id, *size).await;
+            Ok(Some(CycleEvent::Closed {
+                token_id: token_id.clone(),
+                sold,
+            }))
+        }
+        CycleAction::Wait(duration) => {
+            clock.sleep(*duration).await;
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use crate::market::MarketFamily;
+use crate::pricing::EntryPricing;
+use crate::sizing::Sizing;
+use rust_decimal::Decimal;
+
+/// strategy parameters of a single asset, see `BotConfig::settings`
+#[derive(Debug, Clone)]
+pub struct StrategySettings {
+    /// shares of a cycle, the fixed size when `sizing` sizes off the bankroll
+    pub order_size: Decimal,
+    pub limit_enter_price: Decimal,
+    pub hedge_enter_price: Decimal,
+    pub dont_allow_trade_before: i64,
+    pub dont_allow_holding_before: i64,
+    pub stop_loss_after: i64,
+    /// the markets the cycles trade
+    pub market: MarketFamily,
+    /// how the entry orders are priced, `limit_enter_price` is the fixed price
+    pub entry: EntryPricing,
+    /// how many shares a cycle enters with
+    pub sizing: Sizing,
+}
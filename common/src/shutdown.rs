@@ -0,0 +1,82 @@
+use crate::config::{ShutdownConfig, ShutdownHedge};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{Instant, sleep_until};
+
+/// Ctrl-C / SIGTERM as seen by the asset tasks. Holds the deadline once a shutdown
+/// was requested, a second signal exits on the spot.
+#[derive(Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<Option<Instant>>,
+    hedge: ShutdownHedge,
+}
+
+impl Shutdown {
+    pub fn listen(config: &ShutdownConfig) -> Self {
+        let (tx, rx) = watch::channel(None);
+        let deadline = Duration::from_secs(config.deadline);
+
+        tokio::spawn(async move {
+            signal().await;
+            println!(
+                "🛑 Shutdown requested, {}s to wind down, signal again to exit now",
+                deadline.as_secs()
+            );
+            tx.send_replace(Some(Instant::now() + deadline));
+
+            signal().await;
+            eprintln!("🛑 Second signal, exiting without winding down");
+            std::process::exit(130);
+        });
+
+        Shutdown {
+            rx,
+            hedge: config.hedge,
+        }
+    }
+
+    pub fn requested(&self) -> bool {
+        self.rx.borrow().is_some()
+    }
+
+    /// open positions have to be closed now instead of hedged:
+    /// right away with `flatten`, after the deadline with `finish`
+    pub fn flatten_now(&self) -> bool {
+        match *self.rx.borrow() {
+            None => false,
+            Some(deadline) => self.hedge == ShutdownHedge::Flatten || Instant::now() >= deadline,
+        }
+    }
+
+    /// resolves once a shutdown is requested
+    pub async fn wait(&self) {
+        let mut rx = self.rx.clone();
+        if rx.wait_for(Option::is_some).await.is_err() {
+            // the listener is gone, no shutdown is ever coming
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// resolves `grace` after the deadline, whatever is still running then gets dropped
+    pub async fn expired(&self, grace: Duration) {
+        self.wait().await;
+        let deadline = *self.rx.borrow();
+        if let Some(deadline) = deadline {
+            sleep_until(deadline + grace).await;
+        }
+    }
+}
+
+async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+}
@@ -0,0 +1,85 @@
+//! Shares a cycle enters with. The fixed policy is `order_size`, the others size off the
+//! collateral balance: a fraction of it, or a fraction of the Kelly stake for the edge the
+//! entry and hedge prices leave.
+
+use crate::funds::collateral_per_share;
+use crate::utils::floor_dp;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizingMode {
+    /// `order_size` shares
+    #[default]
+    Fixed,
+    /// `fraction` of the bankroll tied up per cycle
+    Fraction,
+    /// `fraction` of the Kelly stake
+    Kelly,
+}
+
+/// how a cycle is sized, see [`order_size`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sizing {
+    pub mode: SizingMode,
+    /// share of the bankroll with `Fraction`, share of the Kelly stake with `Kelly`
+    pub fraction: Decimal,
+    /// most USDC one cycle may tie up
+    pub max_notional: Option<Decimal>,
+    /// what a stopped out leg is expected to sell at, the loss of a cycle with `Kelly`
+    pub exit_price: Decimal,
+}
+
+/// what one cycle is sized from
+#[derive(Debug, Clone)]
+pub struct SizingInput {
+    /// USDC of the account, only read when the policy needs it
+    pub bankroll: Decimal,
+    /// entry prices of both tokens
+    pub prices: [Decimal; 2],
+    pub hedge_price: Decimal,
+    /// finished cycles of the asset
+    pub wins: u32,
+    pub losses: u32,
+    pub min_order_size: Decimal,
+}
+
+/// Shares to enter with, `fixed` is `order_size`. Kelly bets on the hedge filling, it wins
+/// what entry and hedge leave below 1 and loses the entry down to `exit_price`. The chance
+/// is the asset's record with a win and a loss added, so a new asset starts at even.
+/// `Err` when there is no edge or the size ends up below the market minimum.
+pub fn order_size(sizing: &Sizing, fixed: Decimal, input: &SizingInput) -> Result<Decimal, String> {
+    // what the pre-trade check asks for, a size it passes with the whole bankroll
+    let per_share = collateral_per_share(input.prices, input.hedge_price);
+    let size = match sizing.mode {
+        SizingMode::Fixed => fixed,
+        SizingMode::Fraction => input.bankroll * sizing.fraction / per_share,
+        SizingMode::Kelly => {
+            let entry = input.prices[0].max(input.prices[1]);
+            let win = Decimal::ONE - entry - input.hedge_price;
+            let loss = entry - sizing.exit_price;
+            let p = Decimal::from(input.wins + 1) / Decimal::from(input.wins + input.losses + 2);
+            let edge = p * win - (Decimal::ONE - p) * loss;
+            if win <= Decimal::ZERO || loss <= Decimal::ZERO || edge <= Decimal::ZERO {
+                return Err(format!(
+                    "no edge: winning {win} with chance {p:.2} against losing {loss}"
+                ));
+            }
+            sizing.fraction * input.bankroll * edge / (win * loss)
+        }
+    };
+    let size = match sizing.max_notional {
+        Some(cap) => size.min(cap / per_share),
+        None => size,
+    };
+
+    let size = floor_dp(size, 2);
+    if size.is_zero() || size < input.min_order_size {
+        return Err(format!(
+            "{size} shares is below the minimum order size {}",
+            input.min_order_size
+        ));
+    }
+    Ok(size)
+}
@@ -0,0 +1,135 @@
+use crate::config::BotConfig;
+use crate::control::Control;
+use crate::asset::Asset;
+use crate::dto::{HedgeConfig, TradingMode};
+use crate::store::{CycleStage, Store, StoredOrder};
+use crate::utils::allow_trade;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// what `/status` reads from, everything comes from the store so nothing has to be
+/// reported by the asset tasks
+#[derive(Clone)]
+pub struct StatusSource {
+    pub mode: TradingMode,
+    pub assets: Vec<Asset>,
+    pub started_at: i64,
+    pub store: Arc<Store>,
+    pub config: watch::Receiver<Arc<BotConfig>>,
+    pub control: Control,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub mode: TradingMode,
+    /// running, halted or flattening
+    pub trading: &'static str,
+    /// milliseconds the host clock is behind the exchange, `None` before it was measured
+    pub clock_skew_ms: Option<i64>,
+    pub assets: Vec<AssetStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssetStatus {
+    pub asset: String,
+    /// family of the markets it trades and the one a new cycle would target right now,
+    /// `None` once the asset was removed from the config
+    pub market: Option<String>,
+    pub target_market: Option<i64>,
+    /// counted over this session
+    pub wins: u32,
+    pub losses: u32,
+    /// latest cycle, `None` before the first one started
+    pub cycle: Option<CycleStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycleStatus {
+    pub id: i64,
+    pub market_timestamp: i64,
+    pub stage: CycleStage,
+    pub first_token_id: String,
+    pub second_token_id: String,
+    pub entry_orders: Vec<StoredOrder>,
+    pub holding_allowed: bool,
+    /// set once a leg matched
+    pub hedge: Option<HedgeConfig>,
+    pub hedge_order: Option<StoredOrder>,
+}
+
+impl StatusSource {
+    pub fn status(&self) -> anyhow::Result<Status> {
+        let config = self.config.borrow().clone();
+        let summary = self.store.session_summary(self.started_at)?;
+        let now = self.control.clock().now();
+
+        let mut assets = Vec::with_capacity(self.assets.len());
+        for asset in &self.assets {
+            let session = summary.iter().find(|s| s.asset == asset.to_string());
+            let market = config.settings(asset).map(|s| s.market);
+            let cycle = match self.store.latest_cycle(*asset)? {
+                Some(cycle) => {
+                    let orders = self.store.cycle_orders(cycle.id)?;
+                    let hedge_order_id = cycle.hedge_order.as_ref().map(|(id, _)| id.as_str());
+                    // the window the cycle runs with, a reload only applies to the next one
+                    let holding_before = cycle
+                        .holding_before
+                        .or_else(|| config.settings(asset).map(|s| s.dont_allow_holding_before))
+                        .unwrap_or_default();
+                    Some(CycleStatus {
+                        id: cycle.id,
+                        market_timestamp: cycle.timestamp,
+                        stage: cycle.stage,
+                        first_token_id: cycle.tokens.first_asset_id,
+                        second_token_id: cycle.tokens.second_asset_id,
+                        hedge_order: orders
+                            .iter()
+                            .find(|o| Some(o.order_id.as_str()) == hedge_order_id)
+                            .cloned(),
+                        entry_orders: orders.into_iter().filter(|o| o.role == "entry").collect(),
+                        holding_allowed: allow_trade(cycle.timestamp, &holding_before, self.control.clock()),
+                        hedge: cycle.hedge,
+                    })
+                }
+                None => None,
+            };
+
+            assets.push(AssetStatus {
+                asset: asset.to_string(),
+                target_market: market.as_ref().map(|m| m.target(now)),
+                market: market.map(|m| m.name),
+                wins: session.map_or(0, |s| s.wins),
+                losses: session.map_or(0, |s| s.losses),
+                cycle,
+            });
+        }
+
+        Ok(Status {
+            mode: self.mode,
+            trading: self.control.state(),
+            clock_skew_ms: self.control.clock().skew(),
+            assets,
+        })
+    }
+}
+
+async fn status_handler(
+    State(source): State<StatusSource>,
+) -> Result<Json<Status>, (StatusCode, String)> {
+    source
+        .status()
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// `GET /status` on the metrics server, read-only and unauthenticated like `/metrics`
+pub fn routes(source: StatusSource) -> Router {
+    Router::new()
+        .route("/status", get(status_handler))
+        .with_state(source)
+}
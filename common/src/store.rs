@@ -0,0 +1,674 @@
+use crate::asset::Asset;
+use crate::dto::{HedgeConfig, MarketOrderResponse, MarketResponse, OrderResponse, OrderState};
+use crate::outcome::CycleOutcome;
+use rusqlite::{Connection, OptionalExtension, params};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS cycles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    asset TEXT NOT NULL,
+    market_timestamp INTEGER NOT NULL,
+    market_end INTEGER,
+    holding_before INTEGER,
+    first_token_id TEXT NOT NULL,
+    second_token_id TEXT NOT NULL,
+    stage TEXT NOT NULL,
+    hedge TEXT,
+    hedge_order_id TEXT,
+    hedge_size TEXT,
+    result INTEGER,
+    pnl TEXT,
+    outcome TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS cycles_asset_stage ON cycles (asset, stage);
+
+CREATE TABLE IF NOT EXISTS orders (
+    order_id TEXT PRIMARY KEY,
+    cycle_id INTEGER NOT NULL REFERENCES cycles (id),
+    role TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    size TEXT NOT NULL,
+    price TEXT NOT NULL,
+    status TEXT NOT NULL,
+    size_matched TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS orders_cycle ON orders (cycle_id);
+
+CREATE TABLE IF NOT EXISTS adopted_orders (
+    order_id TEXT PRIMARY KEY,
+    token_id TEXT NOT NULL,
+    size TEXT NOT NULL,
+    price TEXT NOT NULL,
+    adopted_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    cycle_id INTEGER NOT NULL REFERENCES cycles (id),
+    stage TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    at INTEGER NOT NULL
+);
+";
+
+/// columns added after the first release, `CREATE TABLE IF NOT EXISTS` leaves older files without them
+const MIGRATIONS: &[(&str, &str, &str)] = &[
+    ("cycles", "pnl", "ALTER TABLE cycles ADD COLUMN pnl TEXT"),
+    ("cycles", "outcome", "ALTER TABLE cycles ADD COLUMN outcome TEXT"),
+    ("cycles", "market_end", "ALTER TABLE cycles ADD COLUMN market_end INTEGER"),
+    ("cycles", "holding_before", "ALTER TABLE cycles ADD COLUMN holding_before INTEGER"),
+];
+
+/// where a cycle stands, the stage decides how it is picked up after a restart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CycleStage {
+    /// entry orders are resting on both tokens
+    Entry,
+    /// a leg matched, the hedge is being placed or watched
+    Hedging,
+    /// stop-loss reached or shutting down, the position is being closed
+    Closing,
+    Done,
+    /// nothing left to manage, e.g. the market resolved while the bot was down
+    Abandoned,
+}
+
+impl Display for CycleStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CycleStage::Entry => "entry",
+            CycleStage::Hedging => "hedging",
+            CycleStage::Closing => "closing",
+            CycleStage::Done => "done",
+            CycleStage::Abandoned => "abandoned",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for CycleStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "entry" => Ok(CycleStage::Entry),
+            "hedging" => Ok(CycleStage::Hedging),
+            "closing" => Ok(CycleStage::Closing),
+            "done" => Ok(CycleStage::Done),
+            "abandoned" => Ok(CycleStage::Abandoned),
+            other => Err(format!("unknown cycle stage: {other}")),
+        }
+    }
+}
+
+/// an unfinished cycle as it was left by the previous run
+#[derive(Debug, Clone)]
+pub struct CycleRecord {
+    pub id: i64,
+    pub asset: Asset,
+    pub timestamp: i64,
+    /// when the market ends, cycles stored before market families were 15 minutes long
+    pub market_end: i64,
+    /// `dont_allow_holding_before` the cycle started with, `None` for cycles stored before it was kept
+    pub holding_before: Option<i64>,
+    pub tokens: MarketResponse,
+    pub stage: CycleStage,
+    pub entry_orders: Vec<OrderResponse>,
+    pub hedge: Option<HedgeConfig>,
+    /// hedge order id and size, set once the hedge is on the book
+    pub hedge_order: Option<(String, Decimal)>,
+}
+
+/// an order as last seen by its cycle
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredOrder {
+    pub order_id: String,
+    /// entry, hedge or close
+    pub role: String,
+    pub token_id: String,
+    pub size: Decimal,
+    pub price: Decimal,
+    pub status: String,
+    pub size_matched: Decimal,
+}
+
+/// a finished cycle whose market has not ended yet, it may still hold shares
+#[derive(Debug, Clone)]
+pub struct FinishedCycle {
+    pub id: i64,
+    pub asset: Asset,
+    pub tokens: MarketResponse,
+}
+
+/// cycles of one asset touched since a point in time
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub asset: String,
+    pub cycles: u32,
+    pub wins: u32,
+    pub losses: u32,
+    /// still open, picked up by the next run
+    pub unfinished: u32,
+}
+
+/// SQLite file holding every cycle, its orders and stage transitions
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs() as i64
+}
+
+fn decimal(value: String) -> rusqlite::Result<Decimal> {
+    Decimal::from_str(&value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+impl Store {
+    pub fn open(path: &str) -> rusqlite::Result<Arc<Self>> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "FULL")?;
+        conn.execute_batch(SCHEMA)?;
+        for (table, column, migration) in MIGRATIONS {
+            let exists: bool = conn.query_row(
+                &format!("SELECT EXISTS (SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1)"),
+                params![column],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                conn.execute_batch(migration)?;
+            }
+        }
+        Ok(Arc::new(Store {
+            conn: Mutex::new(conn),
+        }))
+    }
+
+    /// registers a new cycle before any order of it is placed, along with the holding window
+    /// it runs with
+    pub fn start_cycle(
+        self: &Arc<Self>,
+        asset: Asset,
+        timestamp: i64,
+        market_end: i64,
+        holding_before: i64,
+        tokens: &MarketResponse,
+    ) -> rusqlite::Result<CycleJournal> {
+        let conn = self.conn.lock().unwrap();
+        let now = now();
+        conn.execute(
+            "INSERT INTO cycles (asset, market_timestamp, market_end, holding_before, first_token_id, second_token_id, stage, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+            params![
+                asset.to_string(),
+                timestamp,
+                market_end,
+                holding_before,
+                tokens.first_asset_id,
+                tokens.second_asset_id,
+                CycleStage::Entry.to_string(),
+                now
+            ],
+        )?;
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO transitions (cycle_id, stage, detail, at) VALUES (?1, ?2, 'started', ?3)",
+            params![id, CycleStage::Entry.to_string(), now],
+        )?;
+        Ok(self.journal(id))
+    }
+
+    pub fn journal(self: &Arc<Self>, cycle_id: i64) -> CycleJournal {
+        CycleJournal {
+            store: self.clone(),
+            id: cycle_id,
+        }
+    }
+
+    /// the latest cycle of the asset that is neither done nor abandoned
+    pub fn unfinished_cycle(&self, asset: Asset) -> rusqlite::Result<Option<CycleRecord>> {
+        self.last_cycle(asset, true)
+    }
+
+    /// the latest cycle of the asset, whatever its stage
+    pub fn latest_cycle(&self, asset: Asset) -> rusqlite::Result<Option<CycleRecord>> {
+        self.last_cycle(asset, false)
+    }
+
+    fn last_cycle(&self, asset: Asset, unfinished: bool) -> rusqlite::Result<Option<CycleRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let row = conn
+            .query_row(
+                "SELECT id, market_timestamp, first_token_id, second_token_id, stage, hedge, hedge_order_id, hedge_size,
+                        COALESCE(market_end, market_timestamp + 900), holding_before
+                 FROM cycles WHERE asset = ?1 AND (NOT ?2 OR stage NOT IN ('done', 'abandoned'))
+                 ORDER BY id DESC LIMIT 1",
+                params![asset.to_string(), unfinished],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
+                        row.get::<_, Option<String>>(7)?,
+                        row.get::<_, i64>(8)?,
+                        row.get::<_, Option<i64>>(9)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((
+            id,
+            timestamp,
+            first,
+            second,
+            stage,
+            hedge,
+            hedge_order_id,
+            hedge_size,
+            market_end,
+            holding_before,
+        )) = row
+        else {
+            return Ok(None);
+        };
+
+        let stage = stage.parse().map_err(rusqlite::Error::InvalidColumnName)?;
+        let hedge = hedge
+            .map(|raw| serde_json::from_str::<HedgeConfig>(&raw))
+            .transpose()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let hedge_order = match (hedge_order_id, hedge_size) {
+            (Some(order_id), Some(size)) => Some((order_id, decimal(size)?)),
+            _ => None,
+        };
+
+        let mut statement = conn.prepare(
+            "SELECT order_id, token_id FROM orders WHERE cycle_id = ?1 AND role = 'entry' ORDER BY created_at, rowid",
+        )?;
+        let entry_orders = statement
+            .query_map(params![id], |row| {
+                Ok(OrderResponse {
+                    order_id: row.get(0)?,
+                    token_id: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Some(CycleRecord {
+            id,
+            asset,
+            timestamp,
+            market_end,
+            holding_before,
+            tokens: MarketResponse {
+                first_asset_id: first,
+                second_asset_id: second,
+            },
+            stage,
+            entry_orders,
+            hedge,
+            hedge_order,
+        }))
+    }
+
+    pub fn cycle_orders(&self, cycle_id: i64) -> rusqlite::Result<Vec<StoredOrder>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT order_id, role, token_id, size, price, status, size_matched
+             FROM orders WHERE cycle_id = ?1 ORDER BY created_at, rowid",
+        )?;
+        let rows = statement
+            .query_map(params![cycle_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(order_id, role, token_id, size, price, status, size_matched)| {
+                Ok(StoredOrder {
+                    order_id,
+                    role,
+                    token_id,
+                    size: decimal(size)?,
+                    price: decimal(price)?,
+                    status,
+                    size_matched: decimal(size_matched)?,
+                })
+            })
+            .collect()
+    }
+
+    /// stage of the cycle that placed the order, `None` when no cycle knows it
+    pub fn order_stage(&self, order_id: &str) -> rusqlite::Result<Option<CycleStage>> {
+        let conn = self.conn.lock().unwrap();
+        let stage: Option<String> = conn
+            .query_row(
+                "SELECT cycles.stage FROM orders JOIN cycles ON cycles.id = orders.cycle_id
+                 WHERE orders.order_id = ?1",
+                params![order_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        stage
+            .map(|s| s.parse().map_err(rusqlite::Error::InvalidColumnName))
+            .transpose()
+    }
+
+    /// asset of the latest cycle that traded the token, `None` when no cycle did
+    pub fn token_asset(&self, token_id: &str) -> rusqlite::Result<Option<Asset>> {
+        let conn = self.conn.lock().unwrap();
+        let asset: Option<String> = conn
+            .query_row(
+                "SELECT asset FROM cycles WHERE first_token_id = ?1 OR second_token_id = ?1
+                 ORDER BY id DESC LIMIT 1",
+                params![token_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        asset
+            .map(|a| a.parse().map_err(rusqlite::Error::InvalidColumnName))
+            .transpose()
+    }
+
+    /// records an open order left on the book that no cycle manages
+    pub fn adopt_order(&self, order: &OrderState) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO adopted_orders (order_id, token_id, size, price, adopted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                order.order_id,
+                order.token_id,
+                (order.original_size - order.size_matched).to_string(),
+                order.price.to_string(),
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// (wins, losses) over every finished cycle of the asset
+    pub fn results(&self, asset: Asset) -> rusqlite::Result<(u32, u32)> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COALESCE(SUM(result > 0), 0), COALESCE(SUM(result < 0), 0)
+             FROM cycles WHERE asset = ?1 AND stage = 'done'",
+            params![asset.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    /// realized PnL over every finished cycle of the asset
+    pub fn realized_pnl(&self, asset: Asset) -> rusqlite::Result<Decimal> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT pnl FROM cycles WHERE asset = ?1 AND stage = 'done' AND pnl IS NOT NULL",
+        )?;
+        let rows = statement
+            .query_map(params![asset.to_string()], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter().map(decimal).sum()
+    }
+
+    pub fn session_summary(&self, since: i64) -> rusqlite::Result<Vec<SessionSummary>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT asset, COUNT(*),
+                    COALESCE(SUM(stage = 'done' AND result > 0), 0),
+                    COALESCE(SUM(stage = 'done' AND result < 0), 0),
+                    COALESCE(SUM(stage NOT IN ('done', 'abandoned')), 0)
+             FROM cycles WHERE updated_at >= ?1 GROUP BY asset ORDER BY asset",
+        )?;
+        statement
+            .query_map(params![since], |row| {
+                Ok(SessionSummary {
+                    asset: row.get(0)?,
+                    cycles: row.get(1)?,
+                    wins: row.get(2)?,
+                    losses: row.get(3)?,
+                    unfinished: row.get(4)?,
+                })
+            })?
+            .collect()
+    }
+
+    pub fn finished_in_open_markets(&self, now: i64) -> rusqlite::Result<Vec<FinishedCycle>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT id, asset, first_token_id, second_token_id FROM cycles
+             WHERE stage IN ('done', 'abandoned') AND COALESCE(market_end, market_timestamp + 900) > ?1
+             ORDER BY id",
+        )?;
+        let rows = statement
+            .query_map(params![now], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, asset, first, second)| {
+                Some(FinishedCycle {
+                    id,
+                    asset: asset.parse().ok()?,
+                    tokens: MarketResponse {
+                        first_asset_id: first,
+                        second_asset_id: second,
+                    },
+                })
+            })
+            .collect())
+    }
+
+    /// marks every unfinished cycle abandoned, returns how many there were
+    pub fn abandon_unfinished(&self, reason: &str) -> rusqlite::Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let now = now();
+        conn.execute(
+            "INSERT INTO transitions (cycle_id, stage, detail, at)
+             SELECT id, 'abandoned', ?1, ?2 FROM cycles WHERE stage NOT IN ('done', 'abandoned')",
+            params![reason, now],
+        )?;
+        conn.execute(
+            "UPDATE cycles SET stage = 'abandoned', updated_at = ?1 WHERE stage NOT IN ('done', 'abandoned')",
+            params![now],
+        )
+    }
+}
+
+/// write side of a single cycle. A failed write is logged and trading goes on,
+/// stopping halfway through a hedge would do more harm than a gap in the history
+#[derive(Clone)]
+pub struct CycleJournal {
+    store: Arc<Store>,
+    pub id: i64,
+}
+
+impl CycleJournal {
+    fn write(&self, what: &str, f: impl FnOnce(&Connection, i64) -> rusqlite::Result<()>) {
+        let conn = self.store.conn.lock().unwrap();
+        if let Err(e) = f(&conn, now()) {
+            eprintln!("💾 Failed to store {what} of cycle {}: {e}", self.id);
+        }
+    }
+
+    fn transition(
+        conn: &Connection,
+        id: i64,
+        stage: CycleStage,
+        detail: &str,
+        now: i64,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "UPDATE cycles SET stage = ?1, updated_at = ?2 WHERE id = ?3",
+            params![stage.to_string(), now, id],
+        )?;
+        conn.execute(
+            "INSERT INTO transitions (cycle_id, stage, detail, at) VALUES (?1, ?2, ?3, ?4)",
+            params![id, stage.to_string(), detail, now],
+        )?;
+        Ok(())
+    }
+
+    fn insert_order(
+        conn: &Connection,
+        id: i64,
+        role: &str,
+        order: &OrderResponse,
+        size: Decimal,
+        price: Decimal,
+        now: i64,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO orders (order_id, cycle_id, role, token_id, size, price, status, size_matched, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'LIVE', '0', ?7, ?7)",
+            params![
+                order.order_id,
+                id,
+                role,
+                order.token_id,
+                size.to_string(),
+                price.to_string(),
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// both entry orders, each at its own price
+    pub fn entry_placed(&self, orders: &[OrderResponse; 2], size: Decimal, prices: [Decimal; 2]) {
+        self.write("entry orders", |conn, now| {
+            for (order, price) in orders.iter().zip(prices) {
+                Self::insert_order(conn, self.id, "entry", order, size, price, now)?;
+            }
+            Self::transition(conn, self.id, CycleStage::Entry, "entry orders placed", now)
+        });
+    }
+
+    /// last polled status, only touches the row when something changed
+    pub fn order_status(&self, order: &OrderState) {
+        self.write("order status", |conn, now| {
+            conn.execute(
+                "UPDATE orders SET status = ?1, size_matched = ?2, updated_at = ?3
+                 WHERE order_id = ?4 AND (status != ?1 OR size_matched != ?2)",
+                params![
+                    order.status.to_string(),
+                    order.size_matched.to_string(),
+                    now,
+                    order.order_id
+                ],
+            )?;
+            Ok(())
+        });
+    }
+
+    /// a leg is held, from here a restart has to finish the hedge
+    pub fn hedging(&self, hedge_config: &HedgeConfig) {
+        self.write("hedge config", |conn, now| {
+            let raw = serde_json::to_string(hedge_config)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            conn.execute(
+                "UPDATE cycles SET hedge = ?1, hedge_order_id = NULL, hedge_size = NULL WHERE id = ?2",
+                params![raw, self.id],
+            )?;
+            Self::transition(conn, self.id, CycleStage::Hedging, &raw, now)
+        });
+    }
+
+    pub fn hedge_placed(&self, order: &OrderResponse, size: Decimal, price: Decimal) {
+        self.write("hedge order", |conn, now| {
+            Self::insert_order(conn, self.id, "hedge", order, size, price, now)?;
+            conn.execute(
+                "UPDATE cycles SET hedge_order_id = ?1, hedge_size = ?2 WHERE id = ?3",
+                params![order.order_id, size.to_string(), self.id],
+            )?;
+            Self::transition(conn, self.id, CycleStage::Hedging, "hedge order placed", now)
+        });
+    }
+
+    /// the position is being unwound, `reason` is why
+    pub fn closing(&self, reason: &str) {
+        self.write("closing", |conn, now| {
+            Self::transition(conn, self.id, CycleStage::Closing, reason, now)
+        });
+    }
+
+    /// a market sell went through, stored at its average price
+    pub fn closed(&self, token_id: &str, size: Decimal, response: &MarketOrderResponse) {
+        let order = OrderResponse {
+            token_id: token_id.to_string(),
+            order_id: response.order_id.clone(),
+        };
+        let price = response.sold().price;
+        self.write("close order", |conn, now| {
+            Self::insert_order(conn, self.id, "close", &order, size, price, now)?;
+            conn.execute(
+                "UPDATE orders SET status = 'MATCHED', size_matched = size WHERE order_id = ?1",
+                params![order.order_id],
+            )?;
+            Ok(())
+        });
+    }
+
+    /// whether a close of the token already went through, so a resumed cycle does not sell twice
+    pub fn is_closed(&self, token_id: &str) -> bool {
+        let conn = self.store.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM orders WHERE cycle_id = ?1 AND role = 'close' AND token_id = ?2)",
+            params![self.id, token_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(false)
+    }
+
+    /// `result` keeps counting wins and losses, `outcome` has the fills behind the PnL
+    pub fn finish(&self, outcome: &CycleOutcome) {
+        self.write("outcome", |conn, now| {
+            let raw = serde_json::to_string(outcome)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            conn.execute(
+                "UPDATE cycles SET result = ?1, pnl = ?2, outcome = ?3 WHERE id = ?4",
+                params![outcome.result(), outcome.pnl().to_string(), raw, self.id],
+            )?;
+            let detail = format!("{outcome}, pnl {}", outcome.pnl());
+            Self::transition(conn, self.id, CycleStage::Done, &detail, now)
+        });
+    }
+
+    pub fn abandon(&self, reason: &str) {
+        self.write("abandon", |conn, now| {
+            Self::transition(conn, self.id, CycleStage::Abandoned, reason, now)
+        });
+    }
+}
@@ -0,0 +1,380 @@
+//! Order and trade events of the account from the authenticated user channel of the CLOB
+//! websocket, routed to the cycle that owns the order. The SDK keeps the channel connected
+//! and subscribed, while it is down cycles poll the REST API like before.
+
+use crate::dto::OrderState;
+use crate::error::BotError;
+use crate::metrics::USER_CHANNEL_UP;
+use futures::{Stream, StreamExt};
+use polymarket_client_sdk::auth::{ApiKey, Credentials};
+use polymarket_client_sdk::clob::types::OrderStatusType;
+use polymarket_client_sdk::clob::ws::connection::{ConnectionManager, ConnectionState};
+use polymarket_client_sdk::clob::ws::interest::InterestTracker;
+use polymarket_client_sdk::clob::ws::subscription::SubscriptionManager;
+use polymarket_client_sdk::clob::ws::{OrderMessage, WebSocketConfig, WsMessage};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::env;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, watch};
+use tokio::time::timeout;
+
+pub const USER_CHANNEL_URL: &str = "wss://ws-subscriptions-clob.polymarket.com/ws/user";
+
+/// events of orders no cycle tracks, kept for orders whose event beats the placing response
+const MAX_UNCLAIMED: usize = 1_000;
+
+/// messages of one subscription, a new one is taken after it fell behind
+pub(crate) type Events<'a> =
+    Pin<Box<dyn Stream<Item = polymarket_client_sdk::Result<WsMessage>> + Send + 'a>>;
+
+/// A channel of the CLOB websocket at `url`, kept connected by the SDK with backoff and
+/// subscribed again after every reconnect. The `WebSocketClient` keeps the state of its
+/// user channel to itself, the feeds need it to know when to poll
+pub(crate) fn channel(
+    url: &str,
+) -> Result<(ConnectionManager, Arc<SubscriptionManager>), BotError> {
+    let interest = Arc::new(InterestTracker::new());
+    let connection =
+        ConnectionManager::new(url.to_string(), WebSocketConfig::default(), &interest)?;
+    let subscriptions = Arc::new(SubscriptionManager::new(connection.clone(), interest));
+    subscriptions.start_reconnection_handler();
+    Ok((connection, subscriptions))
+}
+
+/// Follows the connection state: `Some(true)` on every new connection, `Some(false)` once
+/// it went down and `None` while nothing changed for the feed
+pub(crate) fn connection_change(
+    state: &mut watch::Receiver<ConnectionState>,
+    since: &mut Option<Instant>,
+) -> Option<bool> {
+    match *state.borrow_and_update() {
+        ConnectionState::Connected { since: now } if *since != Some(now) => {
+            *since = Some(now);
+            Some(true)
+        }
+        ConnectionState::Connected { .. } => None,
+        _ if since.is_some() => {
+            *since = None;
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+/// next message of `events`, never while there is no subscription
+pub(crate) async fn next_event(
+    events: &mut Option<Events<'_>>,
+) -> Option<polymarket_client_sdk::Result<WsMessage>> {
+    match events {
+        Some(events) => events.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// API credentials of the account, the REST client signs with the same ones
+#[derive(Clone)]
+pub struct ApiCreds {
+    pub key: String,
+    pub secret: String,
+    pub passphrase: String,
+}
+
+impl ApiCreds {
+    /// `CLOB_API_KEY`, `CLOB_SECRET` and `CLOB_PASS_PHRASE`, `None` unless all are set
+    pub fn from_env() -> Option<Self> {
+        let var = |name| env::var(name).ok().filter(|v: &String| !v.is_empty());
+        Some(ApiCreds {
+            key: var("CLOB_API_KEY")?,
+            secret: var("CLOB_SECRET")?,
+            passphrase: var("CLOB_PASS_PHRASE")?,
+        })
+    }
+
+    pub fn credentials(&self) -> Result<Credentials, BotError> {
+        let key = ApiKey::parse_str(&self.key)
+            .map_err(|e| BotError::Fatal(format!("CLOB_API_KEY is not a valid key: {e}")))?;
+        Ok(Credentials::new(key, self.secret.clone(), self.passphrase.clone()))
+    }
+}
+
+/// what an event says about an order
+#[derive(Debug)]
+enum Update {
+    Order(OrderState),
+    /// a trade only carries its own size, the order is polled once to learn the total
+    Trade(String),
+}
+
+/// an order event with every size in it, a trade otherwise so the order gets polled
+fn order_update(order: OrderMessage) -> Update {
+    let decimal = |value: Option<&String>| value.and_then(|v| v.parse::<Decimal>().ok());
+    let (Some(price), Some(original_size), Some(size_matched)) = (
+        decimal(Some(&order.price)),
+        decimal(order.original_size.as_ref()),
+        decimal(order.size_matched.as_ref()),
+    ) else {
+        return Update::Trade(order.id);
+    };
+    // PLACEMENT, UPDATE or CANCELLATION
+    let status = if order.msg_type.as_deref() == Some("CANCELLATION") {
+        OrderStatusType::Canceled
+    } else if size_matched >= original_size {
+        OrderStatusType::Matched
+    } else {
+        OrderStatusType::Live
+    };
+    Update::Order(OrderState {
+        order_id: order.id,
+        token_id: order.asset_id,
+        status,
+        original_size,
+        size_matched,
+        price,
+    })
+}
+
+/// what a message of the user channel says about orders
+fn updates(message: WsMessage) -> Vec<Update> {
+    match message {
+        WsMessage::Order(order) => vec![order_update(order)],
+        WsMessage::Trade(trade) => trade
+            .taker_order_id
+            .into_iter()
+            .chain(trade.maker_orders.into_iter().map(|m| m.order_id))
+            .map(Update::Trade)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// latest state of a tracked order and the connection it came over
+#[derive(Debug, Clone)]
+struct Seen {
+    order: Option<OrderState>,
+    epoch: u64,
+}
+
+#[derive(Default)]
+struct Inbox {
+    orders: Mutex<HashMap<String, Seen>>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct Routes {
+    /// order id to the inbox of the cycle that tracks it
+    owners: HashMap<String, Arc<Inbox>>,
+    unclaimed: HashMap<String, OrderState>,
+}
+
+#[derive(Default)]
+struct FeedInner {
+    routes: Mutex<Routes>,
+    connected: AtomicBool,
+    /// bumped on every connect, what was seen before may have missed events since
+    epoch: AtomicU64,
+}
+
+/// Where the user channel delivers and cycles pick up order updates. Never connected
+/// unless [`run_user_channel`] feeds it, cycles then poll every order.
+#[derive(Clone, Default)]
+pub struct OrderFeed {
+    inner: Arc<FeedInner>,
+}
+
+impl OrderFeed {
+    pub fn connected(&self) -> bool {
+        self.inner.connected.load(Ordering::SeqCst)
+    }
+
+    /// an inbox for the orders of one cycle
+    pub fn subscribe(&self) -> OrderSubscription {
+        OrderSubscription {
+            feed: self.clone(),
+            inbox: Arc::default(),
+        }
+    }
+
+    fn routes(&self) -> MutexGuard<'_, Routes> {
+        self.inner.routes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn epoch(&self) -> u64 {
+        self.inner.epoch.load(Ordering::SeqCst)
+    }
+
+    fn set_connected(&self, connected: bool) {
+        if connected {
+            self.missed();
+        }
+        self.inner.connected.store(connected, Ordering::SeqCst);
+        USER_CHANNEL_UP.set(connected as i64);
+    }
+
+    /// events may have been missed, every tracked order is polled once
+    fn missed(&self) {
+        self.inner.epoch.fetch_add(1, Ordering::SeqCst);
+        self.routes().unclaimed.clear();
+    }
+
+    fn receive(&self, message: WsMessage) {
+        let epoch = self.epoch();
+        let mut routes = self.routes();
+        for update in updates(message) {
+            let (order_id, order) = match update {
+                Update::Order(order) => (order.order_id.clone(), Some(order)),
+                Update::Trade(order_id) => (order_id, None),
+            };
+            let Some(inbox) = routes.owners.get(&order_id).cloned() else {
+                match order {
+                    Some(order) if routes.unclaimed.len() < MAX_UNCLAIMED => {
+                        routes.unclaimed.insert(order_id, order);
+                    }
+                    _ => {
+                        routes.unclaimed.remove(&order_id);
+                    }
+                }
+                continue;
+            };
+            inbox.orders().insert(order_id, Seen { order, epoch });
+            inbox.notify.notify_one();
+        }
+    }
+}
+
+impl Inbox {
+    fn orders(&self) -> MutexGuard<'_, HashMap<String, Seen>> {
+        self.orders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// order updates of one cycle, routed here by order id. Dropping it stops the routing
+pub struct OrderSubscription {
+    feed: OrderFeed,
+    inbox: Arc<Inbox>,
+}
+
+impl OrderSubscription {
+    /// routes events of `order_id` here from now on, along with one that came before
+    pub fn track(&self, order_id: &str) {
+        let mut routes = self.feed.routes();
+        if routes.owners.contains_key(order_id) {
+            return;
+        }
+        routes.owners.insert(order_id.to_string(), self.inbox.clone());
+        let order = routes.unclaimed.remove(order_id);
+        self.inbox.orders().insert(
+            order_id.to_string(),
+            Seen {
+                order,
+                epoch: self.feed.epoch(),
+            },
+        );
+    }
+
+    /// the order as the socket last reported it, `None` when it has to be polled: the
+    /// socket is down, nothing was seen of it over this connection or a trade came in
+    pub fn latest(&self, order_id: &str) -> Option<OrderState> {
+        if !self.feed.connected() {
+            return None;
+        }
+        let epoch = self.feed.epoch();
+        self.inbox
+            .orders()
+            .get(order_id)
+            .filter(|seen| seen.epoch == epoch)
+            .and_then(|seen| seen.order.clone())
+    }
+
+    /// a polled state, the socket only reports changes from here on
+    pub fn polled(&self, order: &OrderState) {
+        if !self.feed.routes().owners.contains_key(&order.order_id) {
+            return;
+        }
+        self.inbox.orders().insert(
+            order.order_id.clone(),
+            Seen {
+                order: Some(order.clone()),
+                epoch: self.feed.epoch(),
+            },
+        );
+    }
+
+    /// waits for an update of a tracked order, at most `wait`
+    pub async fn changed(&self, wait: Duration) {
+        timeout(wait, self.inbox.notify.notified()).await.ok();
+    }
+}
+
+impl Drop for OrderSubscription {
+    fn drop(&mut self) {
+        let mut routes = self.feed.routes();
+        for order_id in self.inbox.orders().keys() {
+            routes.owners.remove(order_id);
+        }
+    }
+}
+
+/// Keeps the user channel of `url` subscribed to every market of the account for good.
+/// `feed` is marked down while the SDK reconnects so cycles poll
+pub async fn run_user_channel(url: String, credentials: Credentials, feed: OrderFeed) {
+    let (connection, subscriptions) = match channel(&url) {
+        Ok(channel) => channel,
+        Err(e) => {
+            eprintln!("🔌 User channel unavailable: {e}, orders are polled");
+            return;
+        }
+    };
+    let subscribe = || match subscriptions.subscribe_user(vec![], credentials.clone()) {
+        Ok(events) => Some(Box::pin(events) as Events),
+        Err(e) => {
+            eprintln!("🔌 Failed to subscribe to the user channel: {e}, orders are polled");
+            None
+        }
+    };
+    let mut state = connection.state_receiver();
+    let mut since = None;
+    let mut events = subscribe();
+    if connection_change(&mut state, &mut since) == Some(true) {
+        feed.set_connected(true);
+    }
+    loop {
+        // a new connection is marked before its first event is routed
+        tokio::select! {
+            biased;
+            changed = state.changed() => {
+                if changed.is_err() {
+                    feed.set_connected(false);
+                    return;
+                }
+                match connection_change(&mut state, &mut since) {
+                    Some(true) => {
+                        feed.set_connected(true);
+                        println!("🔌 User channel connected, order fills come from the socket");
+                    }
+                    Some(false) => {
+                        feed.set_connected(false);
+                        eprintln!("🔌 User channel down, polling orders until it reconnects");
+                    }
+                    None => {}
+                }
+            }
+            event = next_event(&mut events) => match event {
+                Some(Ok(message)) => feed.receive(message),
+                Some(Err(e)) => {
+                    eprintln!("🔌 User channel fell behind: {e}, polling orders once");
+                    feed.missed();
+                    events = subscribe();
+                }
+                None => {
+                    feed.set_connected(false);
+                    return;
+                }
+            },
+        }
+    }
+}
@@ -1,25 +1,17 @@
-use crate::dto::{Asset, OrderResponse};
-use crate::metrics::{
-    HEDGE_ORDERS_CANCELLED_TOTAL, HEDGE_ORDERS_MATCHED_TOTAL, HEDGE_ORDERS_PARTIAL_TOTAL,
-    HEDGE_ORDERS_TOTAL, ORDERS_CANCELLED_TOTAL, ORDERS_MATCHED_TOTAL, ORDERS_PARTIAL_TOTAL,
-    ORDERS_TOTAL, REQUEST_LATENCY, RETRIES_TOTAL, STOP_LOSS_TOTAL,
-};
-use crate::{HedgeConfig, MarketApiResponse, MarketResponse, PreventHoldingConfig};
-use alloy::signers::k256::ecdsa::SigningKey;
-use alloy::signers::k256::ecdsa::signature::SignerMut;
-use alloy::signers::local::LocalSigner;
-use chrono::{DateTime, Local, TimeZone, Timelike};
-use polymarket_client_sdk::auth::Normal;
-use polymarket_client_sdk::clob::Client;
-use polymarket_client_sdk::clob::types::{Amount, OpenOrderResponse, OrderStatusType, OrderType, PostOrderResponse, PriceRequest, PriceRequestBuilder, PriceResponse, Side};
-use reqwest::Client as http_client;
-use rust_decimal::prelude::Zero;
+use crate::clock::{Clock, SystemClock};
+use crate::asset::Asset;
+use crate::dto::{Fill, MarketOrderResponse, OrderResponse, OrderState};
+use crate::error::{BotError, never_sent};
+use crate::exchange::Exchange;
+use crate::store::CycleJournal;
+use crate::metrics::{HEDGE_ORDERS_TOTAL, REQUEST_LATENCY};
+use crate::retry::policies;
+use crate::MarketResponse;
+use polymarket_client_sdk::clob::types::Side;
 use rust_decimal::{Decimal, RoundingStrategy};
-use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use polymarket_client_sdk::auth::state::Authenticated;
+use std::time::Duration;
 use tokio::time::sleep;
 
 pub async fn timed_request<F, T>(service: &str, method: &str, f: F) -> T
@@ -37,211 +29,146 @@ where
     result
 }
 
+/// wall clock unix seconds, for what isn't trading logic, that goes by a [`Clock`]
+pub fn unix_now() -> i64 {
+    SystemClock.now()
+}
+
 /// if current time > grace_second we count it as a stop-loss
-pub fn allow_stop_loss(market_timestamp: i64, grace_seconds: i64) -> bool {
-    // текущее время в unix timestamp (UTC)
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("time went backwards")
-        .as_secs() as i64;
+pub fn allow_stop_loss(
+    market_timestamp: i64,
+    market_end: i64,
+    grace_seconds: i64,
+    clock: &dyn Clock,
+) -> bool {
+    allow_stop_loss_at(market_timestamp, market_end, grace_seconds, clock.now())
+}
 
+/// `allow_stop_loss` at `now` (unix seconds)
+pub fn allow_stop_loss_at(
+    market_timestamp: i64,
+    market_end: i64,
+    grace_seconds: i64,
+    now: i64,
+) -> bool {
     // если мы раньше старта рынка — стоп запрещён
     if now < market_timestamp {
         return false;
     }
 
-    let seconds_since_start = now - market_timestamp;
-
-    // если рынок уже закончился (15 минут = 900 секунд)
-    if seconds_since_start >= 900 {
+    // если рынок уже закончился
+    if now >= market_end {
         return false;
     }
 
     // разрешаем стоп только после grace_seconds
-    seconds_since_start >= grace_seconds
+    now - market_timestamp >= grace_seconds
 }
 
 pub fn floor_dp(value: Decimal, dp: u32) -> Decimal {
     value.round_dp_with_strategy(dp, RoundingStrategy::ToZero)
 }
 
+/// FOK market sell under the `close_position` policy, `None` once it gave up
 pub async fn close_position_with_retry(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
-    asset_id: &String,
+    exchange: &dyn Exchange,
+    asset_id: &str,
     close_size: Decimal,
-    max_retries: usize,
-    asset: &Asset
-) -> Option<PostOrderResponse> {
-    let mut attempt = 0;
-
-    loop {
-        let response = close_position_by_market(&client, &signer, asset_id, close_size)
-            .await
-            .ok()?;
-
-        match response.error_msg.as_deref() {
-            Some("") | None => {
-                // успех
-                return Some(response);
-            }
-            Some(err) => {
-                attempt += 1;
-                RETRIES_TOTAL.with_label_values(&[asset.to_string().as_str(), "close_position"]).inc();
-
-                if attempt >= max_retries {
-                    return None;
-                }
-                println!(
-                    "close_order failed (attempt {}/{}): {}",
-                    attempt, max_retries, err
-                );
-
-                sleep(Duration::from_millis(1000)).await;
+    asset: &Asset,
+) -> Option<MarketOrderResponse> {
+    let result = policies()
+        .close_position
+        .run(asset, "close_position", || async {
+            let response = close_position_by_market(exchange, asset_id, close_size).await?;
+            match response.error_msg.as_deref() {
+                Some("") | None => Ok(response),
+                // the book couldn't take it all, it may on the next try
+                Some(err) => Err(BotError::Transient(format!("not filled: {err}"))),
             }
+        })
+        .await;
+    match result {
+        Ok(response) => Some(response),
+        Err(e) => {
+            eprintln!("close_order failed for good: {e}");
+            None
         }
     }
 }
 
 pub async fn get_order_with_retry(
-    client: &Arc<Client<Authenticated<Normal>>>,
+    exchange: &dyn Exchange,
     order_id: &str,
-    max_retries: usize,
-    asset: &Asset
-) -> polymarket_client_sdk::Result<OpenOrderResponse> {
-    let mut attempt = 0;
-
-    loop {
-        match timed_request("polymarket", "get_order", client.order(order_id)).await {
-            Ok(status) => return Ok(status),
-
-            Err(err) => {
-                attempt += 1;
-                RETRIES_TOTAL.with_label_values(&[asset.to_string().as_str(), "get_order"]).inc();
+    asset: &Asset,
+) -> Result<OrderState, BotError> {
+    policies()
+        .get_order
+        .run(asset, "get_order", || async { Ok(exchange.get_order(order_id).await?) })
+        .await
+}
 
-                if attempt >= max_retries {
-                    return Err(err);
-                }
+pub async fn cancel_order_with_retry(
+    exchange: &dyn Exchange,
+    order_id: &str,
+    asset: &Asset,
+) -> Result<(), BotError> {
+    policies()
+        .cancel_order
+        .run(asset, "cancel_order", || async { Ok(exchange.cancel_order(order_id).await?) })
+        .await
+}
 
+/// GTC buy under the `place_order` policy. Only a buy that surely never reached the exchange
+/// is posted again right away, after a timeout or a 5xx it may rest on the book already and
+/// is looked up among the open orders first
+async fn place_buy_with_retry(
+    exchange: &dyn Exchange,
+    token_id: &str,
+    size: Decimal,
+    price: Decimal,
+    asset: &Asset,
+) -> Result<OrderResponse, BotError> {
+    let maybe_placed = AtomicBool::new(false);
+    policies()
+        .place_order
+        .run(asset, "place_order", || async {
+            if maybe_placed.load(Ordering::Relaxed)
+                && let Some(order) = open_buy(exchange, token_id, size, price).await?
+            {
                 println!(
-                    "get_order failed (attempt {}/{}): {}",
-                    attempt, max_retries, err
+                    "Buy of {size} {token_id} at {price} went through after all: {}",
+                    order.order_id
                 );
-
-                sleep(Duration::from_millis(1000)).await;
+                return Ok(order);
             }
-        }
-    }
-}
-
-pub async fn handle_matched(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
-    cancel_order_id: &str,
-    hedge_config: HedgeConfig,
-) -> polymarket_client_sdk::Result<i8> {
-    ORDERS_TOTAL
-        .with_label_values(&[&hedge_config.asset.to_string()])
-        .inc();
-    ORDERS_TOTAL
-        .with_label_values(&[&hedge_config.asset.to_string()])
-        .inc();
-
-    ORDERS_MATCHED_TOTAL
-        .with_label_values(&[&hedge_config.asset.to_string()])
-        .inc();
-
-    println!("Cancelling another order...");
-    timed_request(
-        "polymarket",
-        "cancel_order",
-        client.cancel_order(cancel_order_id),
-    )
-    .await?;
-    manage_position_after_match(client, signer, hedge_config).await
-}
-
-pub async fn handle_live_order(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
-    status: &OpenOrderResponse,
-    hedge_config: HedgeConfig,
-    cancel_order_id: &str,
-) -> polymarket_client_sdk::Result<bool> {
-    ORDERS_TOTAL
-        .with_label_values(&[&hedge_config.asset.to_string()])
-        .inc();
-
-    if !status.size_matched.is_zero() {
-        ORDERS_PARTIAL_TOTAL
-            .with_label_values(&[&hedge_config.asset.to_string()])
-            .inc();
-        prevent_holding_position(
-            client,
-            signer,
-            PreventHoldingConfig {
-                hedge_config,
-                order_id: cancel_order_id.to_string(),
-            },
-        )
-        .await?;
-        Ok(true)
-    } else {
-        ORDERS_CANCELLED_TOTAL
-            .with_label_values(&[&hedge_config.asset.to_string()])
-            .inc();
-
-        println!("No open position, going to cancel it");
-        timed_request(
-            "polymarket",
-            "cancel_order",
-            client.cancel_order(cancel_order_id),
-        )
-        .await?;
-        Ok(false)
-    }
+            exchange
+                .place_limit_order(token_id, size, price, Side::Buy)
+                .await
+                .map_err(|e| {
+                    if !never_sent(&e) {
+                        maybe_placed.store(true, Ordering::Relaxed);
+                    }
+                    e.into()
+                })
+        })
+        .await
 }
 
-pub async fn prevent_holding_position(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
-    prevent_holding_config: PreventHoldingConfig,
-) -> polymarket_client_sdk::Result<()> {
-    ORDERS_CANCELLED_TOTAL
-        .with_label_values(&[&prevent_holding_config.hedge_config.asset.to_string()])
-        .inc();
-
-    timed_request(
-        "polymarket",
-        "cancel_order",
-        client.cancel_order(&prevent_holding_config.order_id),
-    )
-    .await?;
-    println!("Cancelled first order, closing now");
-
-    let first_order_status: OpenOrderResponse =
-        get_order_with_retry(&client, &prevent_holding_config.order_id.as_str(), 30, &prevent_holding_config.hedge_config.asset).await?;
-    let first_order_size = normalized_size(
-        first_order_status.size_matched,
-        prevent_holding_config.hedge_config.hedge_size,
-    );
-    println!(
-        "Time's up to wait for first order opening, going to open hedge with size = {}",
-        &first_order_size
-    );
-    let true_hedge_config = HedgeConfig {
-        stop_loss_after: prevent_holding_config.hedge_config.stop_loss_after,
-        second_order_id: prevent_holding_config.hedge_config.second_order_id,
-        hedge_asset_id: prevent_holding_config.hedge_config.hedge_asset_id,
-        initial_asset_id: prevent_holding_config.hedge_config.initial_asset_id,
-        hedge_size: first_order_size,
-        hedge_enter_price: prevent_holding_config.hedge_config.hedge_enter_price,
-        close_size: first_order_size,
-        timestamp: prevent_holding_config.hedge_config.timestamp,
-        asset: prevent_holding_config.hedge_config.asset,
-    };
-    manage_position_after_match(client, signer, true_hedge_config.clone()).await?;
-    Ok(())
+/// an open order of the account buying `size` of the token at `price`
+async fn open_buy(
+    exchange: &dyn Exchange,
+    token_id: &str,
+    size: Decimal,
+    price: Decimal,
+) -> Result<Option<OrderResponse>, BotError> {
+    let open = exchange.open_orders().await?;
+    Ok(open
+        .into_iter()
+        .find(|o| o.token_id == token_id && o.original_size == size && o.price == price)
+        .map(|o| OrderResponse {
+            order_id: o.order_id,
+            token_id: o.token_id,
+        }))
 }
 
 pub fn normalized_size(size: Decimal, fallback: Decimal) -> Decimal {
@@ -254,348 +181,150 @@ pub fn normalized_size(size: Decimal, fallback: Decimal) -> Decimal {
     }
 }
 
-pub async fn manage_position_after_match(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
-    hedge_config: HedgeConfig,
-) -> polymarket_client_sdk::Result<i8> {
-    let second_order_status: OpenOrderResponse =
-        get_order_with_retry(client, hedge_config.second_order_id.as_str(), 30, &hedge_config.asset).await?;
-    if second_order_status.status != OrderStatusType::Canceled {
-        println!("Cancelling second order...");
-        ORDERS_CANCELLED_TOTAL
-            .with_label_values(&[&hedge_config.asset.to_string()])
-            .inc();
-
-        timed_request(
-            "polymarket",
-            "cancel_order",
-            client.cancel_order(hedge_config.second_order_id.as_str()),
-        )
-        .await?;
-        println!("Second order cancelled");
-    }
-    let second_order_status: OpenOrderResponse =
-        get_order_with_retry(client, hedge_config.second_order_id.as_str(), 30, &hedge_config.asset).await?;
-    let mut hedge_size = hedge_config.hedge_size;
-    if second_order_status.size_matched > Decimal::zero() {
-        ORDERS_PARTIAL_TOTAL
-            .with_label_values(&[&hedge_config.asset.to_string()])
-            .inc();
-
-        let closing_second_size = floor_dp(second_order_status.size_matched, 2);
-        println!(
-            "Second order partially matched with size: {}",
-            &closing_second_size
-        );
-        hedge_size = closing_second_size - hedge_config.hedge_size;
-        if hedge_size < Decimal::zero() {
-            hedge_size = hedge_config.hedge_size - closing_second_size;
-        }
+/// market-sells `size` of the token unless the cycle already did, what was sold once it is closed
+pub async fn flatten_position(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    asset: &Asset,
+    token_id: &str,
+    size: Decimal,
+) -> Option<Fill> {
+    if journal.is_closed(token_id) {
+        println!("Position in {token_id} was already closed");
+        // the sale is in the store already and counted from there
+        return Some(Fill::default());
     }
+    sell_position(exchange, journal, asset, token_id, size).await
+}
 
-    let hedge_order: OrderResponse = place_hedge_order(
-        &client,
-        &signer,
-        hedge_config.hedge_asset_id.clone(),
-        hedge_size,
-        hedge_config.hedge_enter_price,
-        &hedge_config.asset,
-    )
-    .await?;
-    println!("Hedge order placed: {:?}", hedge_order);
-    sleep(Duration::from_secs(10)).await;
-
-    loop {
-        let hedge_order_status: OpenOrderResponse =
-            get_order_with_retry(client, hedge_order.order_id.as_str(), 20, &hedge_config.asset).await?;
-        println!("Hedge order status: {:?}", hedge_order_status.status);
-        if hedge_order_status.status == OrderStatusType::Matched {
-            HEDGE_ORDERS_MATCHED_TOTAL
-                .with_label_values(&[&hedge_config.asset.to_string()])
-                .inc();
-
-            println!("Hedge order matched");
-            return Ok(1);
+/// market-sells `size` of the token and journals the sale, what was sold once it is closed
+pub async fn sell_position(
+    exchange: &dyn Exchange,
+    journal: &CycleJournal,
+    asset: &Asset,
+    token_id: &str,
+    size: Decimal,
+) -> Option<Fill> {
+    match close_position_with_retry(exchange, token_id, size, asset).await {
+        Some(closed_order) => {
+            journal.closed(token_id, size, &closed_order);
+            println!("Closed {size} of {token_id}: {:?}", closed_order);
+            Some(closed_order.sold())
         }
-        sleep(Duration::from_secs(1)).await;
-        if hedge_order_status.status != OrderStatusType::Matched && allow_stop_loss(hedge_config.timestamp, hedge_config.stop_loss_after) {
-            STOP_LOSS_TOTAL
-                .with_label_values(&[&hedge_config.asset.to_string()])
-                .inc();
-
-            println!("Stop loss reached, cancelling hedge order and closing position...");
-            timed_request(
-                "polymarket",
-                "cancel_order",
-                client.cancel_order(&hedge_order.order_id.as_str()),
-            )
-            .await?;
-
-            HEDGE_ORDERS_CANCELLED_TOTAL
-                .with_label_values(&[&hedge_config.asset.to_string()])
-                .inc();
-            println!("Hedge order canceled");
-            sleep(Duration::from_secs(5)).await;
-            let hedge_order_status: OpenOrderResponse =
-                get_order_with_retry(client, hedge_order.order_id.as_str(), 10, &hedge_config.asset).await?;
-            if hedge_order_status.size_matched > Decimal::zero()
-                && hedge_order_status.size_matched != hedge_size
-            {
-                HEDGE_ORDERS_PARTIAL_TOTAL
-                    .with_label_values(&[&hedge_config.asset.to_string()])
-                    .inc();
-
-                println!("Hedge order partially matched, closing it...");
-                let closing_hedge_size =
-                    normalized_size(hedge_order_status.size_matched, hedge_size);
-                if let Some(closed_order) = close_position_with_retry(
-                    client,
-                    signer,
-                    &hedge_config.hedge_asset_id,
-                    closing_hedge_size,
-                    30,
-                    &hedge_config.asset
-                )
-                .await
-                {
-                    println!(
-                        "Hedge order after partially filling closed: {:?}",
-                        closed_order
-                    );
-                } else {
-                    println!("Failed to close hedge order");
-                }
-            }
-
-            if let Some(closed_order) = close_position_with_retry(
-                client,
-                signer,
-                &hedge_config.initial_asset_id,
-                hedge_config.close_size,
-                30,
-                &hedge_config.asset
-            )
-            .await
-            {
-                println!("Initial position closed after sl: {:?}", closed_order);
-                return Ok(-1);
-            } else {
-                println!("Failed to close initial position");
-                return Ok(0);
-            }
+        None => {
+            println!("Failed to close {size} of {token_id}");
+            None
         }
     }
 }
 
 // if before market start left <= grace_seconds, we can't open new positions
-pub fn allow_trade(market_timestamp: i64, grace_seconds: &i64) -> bool {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("time went backwards")
-        .as_secs() as i64;
-
-    now <= market_timestamp - grace_seconds
-}
-
-pub fn nearest_quarter_hour() -> i64 {
-    let now = Local::now();
-
-    let minute = now.minute();
-    let next_quarter_minute = ((minute / 15) + 1) * 15;
-
-    let quarter = if next_quarter_minute < 60 {
-        now.with_minute(next_quarter_minute)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .with_nanosecond(0)
-            .unwrap()
-    } else {
-        (now + Duration::from_hours(1))
-            .with_minute(0)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .with_nanosecond(0)
-            .unwrap()
-    };
-
-    quarter.timestamp()
+pub fn allow_trade(market_timestamp: i64, grace_seconds: &i64, clock: &dyn Clock) -> bool {
+    allow_trade_at(market_timestamp, *grace_seconds, clock.now())
 }
 
-pub fn next_half_hour() -> i64 {
-    let now: DateTime<Local> = Local::now();
-
-    let minute = now.minute();
-    let current_quarter_minute = (minute / 15) * 15;
-
-    // Начало текущего 15-минутного блока
-    let base = now
-        .with_minute(current_quarter_minute)
-        .unwrap()
-        .with_second(0)
-        .unwrap()
-        .with_nanosecond(0)
-        .unwrap();
-
-    // Через один интервал = +30 минут
-    let target = base + Duration::from_mins(30);
-
-    target.timestamp()
-}
-
-pub async fn get_tokens(
-    http_client: &http_client,
-    timestamp: &i64,
-    asset: Asset,
-) -> Result<MarketResponse, reqwest::Error> {
-    let url =
-        format!("https://gamma-api.polymarket.com/markets/slug/{asset}-updown-15m-{timestamp}");
-    let resp = http_client.get(&url).send().await?;
-
-    let api_resp: MarketApiResponse = resp.json().await?;
-
-    let tokens: Vec<String> = api_resp
-        .clob_token_ids
-        .trim_matches(|c| c == '[' || c == ']')
-        .split(',')
-        .map(|s| s.trim().trim_matches('"').to_string())
-        .collect();
-
-    Ok(MarketResponse {
-        first_asset_id: tokens[0].clone(),
-        second_asset_id: tokens[1].clone(),
-    })
+/// `allow_trade` at `now` (unix seconds)
+pub fn allow_trade_at(market_timestamp: i64, grace_seconds: i64, now: i64) -> bool {
+    now <= market_timestamp - grace_seconds
 }
 
 pub async fn close_position_by_market(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
-    token_id: &String,
+    exchange: &dyn Exchange,
+    token_id: &str,
     amount: Decimal,
-) -> polymarket_client_sdk::Result<PostOrderResponse> {
-    let market_order = client
-        .market_order()
-        .token_id(token_id)
-        .amount(Amount::shares(amount)?)
-        .side(Side::Sell)
-        .order_type(OrderType::FOK)
-        .build()
-        .await?;
-    let signed_order = client.sign(signer, market_order).await?;
+) -> polymarket_client_sdk::Result<MarketOrderResponse> {
     println!("closing position by market order",);
-    let result = timed_request(
-        "polymarket",
-        "close_position_by_market",
-        client.post_order(signed_order),
-    )
-    .await?;
-
-    Ok(result[0].clone())
+    exchange.place_market_order(token_id, amount, Side::Sell).await
 }
 
 pub async fn place_hedge_order(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
+    exchange: &dyn Exchange,
     token_id: String,
     order_size: Decimal,
     price: Decimal,
     asset: &Asset,
-) -> polymarket_client_sdk::Result<OrderResponse> {
+) -> Result<OrderResponse, BotError> {
     HEDGE_ORDERS_TOTAL
-        .with_label_values(&[asset.to_string().as_str()])
+        .with_label_values(&[asset.label()])
         .inc();
 
-    let order = client
-        .limit_order()
-        .token_id(&token_id)
-        .size(order_size)
-        .price(price)
-        .side(Side::Buy)
-        .order_type(OrderType::GTC)
-        .build()
-        .await?;
-
-    let signed_order = client.sign(signer, order).await?;
-    let response = timed_request(
-        "polymarket",
-        "place_hedge_order",
-        client.post_order(signed_order),
-    )
-    .await?;
-
-    Ok(OrderResponse {
-        token_id: token_id,
-        order_id: response[0].order_id.clone(),
-    })
+    place_buy_with_retry(exchange, &token_id, order_size, price, asset).await
 }
 
+/// buys both tokens at their entry price, the first is cancelled when the second fails
 pub async fn open_start_positions(
-    client: &Arc<Client<Authenticated<Normal>>>,
-    signer: &LocalSigner<SigningKey>,
+    exchange: &dyn Exchange,
     order_size: Decimal,
-    price: Decimal,
-    tokens: MarketResponse,
-) -> polymarket_client_sdk::Result<Option<Vec<OrderResponse>>> {
-    let mut orders: Vec<OrderResponse> = vec![];
-    let first_order = client
-        .limit_order()
-        .token_id(&tokens.first_asset_id)
-        .size(order_size)
-        .price(price)
-        .side(Side::Buy)
-        .order_type(OrderType::GTC)
-        .build()
-        .await?;
-
-    let signed_order = client.sign(signer, first_order).await?;
-    let response = timed_request(
-        "polymarket",
-        "open_first_start_positions",
-        client.post_order(signed_order),
-    )
-    .await?;
-    orders.push(OrderResponse {
-        token_id: tokens.first_asset_id,
-        order_id: response[0].order_id.clone(),
-    });
+    prices: [Decimal; 2],
+    tokens: &MarketResponse,
+    asset: &Asset,
+) -> Result<[OrderResponse; 2], BotError> {
+    let first_order =
+        place_buy_with_retry(exchange, &tokens.first_asset_id, order_size, prices[0], asset)
+            .await?;
 
     sleep(Duration::from_secs(1)).await;
 
-    let second_order = client
-        .limit_order()
-        .token_id(&tokens.second_asset_id)
-        .size(order_size)
-        .price(price)
-        .side(Side::Buy)
-        .order_type(OrderType::GTC)
-        .build()
-        .await?;
+    let second_order =
+        match place_buy_with_retry(exchange, &tokens.second_asset_id, order_size, prices[1], asset)
+            .await
+        {
+            Ok(order) => order,
+            Err(e) => {
+                // a single entry is a naked position, it must not outlive the failed pair.
+                // One that can't be cancelled here is left to `clear_entries`
+                if let Err(cancel) =
+                    cancel_order_with_retry(exchange, &first_order.order_id, asset).await
+                {
+                    eprintln!(
+                        "Failed to cancel entry order {} after the second one failed: {cancel}",
+                        first_order.order_id
+                    );
+                }
+                return Err(e);
+            }
+        };
+
+    Ok([first_order, second_order])
+}
 
-    let signed_order = client.sign(signer, second_order).await?;
-    let response = timed_request(
-        "polymarket",
-        "open_second_start_positions",
-        client.post_order(signed_order),
-    )
-    .await?;
-    orders.push(OrderResponse {
-        token_id: tokens.second_asset_id,
-        order_id: response[0].order_id.clone(),
-    });
+/// cancels what failed entries left open on either token, the final states of those orders
+/// and the shares held of each token after that
+pub async fn clear_entries(
+    exchange: &dyn Exchange,
+    tokens: &MarketResponse,
+    asset: &Asset,
+) -> Result<(Vec<OrderState>, Vec<(String, Decimal)>), BotError> {
+    let token_ids = [&tokens.first_asset_id, &tokens.second_asset_id];
+    let mut cancelled = vec![];
+    for order in exchange.open_orders().await? {
+        if !token_ids.contains(&&order.token_id) {
+            continue;
+        }
+        println!("Cancelling entry order {} left by a failed placement", order.order_id);
+        cancel_order_with_retry(exchange, &order.order_id, asset).await?;
+        cancelled.push(get_order_with_retry(exchange, &order.order_id, asset).await?);
+    }
 
-    Ok(Some(orders))
+    let mut held = vec![];
+    for token_id in token_ids {
+        let balance = exchange.token_balance(token_id).await?;
+        if balance > Decimal::ZERO {
+            held.push((token_id.clone(), balance));
+        }
+    }
+    Ok((cancelled, held))
 }
 
 pub async fn get_asset_price(
-    client: &Client<Authenticated<Normal>>,
+    exchange: &dyn Exchange,
     token_id: &str,
-) -> polymarket_client_sdk::Result<PriceResponse> {
-    let price_request = PriceRequest::builder()
-        .token_id(token_id)
-        .side(Side::Sell)
-        .build();
-
-    timed_request("polymarket", "price", client.price(&price_request)).await
+    asset: &Asset,
+) -> Result<Decimal, BotError> {
+    policies()
+        .get_price
+        .run(asset, "get_price", || async {
+            Ok(exchange.get_price(token_id, Side::Sell).await?)
+        })
+        .await
 }
@@ -0,0 +1,122 @@
+//! A small replay through the backtest and the summary it adds up to.
+
+use common::backtest::{BacktestOutcome, MarketData, MarketRecord, run_cycle, summarize};
+use common::dto::{BookLevel, BookSnapshot};
+use common::{Asset, BotConfig};
+use rust_decimal::Decimal;
+
+/// start of the first replayed 15m market
+const START: i64 = 1_765_000_000;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+/// one level on each side
+fn book(bid: i64, ask: i64) -> BookSnapshot {
+    BookSnapshot {
+        bids: vec![BookLevel {
+            price: cents(bid),
+            size: Decimal::from(50),
+        }],
+        asks: vec![BookLevel {
+            price: cents(ask),
+            size: Decimal::from(50),
+        }],
+    }
+}
+
+/// seconds from the start, up and down books as bid and ask in cents
+type Snapshot = (i64, (i64, i64), (i64, i64));
+
+fn market(timestamp: i64, records: &[Snapshot]) -> MarketData {
+    MarketData {
+        asset: Asset::BTC,
+        slug: format!("btc-updown-15m-{timestamp}"),
+        timestamp,
+        records: records
+            .iter()
+            .map(|&(offset, up, down)| MarketRecord {
+                ts: timestamp + offset,
+                up: book(up.0, up.1),
+                down: book(down.0, down.1),
+            })
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn a_replay_adds_up_to_its_summary() {
+    let config = BotConfig::parse(
+        r#"
+        [strategy]
+        order_size = 10
+        limit_enter_price = 0.45
+        hedge_enter_price = 0.50
+        dont_allow_trade_before = 90
+        dont_allow_holding_before = 10
+        stop_loss_after = 15
+
+        [assets.btc]
+        "#,
+    )
+    .unwrap();
+    let settings = config.settings(&Asset::BTC).unwrap();
+
+    let markets = [
+        // up fills, then the hedge on down: 10 pairs for 9.50
+        market(
+            START,
+            &[
+                (-200, (40, 60), (40, 60)),
+                (-150, (40, 45), (40, 60)),
+                (-100, (40, 60), (40, 50)),
+            ],
+        ),
+        // up fills, the hedge never does and up is sold at 0.30 after the stop-loss
+        market(
+            START + 900,
+            &[
+                (-200, (40, 60), (40, 60)),
+                (-150, (40, 45), (40, 60)),
+                (20, (30, 60), (40, 60)),
+            ],
+        ),
+        // nothing crosses before holding is over
+        market(
+            START + 1_800,
+            &[(-200, (40, 60), (40, 60)), (-5, (40, 60), (40, 60))],
+        ),
+        // recorded too late to enter
+        market(START + 2_700, &[(-50, (40, 60), (40, 60))]),
+    ];
+    let mut reports = vec![];
+    for market in &markets {
+        reports.push(run_cycle(market, &settings).await);
+    }
+
+    let outcomes: Vec<_> = reports.iter().map(|r| r.outcome).collect();
+    assert_eq!(
+        outcomes,
+        [
+            BacktestOutcome::Hedged,
+            BacktestOutcome::StopLoss,
+            BacktestOutcome::NoFill,
+            BacktestOutcome::Skipped
+        ]
+    );
+    let pnls: Vec<_> = reports.iter().map(|r| r.pnl).collect();
+    assert_eq!(pnls, [cents(50), cents(-150), Decimal::ZERO, Decimal::ZERO]);
+
+    let summary = summarize(&reports);
+    assert_eq!(summary.cycles, 4);
+    assert_eq!(summary.traded, 2);
+    assert_eq!((summary.wins, summary.losses), (1, 1));
+    assert_eq!((summary.no_fill, summary.skipped), (1, 1));
+    assert_eq!(summary.win_rate, cents(50));
+    assert_eq!(summary.pnl, cents(-100));
+    // down from the 0.50 after the first market to -1.00
+    assert_eq!(summary.max_drawdown, cents(150));
+    assert_eq!((summary.legs_placed, summary.legs_filled), (6, 2));
+    assert_eq!(summary.fill_rate, Decimal::from(2) / Decimal::from(6));
+}
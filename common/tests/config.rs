@@ -0,0 +1,131 @@
+//! Config validation, every error reported at once.
+
+use common::{Asset, BotConfig};
+use rust_decimal::Decimal;
+
+const STRATEGY: &str = r#"
+[strategy]
+order_size = 10
+limit_enter_price = 0.49
+hedge_enter_price = 0.50
+dont_allow_trade_before = 90
+dont_allow_holding_before = 10
+stop_loss_after = 15
+"#;
+
+fn errors(raw: &str) -> Vec<String> {
+    BotConfig::parse(raw).unwrap_err().errors
+}
+
+#[test]
+fn every_invalid_value_is_reported() {
+    let errors = errors(&format!(
+        r#"
+        [paper]
+        balance = 0
+
+        [clock]
+        max_skew = 0
+
+        {STRATEGY}
+
+        [assets.btc]
+        order_size = -1
+        stop_loss_after = 900
+        "#
+    ));
+    assert_eq!(
+        errors,
+        [
+            "paper.balance: must be positive, got 0",
+            "clock.max_skew: must be positive",
+            "assets.btc.order_size: must be positive, got -1",
+            "assets.btc.stop_loss_after: must be within [0, 900), got 900",
+        ]
+    );
+}
+
+#[test]
+fn unknown_keys_are_reported_per_section() {
+    let errors = errors(&format!(
+        r#"
+        [paper]
+        balanse = 100
+
+        {STRATEGY}
+
+        [assets.btc]
+        ordr_size = 5
+
+        [assets.eth]
+        order_size = "ten"
+        "#
+    ));
+    assert_eq!(errors.len(), 3, "{errors:?}");
+    assert!(errors[0].starts_with("assets.btc: unknown field `ordr_size`"));
+    assert!(errors[1].starts_with("assets.eth: invalid value"));
+    assert!(errors[2].starts_with("paper: unknown field `balanse`"));
+}
+
+#[test]
+fn a_missing_key_is_reported_once() {
+    let errors = errors(
+        r#"
+        [strategy]
+        limit_enter_price = 0.49
+        hedge_enter_price = 0.50
+        dont_allow_trade_before = 90
+        stop_loss_after = 15
+
+        [assets.btc]
+        "#,
+    );
+    // checks that read the zeroed keys stay quiet, the others still run
+    assert_eq!(
+        errors,
+        [
+            "assets.btc.order_size: not set here nor in [strategy]",
+            "assets.btc.dont_allow_holding_before: not set here nor in [strategy]",
+        ]
+    );
+}
+
+#[test]
+fn asset_sections_override_the_strategy() {
+    let config = BotConfig::parse(&format!(
+        r#"
+        {STRATEGY}
+
+        [assets.btc]
+
+        [assets.eth]
+        order_size = 25
+        market = "1h"
+        "#
+    ))
+    .unwrap();
+    let btc = config.settings(&Asset::BTC).unwrap();
+    let eth = config.settings(&Asset::ETH).unwrap();
+    assert_eq!(btc.order_size, Decimal::TEN);
+    assert_eq!(eth.order_size, Decimal::from(25));
+    assert_eq!(eth.limit_enter_price, btc.limit_enter_price);
+    assert_eq!((btc.market.interval, eth.market.interval), (900, 3_600));
+
+    // an override is checked against the market it trades
+    let errors = errors(&format!(
+        r#"
+        {STRATEGY}
+
+        [assets.eth]
+        market = "1h"
+        stop_loss_after = 1200
+
+        [assets.sol]
+        stop_loss_after = 1200
+        "#
+    ));
+    assert_eq!(
+        errors,
+        ["assets.sol.stop_loss_after: must be within [0, 900), got 1200"]
+    );
+}
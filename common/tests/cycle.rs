@@ -0,0 +1,598 @@
+//! The cycle state machine, each state set up by hand and stepped with hand-made order states.
+
+use common::cycle::{CloseReason, CycleAction, CycleEvent, CycleMachine, CycleState, Market};
+use common::dto::{Fill, HedgeConfig, MarketResponse, OrderResponse, OrderState};
+use common::outcome::CycleOutcome;
+use common::{Asset, BotConfig};
+use polymarket_client_sdk::clob::types::OrderStatusType;
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+/// start of the market the cycles trade
+const START: i64 = 1_765_000_000;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+fn machine(state: CycleState) -> CycleMachine {
+    let config = BotConfig::parse(
+        r#"
+        [strategy]
+        order_size = 10
+        limit_enter_price = 0.45
+        hedge_enter_price = 0.50
+        dont_allow_trade_before = 90
+        dont_allow_holding_before = 10
+        stop_loss_after = 15
+
+        [assets.btc]
+        "#,
+    )
+    .unwrap();
+    let settings = config.settings(&Asset::BTC).unwrap();
+    CycleMachine::with_state(Asset::BTC, settings, state).with_logging(false)
+}
+
+fn market() -> Market {
+    Market {
+        timestamp: START,
+        tokens: MarketResponse {
+            first_asset_id: "up".to_string(),
+            second_asset_id: "down".to_string(),
+        },
+        prices: [cents(45), cents(45)],
+    }
+}
+
+fn entry_resting() -> CycleState {
+    CycleState::EntryResting {
+        market: market(),
+        first: "entry-up".to_string(),
+        second: "entry-down".to_string(),
+        stopping: false,
+    }
+}
+
+/// 10 up held, hedged on down against the down entry
+fn hedge() -> HedgeConfig {
+    HedgeConfig {
+        stop_loss_after: 15,
+        asset: Asset::BTC,
+        second_order_id: "entry-down".to_string(),
+        hedge_asset_id: "down".to_string(),
+        initial_asset_id: "up".to_string(),
+        hedge_size: Decimal::TEN,
+        hedge_enter_price: cents(50),
+        close_size: Decimal::TEN,
+        timestamp: START,
+    }
+}
+
+fn leg_matched() -> CycleState {
+    CycleState::LegMatched {
+        hedge: hedge(),
+        held_order: None,
+        cancel_sent: true,
+    }
+}
+
+fn hedging(order_id: Option<&str>) -> CycleState {
+    CycleState::Hedging {
+        hedge: hedge(),
+        order_id: order_id.map(str::to_string),
+        size: Decimal::TEN,
+    }
+}
+
+fn stop_loss() -> CycleState {
+    CycleState::StopLoss {
+        hedge: hedge(),
+        order_id: "hedge".to_string(),
+        size: Decimal::TEN,
+        reason: CloseReason::StopLoss,
+    }
+}
+
+fn order(order_id: &str, status: OrderStatusType, size_matched: i64) -> OrderState {
+    OrderState {
+        order_id: order_id.to_string(),
+        token_id: order_id.trim_start_matches("entry-").to_string(),
+        status,
+        original_size: Decimal::TEN,
+        size_matched: Decimal::from(size_matched),
+        price: cents(45),
+    }
+}
+
+fn orders(orders: &[(&str, OrderStatusType, i64)]) -> CycleEvent {
+    CycleEvent::Orders(
+        orders
+            .iter()
+            .map(|&(order_id, status, size_matched)| order(order_id, status, size_matched))
+            .collect(),
+    )
+}
+
+fn close(token_id: &str, size: i64) -> CycleAction {
+    CycleAction::Close {
+        token_id: token_id.to_string(),
+        size: Decimal::from(size),
+    }
+}
+
+fn cancel(order_id: &str) -> CycleAction {
+    CycleAction::Cancel(order_id.to_string())
+}
+
+fn place_hedge(size: i64) -> CycleAction {
+    CycleAction::PlaceHedge {
+        token_id: "down".to_string(),
+        size: Decimal::from(size),
+        price: cents(50),
+    }
+}
+
+#[test]
+fn resting_entries_are_cancelled_once_holding_is_over() {
+    let mut machine = machine(entry_resting());
+    let both_live = || {
+        orders(&[
+            ("entry-up", OrderStatusType::Live, 0),
+            ("entry-down", OrderStatusType::Live, 0),
+        ])
+    };
+
+    assert_eq!(machine.step(both_live(), START - 60), []);
+    assert!(matches!(machine.state(), CycleState::EntryResting { .. }));
+    assert_eq!(
+        machine.step(both_live(), START - 5),
+        [cancel("entry-up"), cancel("entry-down")]
+    );
+
+    // both came back cancelled without a fill
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Canceled, 0),
+            ("entry-down", OrderStatusType::Canceled, 0),
+        ]),
+        START - 4,
+    );
+    assert_eq!(actions, []);
+    assert!(matches!(
+        machine.state(),
+        CycleState::Done(CycleOutcome::NoFill)
+    ));
+}
+
+#[test]
+fn a_matched_leg_cancels_the_other_entry_and_hedges() {
+    let mut machine = machine(entry_resting());
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Matched, 10),
+            ("entry-down", OrderStatusType::Live, 0),
+        ]),
+        START - 60,
+    );
+    assert_eq!(actions, [cancel("entry-down")]);
+    let CycleState::LegMatched {
+        hedge, held_order, ..
+    } = machine.state()
+    else {
+        panic!("went to {:?}", machine.state());
+    };
+    assert_eq!(
+        (
+            hedge.initial_asset_id.as_str(),
+            hedge.hedge_asset_id.as_str()
+        ),
+        ("up", "down")
+    );
+    assert_eq!((hedge.hedge_size, held_order), (Decimal::TEN, &None));
+
+    let actions = machine.step(
+        orders(&[("entry-down", OrderStatusType::Canceled, 0)]),
+        START - 55,
+    );
+    assert_eq!(actions, [place_hedge(10)]);
+    assert!(matches!(
+        machine.state(),
+        CycleState::Hedging { order_id: None, .. }
+    ));
+}
+
+#[test]
+fn a_partial_fill_at_the_holding_deadline_is_hedged_at_its_size() {
+    let mut machine = machine(entry_resting());
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Live, 4),
+            ("entry-down", OrderStatusType::Live, 0),
+        ]),
+        START - 5,
+    );
+    assert_eq!(actions, [cancel("entry-up")]);
+    assert!(matches!(
+        machine.state(),
+        CycleState::LegMatched {
+            held_order: Some(_),
+            cancel_sent: false,
+            ..
+        }
+    ));
+
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Canceled, 4),
+            ("entry-down", OrderStatusType::Live, 0),
+        ]),
+        START - 4,
+    );
+    assert_eq!(actions, [cancel("entry-down")]);
+
+    // the held order filled one more before its cancel went through
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Canceled, 5),
+            ("entry-down", OrderStatusType::Canceled, 0),
+        ]),
+        START - 3,
+    );
+    assert_eq!(actions, [place_hedge(5)]);
+    let CycleState::Hedging { hedge, size, .. } = machine.state() else {
+        panic!("went to {:?}", machine.state());
+    };
+    assert_eq!(
+        (hedge.close_size, *size),
+        (Decimal::from(5), Decimal::from(5))
+    );
+}
+
+#[test]
+fn a_matched_hedge_finishes_the_cycle() {
+    let mut machine = machine(hedging(None));
+    let placed = CycleEvent::HedgePlaced(OrderResponse {
+        order_id: "hedge".to_string(),
+        token_id: "down".to_string(),
+    });
+    assert_eq!(
+        machine.step(placed, START - 50),
+        [CycleAction::Wait(Duration::from_secs(10))]
+    );
+    assert_eq!(machine.watched(), ["hedge"]);
+
+    assert_eq!(
+        machine.step(orders(&[("hedge", OrderStatusType::Live, 0)]), START),
+        []
+    );
+    assert_eq!(
+        machine.step(
+            orders(&[("hedge", OrderStatusType::Matched, 10)]),
+            START + 5
+        ),
+        []
+    );
+    assert!(matches!(
+        machine.state(),
+        CycleState::Done(CycleOutcome::Hedged(_))
+    ));
+}
+
+#[test]
+fn the_stop_loss_fires_at_its_deadline_and_sells_both_sides() {
+    let mut machine = machine(hedging(Some("hedge")));
+    assert_eq!(machine.step(CycleEvent::Tick, START + 14), []);
+    assert_eq!(
+        machine.step(CycleEvent::Tick, START + 15),
+        [cancel("hedge"), CycleAction::Wait(Duration::from_secs(5))]
+    );
+    assert!(matches!(
+        machine.state(),
+        CycleState::StopLoss {
+            reason: CloseReason::StopLoss,
+            ..
+        }
+    ));
+
+    // the hedge took 4 before it was cancelled
+    let actions = machine.step(
+        orders(&[("hedge", OrderStatusType::Canceled, 4)]),
+        START + 20,
+    );
+    assert_eq!(actions, [close("down", 4), close("up", 10)]);
+
+    let sold = |token_id: &str| CycleEvent::Closed {
+        token_id: token_id.to_string(),
+        sold: Some(Fill {
+            size: Decimal::from(4),
+            price: cents(40),
+        }),
+    };
+    assert_eq!(machine.step(sold("down"), START + 21), []);
+    assert!(matches!(
+        machine.state(),
+        CycleState::Flattening { pending: 1, .. }
+    ));
+    assert_eq!(machine.step(sold("up"), START + 21), []);
+    assert!(matches!(
+        machine.state(),
+        CycleState::Done(CycleOutcome::StopLoss(_))
+    ));
+}
+
+/// `control` and then `then` at `now`, the actions of `then`
+fn after(
+    state: CycleState,
+    control: CycleEvent,
+    then: CycleEvent,
+    now: i64,
+) -> (Vec<CycleAction>, CycleState) {
+    let mut machine = machine(state);
+    assert_eq!(machine.step(control, now), []);
+    let actions = machine.step(then, now);
+    (actions, machine.state().clone())
+}
+
+#[test]
+fn halt_stops_entering_and_keeps_hedging() {
+    let (actions, state) = after(
+        CycleState::AwaitingMarket {
+            market: None,
+            failures: 0,
+        },
+        CycleEvent::Halt,
+        CycleEvent::MarketOpened(market()),
+        START - 200,
+    );
+    assert_eq!(actions, []);
+    assert!(matches!(state, CycleState::Done(CycleOutcome::NoFill)));
+
+    let both_live = orders(&[
+        ("entry-up", OrderStatusType::Live, 0),
+        ("entry-down", OrderStatusType::Live, 3),
+    ]);
+    let (actions, state) = after(entry_resting(), CycleEvent::Halt, both_live, START - 60);
+    assert_eq!(actions, [cancel("entry-up"), cancel("entry-down")]);
+    assert!(matches!(
+        state,
+        CycleState::EntryResting { stopping: true, .. }
+    ));
+    // the fill left on the stopped entries is hedged
+    let mut machine = machine(state);
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Canceled, 0),
+            ("entry-down", OrderStatusType::Canceled, 3),
+        ]),
+        START - 59,
+    );
+    assert_eq!(
+        actions,
+        [CycleAction::PlaceHedge {
+            token_id: "up".to_string(),
+            size: Decimal::from(3),
+            price: cents(50),
+        }]
+    );
+
+    let cancelled = orders(&[("entry-down", OrderStatusType::Canceled, 0)]);
+    let (actions, state) = after(leg_matched(), CycleEvent::Halt, cancelled, START - 60);
+    assert_eq!(actions, [place_hedge(10)]);
+    assert!(matches!(state, CycleState::Hedging { .. }));
+
+    let (actions, state) = after(
+        hedging(Some("hedge")),
+        CycleEvent::Halt,
+        CycleEvent::Tick,
+        START - 60,
+    );
+    assert_eq!(actions, []);
+    assert!(matches!(
+        state,
+        CycleState::Hedging {
+            order_id: Some(_),
+            ..
+        }
+    ));
+
+    let (actions, state) = after(hedging(None), CycleEvent::Halt, CycleEvent::Tick, START);
+    assert_eq!(actions, []);
+    assert!(matches!(state, CycleState::Hedging { order_id: None, .. }));
+}
+
+#[test]
+fn flatten_sells_whatever_is_held() {
+    let (actions, state) = after(
+        CycleState::AwaitingMarket {
+            market: None,
+            failures: 0,
+        },
+        CycleEvent::Flatten,
+        CycleEvent::Tick,
+        START - 200,
+    );
+    assert_eq!(actions, []);
+    assert!(matches!(state, CycleState::Done(CycleOutcome::NoFill)));
+
+    let (actions, state) = after(
+        entry_resting(),
+        CycleEvent::Flatten,
+        orders(&[
+            ("entry-up", OrderStatusType::Live, 2),
+            ("entry-down", OrderStatusType::Canceled, 0),
+        ]),
+        START - 60,
+    );
+    assert_eq!(actions, [cancel("entry-up")]);
+    let mut machine = machine(state);
+    machine.step(CycleEvent::Flatten, START - 59);
+    let actions = machine.step(
+        orders(&[
+            ("entry-up", OrderStatusType::Canceled, 2),
+            ("entry-down", OrderStatusType::Canceled, 0),
+        ]),
+        START - 59,
+    );
+    assert_eq!(actions, [close("up", 2)]);
+
+    // the other entry's fill is sold along with the held leg
+    let cancelled = orders(&[("entry-down", OrderStatusType::Canceled, 3)]);
+    let (actions, state) = after(leg_matched(), CycleEvent::Flatten, cancelled, START - 60);
+    assert_eq!(actions, [close("down", 3), close("up", 10)]);
+    assert!(matches!(
+        state,
+        CycleState::Flattening {
+            reason: CloseReason::Flatten,
+            pending: 2,
+            ..
+        }
+    ));
+
+    let (actions, state) = after(
+        hedging(Some("hedge")),
+        CycleEvent::Flatten,
+        CycleEvent::Tick,
+        START - 60,
+    );
+    assert_eq!(
+        actions,
+        [cancel("hedge"), CycleAction::Wait(Duration::from_secs(5))]
+    );
+    assert!(matches!(
+        state,
+        CycleState::StopLoss {
+            reason: CloseReason::Flatten,
+            ..
+        }
+    ));
+
+    let (actions, _) = after(
+        hedging(None),
+        CycleEvent::Flatten,
+        CycleEvent::Tick,
+        START - 60,
+    );
+    assert_eq!(actions, [close("up", 10)]);
+
+    // already selling, the stop-loss keeps its reason
+    let cancelled = orders(&[("hedge", OrderStatusType::Canceled, 0)]);
+    let (actions, state) = after(stop_loss(), CycleEvent::Flatten, cancelled, START + 20);
+    assert_eq!(actions, [close("up", 10)]);
+    assert!(matches!(
+        state,
+        CycleState::Flattening {
+            reason: CloseReason::StopLoss,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn halt_and_flatten_leave_closing_and_finished_cycles_alone() {
+    let flattening = || CycleState::Flattening {
+        reason: CloseReason::StopLoss,
+        initial: "up".to_string(),
+        pending: 1,
+        failed: false,
+    };
+    for control in [CycleEvent::Halt, CycleEvent::Flatten] {
+        let (actions, state) = after(flattening(), control.clone(), CycleEvent::Tick, START + 20);
+        assert_eq!(actions, []);
+        assert!(matches!(state, CycleState::Flattening { pending: 1, .. }));
+
+        let (actions, state) = after(
+            CycleState::Done(CycleOutcome::NoFill),
+            control,
+            CycleEvent::Tick,
+            START + 20,
+        );
+        assert_eq!(actions, []);
+        assert!(matches!(state, CycleState::Done(CycleOutcome::NoFill)));
+    }
+}
+
+fn awaiting(failures: u32) -> CycleState {
+    CycleState::AwaitingMarket {
+        market: Some(market()),
+        failures,
+    }
+}
+
+fn cleared(held: &[(&str, i64)]) -> CycleEvent {
+    CycleEvent::EntriesCleared {
+        cancelled: vec![],
+        held: held
+            .iter()
+            .map(|&(token_id, size)| (token_id.to_string(), Decimal::from(size)))
+            .collect(),
+    }
+}
+
+#[test]
+fn failed_entries_are_cleared_before_they_are_placed_again() {
+    let clear = || CycleAction::ClearEntries {
+        tokens: market().tokens,
+    };
+    let place = CycleAction::PlaceEntries {
+        tokens: market().tokens,
+        size: Decimal::TEN,
+        prices: market().prices,
+    };
+
+    let mut retrying = machine(awaiting(0));
+    assert_eq!(
+        retrying.step(CycleEvent::EntriesFailed { retry: true }, START - 200),
+        []
+    );
+    assert_eq!(retrying.step(CycleEvent::Tick, START - 199), [clear()]);
+    assert_eq!(retrying.step(cleared(&[]), START - 199), [place]);
+    assert!(matches!(
+        retrying.state(),
+        CycleState::AwaitingMarket { failures: 1, .. }
+    ));
+
+    // a failed placement that bought anyway is sold instead of entered again
+    let mut filled = machine(awaiting(1));
+    assert_eq!(filled.step(CycleEvent::Tick, START - 199), [clear()]);
+    assert_eq!(
+        filled.step(cleared(&[("up", 4)]), START - 199),
+        [close("up", 4)]
+    );
+    assert!(matches!(
+        filled.state(),
+        CycleState::Flattening {
+            reason: CloseReason::Flatten,
+            pending: 1,
+            ..
+        }
+    ));
+
+    // out of attempts, past the trade window or halted, the cycle ends once it is cleared
+    for (state, event, now) in [
+        (awaiting(5), CycleEvent::Tick, START - 199),
+        (awaiting(1), CycleEvent::Tick, START - 60),
+        (awaiting(1), CycleEvent::Halt, START - 199),
+    ] {
+        let mut stopping = machine(state);
+        assert_eq!(stopping.step(event, now), [clear()]);
+        assert_eq!(stopping.step(cleared(&[]), now), []);
+        assert!(matches!(
+            stopping.state(),
+            CycleState::Done(CycleOutcome::NoFill)
+        ));
+    }
+
+    // placing them again can't fix every error
+    let mut rejected = machine(awaiting(0));
+    assert_eq!(
+        rejected.step(CycleEvent::EntriesFailed { retry: false }, START - 200),
+        []
+    );
+    assert_eq!(rejected.step(CycleEvent::Tick, START - 199), [clear()]);
+    assert_eq!(rejected.step(cleared(&[]), START - 199), []);
+    assert!(matches!(
+        rejected.state(),
+        CycleState::Done(CycleOutcome::NoFill)
+    ));
+}
@@ -0,0 +1,67 @@
+//! The pre-trade check of balance and allowance.
+
+use common::Asset;
+use common::dto::Collateral;
+use common::funds::{Reservations, Shortfall, available, required_collateral, shortfall};
+use rust_decimal::Decimal;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+#[test]
+fn a_cycle_needs_both_entries_and_the_hedge() {
+    let need = required_collateral(Decimal::TEN, [cents(45), cents(47)], cents(50));
+    assert_eq!(need, cents(1_420));
+
+    let funded = Collateral {
+        balance: cents(1_420),
+        allowance: Decimal::MAX,
+    };
+    assert_eq!(shortfall(&funded, need), None);
+
+    let short = Collateral {
+        balance: cents(1_419),
+        ..funded
+    };
+    assert_eq!(
+        shortfall(&short, need),
+        Some(Shortfall::Balance {
+            have: cents(1_419),
+            need
+        })
+    );
+}
+
+#[test]
+fn a_lost_approval_skips_the_cycle_whatever_the_balance() {
+    let revoked = Collateral {
+        balance: Decimal::from(1_000),
+        allowance: Decimal::ZERO,
+    };
+    let skipped = shortfall(&revoked, cents(1_420)).unwrap();
+    assert_eq!(skipped.reason(), "insufficient_allowance");
+}
+
+#[test]
+fn cycles_of_other_assets_keep_what_they_set_aside() {
+    let reservations = Reservations::default();
+    let btc = reservations.reserve(Asset::BTC, cents(1_420));
+    let eth = reservations.reserve(Asset::ETH, cents(1_000));
+    assert_eq!(reservations.others(Asset::BTC), cents(1_000));
+
+    // 30 USDC leave 5.80 to a sol cycle while both run
+    let collateral = Collateral {
+        balance: Decimal::from(30),
+        allowance: Decimal::MAX,
+    };
+    let left = available(&collateral, reservations.others(Asset::SOL));
+    assert_eq!(left.balance, cents(580));
+    assert!(shortfall(&left, cents(1_420)).is_some());
+
+    drop(eth);
+    let left = available(&collateral, reservations.others(Asset::SOL));
+    assert_eq!(shortfall(&left, cents(1_420)), None);
+    drop(btc);
+    assert_eq!(reservations.others(Asset::SOL), Decimal::ZERO);
+}
@@ -0,0 +1,104 @@
+//! Books of the market channel against a local websocket stand-in of the CLOB.
+
+use async_trait::async_trait;
+use common::clock::{Clock, ManualClock};
+use common::dto::BookSnapshot;
+use common::market_data::{MarketData, run_market_channel};
+use common::paper::BookSource;
+use futures::{SinkExt, StreamExt};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::Message;
+
+/// nothing to poll, books only come from the socket
+struct Empty;
+
+#[async_trait]
+impl BookSource for Empty {
+    async fn book(&self, _token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        Ok(BookSnapshot::default())
+    }
+}
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+/// `check` polled until it holds, the channel runs in its own task
+async fn eventually(what: &str, check: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if check() {
+            return;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+#[tokio::test]
+async fn books_follow_the_watched_tokens() {
+    let clock = Arc::new(ManualClock::new(1_765_000_000));
+    let data = MarketData::new(Arc::new(Empty), clock.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/ws/market", listener.local_addr().unwrap());
+    tokio::spawn(run_market_channel(url, data.clone()));
+    let _watch = data.watch(&["up"]);
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut ws = accept_async(stream).await.unwrap();
+    let subscription = loop {
+        match ws.next().await.unwrap().unwrap() {
+            Message::Text(text) if text.as_str() == "PING" => {
+                ws.send(Message::text("PONG")).await.unwrap();
+            }
+            Message::Text(text) => break serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            _ => {}
+        }
+    };
+    assert_eq!(subscription["type"], "market");
+    assert_eq!(subscription["assets_ids"], serde_json::json!(["up"]));
+
+    let book = serde_json::json!({
+        "event_type": "book",
+        "asset_id": "up",
+        "market": "0xmarket",
+        "timestamp": "1765000000000",
+        "bids": [{ "price": "0.40", "size": "10" }, { "price": "0.45", "size": "5" }],
+        "asks": [{ "price": "0.60", "size": "10" }],
+    });
+    ws.send(Message::text(book.to_string())).await.unwrap();
+    eventually("the book", || data.book("up").is_some_and(|b| b.streamed)).await;
+    let up = data.book("up").unwrap();
+    assert_eq!(
+        (up.best_bid(), up.updated_at),
+        (Some(cents(45)), 1_765_000_000)
+    );
+
+    let change = serde_json::json!({
+        "event_type": "price_change",
+        "market": "0xmarket",
+        "timestamp": "1765000000001",
+        "price_changes": [{ "asset_id": "up", "price": "0.50", "size": "5", "side": "SELL" }],
+    });
+    ws.send(Message::text(change.to_string())).await.unwrap();
+    eventually("the change", || {
+        data.book("up").and_then(|b| b.best_ask()) == Some(cents(50))
+    })
+    .await;
+
+    // a quiet market keeps the book, the socket would have sent any change
+    clock.advance(Duration::from_secs(60));
+    assert!(data.book("up").unwrap().fresh(clock.now(), 10));
+
+    // the poller takes over until the next snapshot
+    ws.close(None).await.ok();
+    eventually("the disconnect", || {
+        data.book("up").is_some_and(|b| !b.streamed)
+    })
+    .await;
+    assert!(!data.book("up").unwrap().fresh(clock.now(), 10));
+}
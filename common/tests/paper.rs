@@ -0,0 +1,122 @@
+//! The paper matcher against books set by hand.
+
+use async_trait::async_trait;
+use common::dto::{BookLevel, BookSnapshot};
+use common::exchange::Exchange;
+use common::paper::{BookSource, PaperExchange};
+use polymarket_client_sdk::clob::types::{OrderStatusType, Side};
+use rust_decimal::Decimal;
+use std::sync::{Arc, Mutex};
+
+const TOKEN: &str = "up";
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+/// the same book until the test sets another
+#[derive(Default)]
+struct HandBook(Mutex<BookSnapshot>);
+
+impl HandBook {
+    fn asks(&self, levels: &[(i64, i64)]) {
+        self.0.lock().unwrap().asks = levels
+            .iter()
+            .map(|&(price, size)| BookLevel {
+                price: cents(price),
+                size: Decimal::from(size),
+            })
+            .collect();
+    }
+}
+
+#[async_trait]
+impl BookSource for HandBook {
+    async fn book(&self, _token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+}
+
+fn exchange(book: &Arc<HandBook>) -> PaperExchange {
+    PaperExchange::new(Box::new(book.clone()), Decimal::from(100)).with_logging(false)
+}
+
+#[tokio::test]
+async fn a_crossing_buy_takes_the_asks_at_their_prices() {
+    let book = Arc::new(HandBook::default());
+    book.asks(&[(48, 5), (50, 10)]);
+    let paper = exchange(&book);
+
+    let order = paper
+        .place_limit_order(TOKEN, Decimal::TEN, cents(50), Side::Buy)
+        .await
+        .unwrap();
+    let state = paper.get_order(&order.order_id).await.unwrap();
+    assert_eq!(state.status, OrderStatusType::Matched);
+    assert_eq!(paper.position(TOKEN), Decimal::TEN);
+    // 5 at 0.48 and 5 at 0.50
+    assert_eq!(paper.balance(), cents(10_000 - 490));
+}
+
+#[tokio::test]
+async fn a_resting_buy_fills_at_its_limit_once_crossed() {
+    let book = Arc::new(HandBook::default());
+    book.asks(&[(55, 20)]);
+    let paper = exchange(&book);
+
+    let order = paper
+        .place_limit_order(TOKEN, Decimal::TEN, cents(50), Side::Buy)
+        .await
+        .unwrap();
+    assert_eq!(
+        paper.get_order(&order.order_id).await.unwrap().status,
+        OrderStatusType::Live
+    );
+    assert_eq!(paper.balance(), cents(10_000 - 500));
+
+    book.asks(&[(49, 20)]);
+    let state = paper.get_order(&order.order_id).await.unwrap();
+    assert_eq!(state.status, OrderStatusType::Matched);
+    assert_eq!(paper.balance(), cents(10_000 - 500));
+}
+
+#[tokio::test]
+async fn an_unchanged_book_fills_only_once() {
+    let book = Arc::new(HandBook::default());
+    book.asks(&[(50, 5)]);
+    let paper = exchange(&book);
+
+    let order = paper
+        .place_limit_order(TOKEN, Decimal::TEN, cents(50), Side::Buy)
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        let state = paper.get_order(&order.order_id).await.unwrap();
+        assert_eq!(state.status, OrderStatusType::Live);
+        assert_eq!(state.size_matched, Decimal::from(5));
+    }
+
+    // the level changed, what it shows now is new liquidity
+    book.asks(&[(50, 8)]);
+    let state = paper.get_order(&order.order_id).await.unwrap();
+    assert_eq!(state.status, OrderStatusType::Matched);
+    assert_eq!(state.size_matched, Decimal::TEN);
+}
+
+#[tokio::test]
+async fn cancelling_returns_the_unfilled_collateral() {
+    let book = Arc::new(HandBook::default());
+    book.asks(&[(50, 4)]);
+    let paper = exchange(&book);
+
+    let order = paper
+        .place_limit_order(TOKEN, Decimal::TEN, cents(50), Side::Buy)
+        .await
+        .unwrap();
+    paper.cancel_order(&order.order_id).await.unwrap();
+    let state = paper.get_order(&order.order_id).await.unwrap();
+    assert_eq!(state.status, OrderStatusType::Canceled);
+    assert_eq!(state.size_matched, Decimal::from(4));
+    assert_eq!(paper.position(TOKEN), Decimal::from(4));
+    assert_eq!(paper.balance(), cents(10_000 - 200));
+}
@@ -0,0 +1,74 @@
+//! Entry prices picked off hand-made books.
+
+use common::pricing::{EntryPricing, PriceSource, PricingMode, entry_prices};
+use common::{BookLevel, BookSnapshot};
+use rust_decimal::Decimal;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+fn book(bid: i64, ask: i64, size: i64) -> BookSnapshot {
+    BookSnapshot {
+        bids: vec![BookLevel { price: cents(bid), size: Decimal::from(size) }],
+        asks: vec![BookLevel { price: cents(ask), size: Decimal::from(size) }],
+    }
+}
+
+fn pricing() -> EntryPricing {
+    EntryPricing {
+        mode: PricingMode::Book,
+        ticks_below_bid: 1,
+        min_price: cents(30),
+        max_price: cents(49),
+        max_combined: cents(90),
+        max_spread: cents(10),
+        min_depth: Decimal::from(50),
+    }
+}
+
+#[test]
+fn entries_rest_a_tick_under_the_best_bid() {
+    let quote = entry_prices(
+        &pricing(),
+        cents(45),
+        cents(1),
+        [Some(&book(44, 46, 100)), Some(&book(40, 42, 100))],
+    )
+    .unwrap();
+    assert_eq!(quote.prices, [cents(43), cents(39)]);
+    assert_eq!(quote.sources, [PriceSource::Book; 2]);
+}
+
+#[test]
+fn unusable_books_fall_back_to_the_fixed_price() {
+    let quote = entry_prices(
+        &pricing(),
+        cents(45),
+        cents(1),
+        [Some(&book(20, 60, 100)), Some(&book(40, 42, 10))],
+    )
+    .unwrap();
+    assert_eq!(quote.prices, [cents(45), cents(45)]);
+    assert_eq!(quote.sources[0], PriceSource::Fallback("spread too wide"));
+    assert_eq!(quote.sources[1], PriceSource::Fallback("bids too thin"));
+
+    let quote = entry_prices(&pricing(), cents(45), cents(1), [None, None]).unwrap();
+    assert_eq!(quote.sources, [PriceSource::Fallback("no book"); 2]);
+}
+
+#[test]
+fn the_combined_cap_comes_off_the_dearer_side() {
+    // 0.49 (capped from 0.55) + 0.47 is over 0.90
+    let quote = entry_prices(
+        &pricing(),
+        cents(45),
+        cents(1),
+        [Some(&book(56, 57, 100)), Some(&book(48, 50, 100))],
+    )
+    .unwrap();
+    assert_eq!(quote.prices, [cents(43), cents(47)]);
+
+    let tight = EntryPricing { max_combined: cents(59), ..pricing() };
+    assert!(entry_prices(&tight, cents(45), cents(1), [None, None]).is_err());
+}
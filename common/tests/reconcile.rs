@@ -0,0 +1,160 @@
+//! Startup reconciliation of a paper account against the store.
+
+use async_trait::async_trait;
+use common::Asset;
+use common::config::OrphanPolicy;
+use common::dto::{BookLevel, BookSnapshot, MarketResponse, OrderState};
+use common::exchange::Exchange;
+use common::outcome::CycleOutcome;
+use common::paper::{BookSource, PaperExchange};
+use common::reconcile::reconcile;
+use common::store::Store;
+use polymarket_client_sdk::clob::types::{OrderStatusType, Side};
+use rust_decimal::Decimal;
+
+const START: i64 = 1_765_000_000;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+/// asks at 0.50 on every token, buys below rest and buys at 0.50 fill
+struct Asks;
+
+#[async_trait]
+impl BookSource for Asks {
+    async fn book(&self, _token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        Ok(BookSnapshot {
+            bids: vec![],
+            asks: vec![BookLevel {
+                price: cents(50),
+                size: Decimal::from(100),
+            }],
+        })
+    }
+}
+
+fn paper() -> PaperExchange {
+    PaperExchange::new(Box::new(Asks), Decimal::from(1_000)).with_logging(false)
+}
+
+fn store(name: &str) -> std::sync::Arc<Store> {
+    let path = std::env::temp_dir().join(format!("reconcile-{}-{name}.db", std::process::id()));
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{suffix}", path.display())).ok();
+    }
+    Store::open(path.to_str().unwrap()).unwrap()
+}
+
+fn tokens(first: &str, second: &str) -> MarketResponse {
+    MarketResponse {
+        first_asset_id: first.to_string(),
+        second_asset_id: second.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn only_markets_of_the_traded_assets_are_cleaned_up() {
+    let paper = paper();
+    let store = store("scope");
+    let ours = paper
+        .place_limit_order("btc-up", Decimal::TEN, cents(40), Side::Buy)
+        .await
+        .unwrap();
+    let eth = paper
+        .place_limit_order("eth-up", Decimal::TEN, cents(40), Side::Buy)
+        .await
+        .unwrap();
+    let manual = paper
+        .place_limit_order("other", Decimal::TEN, cents(40), Side::Buy)
+        .await
+        .unwrap();
+
+    // the btc cycle finished without cancelling its entry, the eth one is another bot's
+    let btc = store
+        .start_cycle(
+            Asset::BTC,
+            START,
+            START + 900,
+            10,
+            &tokens("btc-up", "btc-down"),
+        )
+        .unwrap();
+    btc.entry_placed(&[ours.clone(), ours.clone()], Decimal::TEN, [cents(40); 2]);
+    btc.finish(&CycleOutcome::NoFill);
+    store
+        .start_cycle(
+            Asset::ETH,
+            START,
+            START + 900,
+            10,
+            &tokens("eth-up", "eth-down"),
+        )
+        .unwrap();
+
+    let report = reconcile(&paper, &store, OrphanPolicy::Cancel, &[Asset::BTC])
+        .await
+        .unwrap();
+    assert_eq!((report.cancelled, report.foreign), (1, 2));
+    assert_eq!(
+        paper.get_order(&ours.order_id).await.unwrap().status,
+        OrderStatusType::Canceled
+    );
+    for order in [eth, manual] {
+        assert_eq!(
+            paper.get_order(&order.order_id).await.unwrap().status,
+            OrderStatusType::Live
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_cycle_holding_other_shares_than_its_orders_is_abandoned() {
+    let paper = paper();
+    let store = store("positions");
+    let up = paper
+        .place_limit_order("btc-up", Decimal::TEN, cents(50), Side::Buy)
+        .await
+        .unwrap();
+    let down = paper
+        .place_limit_order("btc-down", Decimal::TEN, cents(40), Side::Buy)
+        .await
+        .unwrap();
+    let journal = store
+        .start_cycle(
+            Asset::BTC,
+            START,
+            START + 900,
+            10,
+            &tokens("btc-up", "btc-down"),
+        )
+        .unwrap();
+    journal.entry_placed(
+        &[up.clone(), down.clone()],
+        Decimal::TEN,
+        [cents(50), cents(40)],
+    );
+    journal.order_status(&paper.get_order(&up.order_id).await.unwrap());
+
+    // 10 up were bought and are held, the down entry may still fill
+    let report = reconcile(&paper, &store, OrphanPolicy::Cancel, &[Asset::BTC])
+        .await
+        .unwrap();
+    assert_eq!((report.known, report.mismatched), (1, 0));
+    assert!(store.unfinished_cycle(Asset::BTC).unwrap().is_some());
+
+    // the store saw the down entry fill, the account holds none of it
+    journal.order_status(&OrderState {
+        order_id: down.order_id.clone(),
+        token_id: "btc-down".to_string(),
+        status: OrderStatusType::Matched,
+        original_size: Decimal::TEN,
+        size_matched: Decimal::TEN,
+        price: cents(40),
+    });
+    let report = reconcile(&paper, &store, OrphanPolicy::Cancel, &[Asset::BTC])
+        .await
+        .unwrap();
+    assert_eq!(report.mismatched, 1);
+    assert!(store.unfinished_cycle(Asset::BTC).unwrap().is_none());
+}
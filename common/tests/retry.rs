@@ -0,0 +1,168 @@
+//! Placing an order again after a failure that may or may not have reached the exchange.
+
+use async_trait::async_trait;
+use common::Asset;
+use common::dto::{
+    BookLevel, BookSnapshot, Collateral, MarketOrderResponse, OrderResponse, OrderState,
+};
+use common::exchange::Exchange;
+use common::paper::{BookSource, PaperExchange};
+use common::place_hedge_order;
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::error::Error;
+use reqwest::{Method, StatusCode};
+use rust_decimal::Decimal;
+use std::sync::Mutex;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+/// asks at 0.60, a buy at 0.50 rests
+struct Asks;
+
+#[async_trait]
+impl BookSource for Asks {
+    async fn book(&self, _token_id: &str) -> polymarket_client_sdk::Result<BookSnapshot> {
+        Ok(BookSnapshot {
+            bids: vec![],
+            asks: vec![BookLevel {
+                price: cents(60),
+                size: Decimal::from(100),
+            }],
+        })
+    }
+}
+
+/// how a placement fails
+#[derive(Clone, Copy)]
+enum Failure {
+    /// the order is on the book, the answer got lost
+    PlacedThenTimedOut,
+    TimedOut,
+    RateLimited,
+}
+
+/// the paper exchange with placements failing as scripted
+struct Flaky {
+    paper: PaperExchange,
+    failures: Mutex<Vec<Failure>>,
+}
+
+impl Flaky {
+    fn new(failures: &[Failure]) -> Self {
+        Flaky {
+            paper: PaperExchange::new(Box::new(Asks), Decimal::from(1_000)).with_logging(false),
+            failures: Mutex::new(failures.iter().rev().copied().collect()),
+        }
+    }
+}
+
+fn status(code: StatusCode) -> Error {
+    Error::status(code, Method::POST, "/order".to_string(), "scripted")
+}
+
+#[async_trait]
+impl Exchange for Flaky {
+    async fn place_limit_order(
+        &self,
+        token_id: &str,
+        size: Decimal,
+        price: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<OrderResponse> {
+        let failure = self.failures.lock().unwrap().pop();
+        match failure {
+            None => {
+                self.paper
+                    .place_limit_order(token_id, size, price, side)
+                    .await
+            }
+            Some(Failure::PlacedThenTimedOut) => {
+                self.paper
+                    .place_limit_order(token_id, size, price, side)
+                    .await?;
+                Err(status(StatusCode::GATEWAY_TIMEOUT))
+            }
+            Some(Failure::TimedOut) => Err(status(StatusCode::GATEWAY_TIMEOUT)),
+            Some(Failure::RateLimited) => Err(status(StatusCode::TOO_MANY_REQUESTS)),
+        }
+    }
+
+    async fn place_market_order(
+        &self,
+        token_id: &str,
+        shares: Decimal,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<MarketOrderResponse> {
+        self.paper.place_market_order(token_id, shares, side).await
+    }
+
+    async fn get_order(&self, order_id: &str) -> polymarket_client_sdk::Result<OrderState> {
+        self.paper.get_order(order_id).await
+    }
+
+    async fn cancel_order(&self, order_id: &str) -> polymarket_client_sdk::Result<()> {
+        self.paper.cancel_order(order_id).await
+    }
+
+    async fn get_price(
+        &self,
+        token_id: &str,
+        side: Side,
+    ) -> polymarket_client_sdk::Result<Decimal> {
+        self.paper.get_price(token_id, side).await
+    }
+
+    async fn open_orders(&self) -> polymarket_client_sdk::Result<Vec<OrderState>> {
+        self.paper.open_orders().await
+    }
+
+    async fn token_balance(&self, token_id: &str) -> polymarket_client_sdk::Result<Decimal> {
+        self.paper.token_balance(token_id).await
+    }
+
+    async fn collateral(&self, neg_risk: bool) -> polymarket_client_sdk::Result<Collateral> {
+        self.paper.collateral(neg_risk).await
+    }
+
+    async fn fee_rate_bps(&self, token_id: &str) -> polymarket_client_sdk::Result<u32> {
+        self.paper.fee_rate_bps(token_id).await
+    }
+}
+
+/// places the hedge through `failures`, the orders resting afterwards
+async fn hedge_through(failures: &[Failure]) -> (OrderResponse, Vec<OrderState>) {
+    let exchange = Flaky::new(failures);
+    let order = place_hedge_order(
+        &exchange,
+        "down".to_string(),
+        Decimal::TEN,
+        cents(50),
+        &Asset::BTC,
+    )
+    .await
+    .unwrap();
+    (order, exchange.open_orders().await.unwrap())
+}
+
+#[tokio::test]
+async fn a_timed_out_order_that_went_through_is_not_posted_again() {
+    let (order, open) = hedge_through(&[Failure::PlacedThenTimedOut]).await;
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].order_id, order.order_id);
+}
+
+#[tokio::test]
+async fn a_timed_out_order_that_never_landed_is_posted_again() {
+    let (order, open) = hedge_through(&[Failure::TimedOut]).await;
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].order_id, order.order_id);
+}
+
+#[tokio::test]
+async fn a_rate_limited_order_is_posted_again_without_a_lookup() {
+    let (order, open) = hedge_through(&[Failure::RateLimited, Failure::RateLimited]).await;
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].order_id, order.order_id);
+}
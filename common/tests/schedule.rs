@@ -0,0 +1,111 @@
+//! Market schedules and grace windows at pinned and manually moved times.
+
+use common::{Asset, BotConfig};
+use common::clock::{Clock, FixedClock, ManualClock};
+use common::market::preset;
+use common::utils::{allow_stop_loss, allow_trade};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 2025-10-17 11:50:00 UTC
+const MORNING: i64 = 1_760_701_800;
+
+#[test]
+fn the_quarter_hour_target_wraps_the_hour() {
+    let market = preset("15m").unwrap();
+    // 11:52, the cycle targets 12:15
+    let now = MORNING + 120;
+    assert_eq!(market.current(now), MORNING - 300);
+    assert_eq!(market.target(now), MORNING + 1_500);
+    assert_eq!(market.slug(Asset::BTC, MORNING + 1_500), "btc-updown-15m-1760703300");
+}
+
+#[test]
+fn hourly_slugs_follow_eastern_time_across_dst() {
+    let market = preset("1h").unwrap();
+    // 11:50 UTC is 7:50 EDT
+    let start = market.current(MORNING);
+    assert_eq!(start, MORNING - 3_000);
+    assert_eq!(market.slug(Asset::BTC, start), "bitcoin-up-or-down-october-17-7am-et");
+
+    // 2026-03-08 07:00 UTC: 1am EST ends, the next hour is 3am EDT
+    let spring = 1_772_953_200;
+    let before = market.current(spring - 1);
+    assert_eq!(market.end(before), spring);
+    assert_eq!(market.slug(Asset::ETH, before), "ethereum-up-or-down-march-8-1am-et");
+    assert_eq!(market.slug(Asset::ETH, spring), "ethereum-up-or-down-march-8-3am-et");
+}
+
+#[test]
+fn daily_markets_run_noon_to_noon_eastern() {
+    let market = preset("daily").unwrap();
+    let start = market.current(MORNING);
+    // 2025-10-16 12:00 EDT to 2025-10-17 12:00 EDT
+    assert_eq!(start, 1_760_630_400);
+    assert_eq!(market.end(start), 1_760_716_800);
+    assert_eq!(market.slug(Asset::SOL, start), "solana-up-or-down-on-october-17");
+}
+
+#[test]
+fn configured_assets_spell_their_own_slugs() {
+    let config = BotConfig::parse(
+        r#"
+        [strategy]
+        order_size = 10
+        limit_enter_price = 0.49
+        hedge_enter_price = 0.50
+        dont_allow_trade_before = 90
+        dont_allow_holding_before = 10
+        stop_loss_after = 15
+
+        [assets.doge]
+        name = "dogecoin"
+        market = "1h"
+
+        [assets.pepe]
+        enabled = false
+        "#,
+    )
+    .unwrap();
+    config.register_assets();
+    let doge: Asset = "doge".parse().unwrap();
+    assert_eq!(config.assets(), vec![doge]);
+    assert_eq!(doge.label(), "doge");
+
+    let settings = config.settings(&doge).unwrap();
+    let start = settings.market.current(MORNING);
+    assert_eq!(settings.market.slug(doge, start), "dogecoin-up-or-down-october-17-7am-et");
+    assert_eq!(preset("15m").unwrap().slug(doge, MORNING + 600), "doge-updown-15m-1760702400");
+}
+
+#[test]
+fn grace_windows_at_a_fixed_time() {
+    let start = MORNING + 600;
+    let end = start + 900;
+    let before_start = FixedClock(start - 60);
+    assert!(allow_trade(start, &90, &FixedClock(start - 90)));
+    assert!(!allow_trade(start, &90, &before_start));
+    assert!(!allow_stop_loss(start, end, 15, &before_start));
+    assert!(!allow_stop_loss(start, end, 15, &FixedClock(start + 10)));
+    assert!(allow_stop_loss(start, end, 15, &FixedClock(start + 15)));
+    assert!(!allow_stop_loss(start, end, 15, &FixedClock(end)));
+}
+
+#[tokio::test]
+async fn manual_time_wakes_sleepers_once_moved_past() {
+    let clock = Arc::new(ManualClock::new(MORNING));
+    let sleeper = tokio::spawn({
+        let clock = clock.clone();
+        async move { clock.sleep(Duration::from_secs(30)).await }
+    });
+    // the sleep counts from when it starts
+    tokio::task::yield_now().await;
+
+    clock.advance(Duration::from_secs(29));
+    tokio::task::yield_now().await;
+    assert!(!sleeper.is_finished());
+
+    clock.advance(Duration::from_secs(1));
+    sleeper.await.unwrap();
+    assert_eq!(clock.now(), MORNING + 30);
+}
@@ -0,0 +1,78 @@
+//! Cycle sizes under each policy.
+
+use common::funds::required_collateral;
+use common::sizing::{Sizing, SizingInput, SizingMode, order_size};
+use rust_decimal::Decimal;
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+fn input(wins: u32, losses: u32) -> SizingInput {
+    SizingInput {
+        bankroll: Decimal::from(1_000),
+        prices: [cents(45), cents(47)],
+        hedge_price: cents(45),
+        wins,
+        losses,
+        min_order_size: Decimal::from(5),
+    }
+}
+
+fn sizing(mode: SizingMode, fraction: i64) -> Sizing {
+    Sizing {
+        mode,
+        fraction: cents(fraction),
+        max_notional: None,
+        exit_price: cents(30),
+    }
+}
+
+#[test]
+fn fractions_of_the_bankroll_tie_up_the_whole_cycle() {
+    // 50 USDC over 1.37 a share for both entries and the hedge
+    let size = order_size(&sizing(SizingMode::Fraction, 5), Decimal::TEN, &input(0, 0)).unwrap();
+    assert_eq!(size, cents(3_649));
+
+    let capped = Sizing {
+        max_notional: Some(Decimal::from(20)),
+        ..sizing(SizingMode::Fraction, 5)
+    };
+    assert_eq!(order_size(&capped, Decimal::TEN, &input(0, 0)).unwrap(), cents(1_459));
+}
+
+#[test]
+fn the_whole_bankroll_passes_the_pre_trade_check() {
+    let input = input(0, 0);
+    let size = order_size(&sizing(SizingMode::Fraction, 100), Decimal::TEN, &input).unwrap();
+    let need = required_collateral(size, input.prices, input.hedge_price);
+    assert!(need <= input.bankroll, "{need} over {}", input.bankroll);
+
+    let capped = Sizing {
+        max_notional: Some(input.bankroll),
+        ..sizing(SizingMode::Fraction, 100)
+    };
+    let size = order_size(&capped, Decimal::TEN, &input).unwrap();
+    assert!(required_collateral(size, input.prices, input.hedge_price) <= input.bankroll);
+}
+
+#[test]
+fn kelly_only_bets_with_an_edge() {
+    // winning 0.08 against losing 0.17 needs a win rate over 68%
+    assert!(order_size(&sizing(SizingMode::Kelly, 25), Decimal::TEN, &input(5, 5)).is_err());
+    let size = order_size(&sizing(SizingMode::Kelly, 25), Decimal::TEN, &input(18, 2)).unwrap();
+    assert!(size > Decimal::ZERO);
+}
+
+#[test]
+fn sizes_below_the_market_minimum_are_refused() {
+    let small = SizingInput {
+        bankroll: Decimal::from(50),
+        ..input(0, 0)
+    };
+    assert!(order_size(&sizing(SizingMode::Fraction, 5), Decimal::TEN, &small).is_err());
+    assert_eq!(
+        order_size(&sizing(SizingMode::Fixed, 0), Decimal::TEN, &small).unwrap(),
+        Decimal::TEN
+    );
+}
@@ -0,0 +1,309 @@
+//! Cycles written through the journal, read back after reopening the file, and resumed.
+
+use common::cycle::{CycleAction, CycleEvent, CycleMachine, CycleState};
+use common::dto::{HedgeConfig, MarketResponse, OrderResponse, OrderState};
+use common::outcome::{CycleFills, CycleOutcome};
+use common::settings::StrategySettings;
+use common::store::{CycleStage, Store};
+use common::{Asset, BotConfig};
+use polymarket_client_sdk::clob::types::OrderStatusType;
+use rusqlite::Connection;
+use rust_decimal::Decimal;
+use std::path::{Path, PathBuf};
+
+/// start of the market the cycles trade
+const START: i64 = 1_765_000_000;
+
+/// a fresh file per test
+fn db(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("store-{}-{name}.db", std::process::id()));
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{suffix}", path.display())).ok();
+    }
+    path
+}
+
+fn cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+fn settings() -> StrategySettings {
+    let config = BotConfig::parse(
+        r#"
+        [strategy]
+        order_size = 10
+        limit_enter_price = 0.45
+        hedge_enter_price = 0.50
+        dont_allow_trade_before = 90
+        dont_allow_holding_before = 10
+        stop_loss_after = 15
+
+        [assets.btc]
+        "#,
+    )
+    .unwrap();
+    config.settings(&Asset::BTC).unwrap()
+}
+
+fn tokens() -> MarketResponse {
+    MarketResponse {
+        first_asset_id: "up".to_string(),
+        second_asset_id: "down".to_string(),
+    }
+}
+
+fn order(order_id: &str, token_id: &str) -> OrderResponse {
+    OrderResponse {
+        order_id: order_id.to_string(),
+        token_id: token_id.to_string(),
+    }
+}
+
+/// up held, its hedge goes on down against the cancelled down entry
+fn hedge() -> HedgeConfig {
+    HedgeConfig {
+        stop_loss_after: 15,
+        asset: Asset::BTC,
+        second_order_id: "entry-down".to_string(),
+        hedge_asset_id: "down".to_string(),
+        initial_asset_id: "up".to_string(),
+        hedge_size: Decimal::TEN,
+        hedge_enter_price: cents(50),
+        close_size: Decimal::TEN,
+        timestamp: START,
+    }
+}
+
+/// the latest unfinished cycle after reopening the file, resumed
+fn reopen(path: &Path) -> (CycleStage, CycleMachine) {
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let cycle = store.unfinished_cycle(Asset::BTC).unwrap().unwrap();
+    let orders = store.cycle_orders(cycle.id).unwrap();
+    let machine = CycleMachine::resume(&cycle, &orders, settings())
+        .unwrap()
+        .with_logging(false);
+    (cycle.stage, machine)
+}
+
+#[test]
+fn every_stage_resumes_after_reopening() {
+    let path = db("stages");
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let journal = store
+        .start_cycle(Asset::BTC, START, START + 900, 10, &tokens())
+        .unwrap();
+    journal.entry_placed(
+        &[order("entry-up", "up"), order("entry-down", "down")],
+        Decimal::TEN,
+        [cents(45), cents(47)],
+    );
+    drop(store);
+
+    let (stage, machine) = reopen(&path);
+    assert_eq!(stage, CycleStage::Entry);
+    let CycleState::EntryResting {
+        market,
+        first,
+        second,
+        ..
+    } = machine.state()
+    else {
+        panic!("resumed as {:?}", machine.state());
+    };
+    assert_eq!(
+        (first.as_str(), second.as_str()),
+        ("entry-up", "entry-down")
+    );
+    assert_eq!(market.prices, [cents(45), cents(47)]);
+
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let journal = store.journal(journal.id);
+    journal.hedging(&hedge());
+    drop(store);
+    let (stage, machine) = reopen(&path);
+    assert_eq!(stage, CycleStage::Hedging);
+    assert!(matches!(machine.state(), CycleState::LegMatched { .. }));
+
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let journal = store.journal(journal.id);
+    journal.hedge_placed(&order("hedge", "down"), Decimal::TEN, cents(50));
+    journal.closing("stop-loss reached");
+    drop(store);
+    // the stop-loss is time based, it fires again from the hedge
+    let (stage, machine) = reopen(&path);
+    assert_eq!(stage, CycleStage::Closing);
+    let CycleState::Hedging { order_id, size, .. } = machine.state() else {
+        panic!("resumed as {:?}", machine.state());
+    };
+    assert_eq!((order_id.as_deref(), *size), (Some("hedge"), Decimal::TEN));
+
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let outcome = CycleOutcome::StopLoss(CycleFills {
+        legs: vec![],
+        fees: Decimal::ZERO,
+        pnl: cents(-150),
+    });
+    store.journal(journal.id).finish(&outcome);
+    drop(store);
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    assert!(store.unfinished_cycle(Asset::BTC).unwrap().is_none());
+    assert_eq!(store.results(Asset::BTC).unwrap(), (0, 1));
+    assert_eq!(store.realized_pnl(Asset::BTC).unwrap(), cents(-150));
+}
+
+#[test]
+fn a_flatten_without_a_hedge_order_sells_again_after_a_restart() {
+    let path = db("flatten");
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let journal = store
+        .start_cycle(Asset::BTC, START, START + 900, 10, &tokens())
+        .unwrap();
+    journal.entry_placed(
+        &[order("entry-up", "up"), order("entry-down", "down")],
+        Decimal::TEN,
+        [cents(45), cents(45)],
+    );
+    journal.hedging(&hedge());
+    journal.closing("flatten");
+    drop(store);
+
+    let (stage, mut machine) = reopen(&path);
+    assert_eq!(stage, CycleStage::Closing);
+    assert_eq!(machine.watched(), ["entry-down"]);
+    let down = OrderState {
+        order_id: "entry-down".to_string(),
+        token_id: "down".to_string(),
+        status: OrderStatusType::Canceled,
+        original_size: Decimal::TEN,
+        size_matched: Decimal::from(3),
+        price: cents(45),
+    };
+    let actions = machine.step(CycleEvent::Orders(vec![down]), START - 60);
+    assert_eq!(
+        actions,
+        [
+            CycleAction::Close {
+                token_id: "down".to_string(),
+                size: Decimal::from(3)
+            },
+            CycleAction::Close {
+                token_id: "up".to_string(),
+                size: Decimal::TEN
+            },
+        ]
+    );
+    assert!(matches!(machine.state(), CycleState::Flattening { .. }));
+}
+
+#[test]
+fn a_resumed_cycle_keeps_the_holding_window_it_started_with() {
+    let path = db("holding");
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let journal = store
+        .start_cycle(Asset::BTC, START, START + 900, 30, &tokens())
+        .unwrap();
+    journal.entry_placed(
+        &[order("entry-up", "up"), order("entry-down", "down")],
+        Decimal::TEN,
+        [cents(45), cents(45)],
+    );
+    drop(store);
+
+    // the config was reloaded down to 10 seconds in between, 20 before the start the cycle
+    // still stops holding
+    let (_, mut machine) = reopen(&path);
+    let live = |order_id: &str, token_id: &str| OrderState {
+        order_id: order_id.to_string(),
+        token_id: token_id.to_string(),
+        status: OrderStatusType::Live,
+        original_size: Decimal::TEN,
+        size_matched: Decimal::ZERO,
+        price: cents(45),
+    };
+    let actions = machine.step(
+        CycleEvent::Orders(vec![live("entry-up", "up"), live("entry-down", "down")]),
+        START - 20,
+    );
+    assert_eq!(
+        actions,
+        [
+            CycleAction::Cancel("entry-up".to_string()),
+            CycleAction::Cancel("entry-down".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn a_resumed_cycle_keeps_the_size_it_entered_with() {
+    let path = db("size");
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let journal = store
+        .start_cycle(Asset::BTC, START, START + 900, 10, &tokens())
+        .unwrap();
+    // sized off the bankroll to 7 while the config says 10
+    let size = Decimal::from(7);
+    journal.entry_placed(
+        &[order("entry-up", "up"), order("entry-down", "down")],
+        size,
+        [cents(45), cents(45)],
+    );
+    drop(store);
+
+    let (_, mut machine) = reopen(&path);
+    let state = |order_id: &str, token_id: &str, status, size_matched| OrderState {
+        order_id: order_id.to_string(),
+        token_id: token_id.to_string(),
+        status,
+        original_size: size,
+        size_matched,
+        price: cents(45),
+    };
+    machine.step(
+        CycleEvent::Orders(vec![
+            state("entry-up", "up", OrderStatusType::Matched, size),
+            state("entry-down", "down", OrderStatusType::Live, Decimal::ZERO),
+        ]),
+        START - 60,
+    );
+    let hedge = machine.hedge().expect("up matched");
+    assert_eq!((hedge.hedge_size, hedge.close_size), (size, size));
+}
+
+#[test]
+fn migrations_bring_a_first_release_file_up_to_date() {
+    let path = db("migrations");
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE cycles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            asset TEXT NOT NULL,
+            market_timestamp INTEGER NOT NULL,
+            first_token_id TEXT NOT NULL,
+            second_token_id TEXT NOT NULL,
+            stage TEXT NOT NULL,
+            hedge TEXT,
+            hedge_order_id TEXT,
+            hedge_size TEXT,
+            result INTEGER,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        INSERT INTO cycles (asset, market_timestamp, first_token_id, second_token_id, stage, created_at, updated_at)
+        VALUES ('btc', 1765000000, 'up', 'down', 'entry', 0, 0);",
+    )
+    .unwrap();
+    drop(conn);
+
+    let store = Store::open(path.to_str().unwrap()).unwrap();
+    let cycle = store.latest_cycle(Asset::BTC).unwrap().unwrap();
+    // 15 minute markets were all there was
+    assert_eq!(cycle.market_end, START + 900);
+    assert_eq!(cycle.holding_before, None);
+    assert_eq!(cycle.stage, CycleStage::Entry);
+    store.journal(cycle.id).finish(&CycleOutcome::NoFill);
+    assert_eq!(store.realized_pnl(Asset::BTC).unwrap(), Decimal::ZERO);
+
+    // a second open finds nothing left to migrate
+    drop(store);
+    Store::open(path.to_str().unwrap()).unwrap();
+}
@@ -0,0 +1,228 @@
+//! The user channel against a local websocket stand-in of the CLOB.
+
+use common::user_channel::{ApiCreds, OrderFeed, run_user_channel};
+use futures::{SinkExt, StreamExt};
+use polymarket_client_sdk::auth::Credentials;
+use polymarket_client_sdk::clob::types::OrderStatusType;
+use rust_decimal::Decimal;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{WebSocketStream, accept_async};
+
+/// server side of one connection
+struct StandIn {
+    ws: WebSocketStream<TcpStream>,
+}
+
+impl StandIn {
+    async fn accept(listener: &TcpListener) -> StandIn {
+        let (stream, _) = listener.accept().await.unwrap();
+        StandIn {
+            ws: accept_async(stream).await.unwrap(),
+        }
+    }
+
+    async fn text(&mut self, text: &str) {
+        self.ws.send(Message::text(text)).await.unwrap();
+    }
+
+    /// the subscription the client opens with, heartbeats answered on the way
+    async fn subscribed(&mut self) -> serde_json::Value {
+        loop {
+            match self.ws.next().await.unwrap().unwrap() {
+                Message::Text(text) if text.as_str() == "PING" => self.text("PONG").await,
+                Message::Text(text) => return serde_json::from_str(text.as_str()).unwrap(),
+                _ => {}
+            }
+        }
+    }
+
+    async fn close(mut self) {
+        self.ws.close(None).await.ok();
+    }
+}
+
+fn creds() -> Credentials {
+    ApiCreds {
+        key: "00000000-0000-0000-0000-000000000001".into(),
+        secret: "secret".into(),
+        passphrase: "passphrase".into(),
+    }
+    .credentials()
+    .unwrap()
+}
+
+async fn start(feed: &OrderFeed) -> (TcpListener, StandIn) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/ws/user", listener.local_addr().unwrap());
+    tokio::spawn(run_user_channel(url, creds(), feed.clone()));
+    let mut server = StandIn::accept(&listener).await;
+    server.subscribed().await;
+    eventually("the connection", || feed.connected()).await;
+    (listener, server)
+}
+
+fn order_event(id: &str, size_matched: &str, kind: &str) -> String {
+    serde_json::json!({
+        "event_type": "order",
+        "id": id,
+        "market": "0xmarket",
+        "asset_id": "token",
+        "side": "BUY",
+        "price": "0.45",
+        "original_size": "10",
+        "size_matched": size_matched,
+        "type": kind,
+    })
+    .to_string()
+}
+
+/// `check` polled until it holds, the client runs in its own task
+async fn eventually(what: &str, check: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if check() {
+            return;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+#[tokio::test]
+async fn subscribes_with_the_api_credentials() {
+    let feed = OrderFeed::default();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/ws/user", listener.local_addr().unwrap());
+    tokio::spawn(run_user_channel(url, creds(), feed.clone()));
+
+    let subscription = StandIn::accept(&listener).await.subscribed().await;
+    assert_eq!(subscription["type"], "user");
+    assert_eq!(
+        subscription["auth"]["apiKey"],
+        "00000000-0000-0000-0000-000000000001"
+    );
+    assert_eq!(subscription["auth"]["secret"], "secret");
+    assert_eq!(subscription["auth"]["passphrase"], "passphrase");
+    eventually("the connection", || feed.connected()).await;
+}
+
+#[tokio::test]
+async fn order_events_reach_the_cycle_that_owns_the_order() {
+    let feed = OrderFeed::default();
+    let first = feed.subscribe();
+    let second = feed.subscribe();
+    first.track("0x1");
+    second.track("0x2");
+    let (_listener, mut server) = start(&feed).await;
+
+    server.text(&order_event("0x2", "4", "UPDATE")).await;
+    second.changed(Duration::from_secs(2)).await;
+
+    let order = second.latest("0x2").expect("routed to the owner");
+    assert_eq!(order.status, OrderStatusType::Live);
+    assert_eq!(order.size_matched, Decimal::from(4));
+    assert_eq!(order.token_id, "token");
+    assert!(first.latest("0x2").is_none());
+    assert!(
+        first.latest("0x1").is_none(),
+        "nothing seen of it yet, so it is polled"
+    );
+
+    // several events in one message
+    let batch = format!(
+        "[{},{}]",
+        order_event("0x1", "0", "CANCELLATION"),
+        order_event("0x2", "10", "UPDATE")
+    );
+    server.text(&batch).await;
+    eventually("both updates", || {
+        first
+            .latest("0x1")
+            .is_some_and(|o| o.status == OrderStatusType::Canceled)
+            && second
+                .latest("0x2")
+                .is_some_and(|o| o.status == OrderStatusType::Matched)
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn an_event_before_tracking_is_kept_for_the_owner() {
+    let feed = OrderFeed::default();
+    let (_listener, mut server) = start(&feed).await;
+    // events are handled in order, once the probe's arrived the early one is in
+    let probe = feed.subscribe();
+    probe.track("0xprobe");
+
+    server.text(&order_event("0x3", "10", "UPDATE")).await;
+    server.text(&order_event("0xprobe", "0", "PLACEMENT")).await;
+    eventually("the probe", || probe.latest("0xprobe").is_some()).await;
+
+    let updates = feed.subscribe();
+    updates.track("0x3");
+    let order = updates.latest("0x3").expect("kept until tracked");
+    assert_eq!(order.status, OrderStatusType::Matched);
+}
+
+#[tokio::test]
+async fn a_trade_asks_for_a_poll() {
+    let feed = OrderFeed::default();
+    let updates = feed.subscribe();
+    updates.track("0x4");
+    let (_listener, mut server) = start(&feed).await;
+
+    server.text(&order_event("0x4", "0", "PLACEMENT")).await;
+    eventually("the placement", || updates.latest("0x4").is_some()).await;
+
+    let trade = serde_json::json!({
+        "event_type": "trade",
+        "id": "trade",
+        "market": "0xmarket",
+        "asset_id": "token",
+        "side": "BUY",
+        "size": "3",
+        "price": "0.45",
+        "status": "MATCHED",
+        "taker_order_id": "0xother",
+        "maker_orders": [{
+            "asset_id": "token",
+            "matched_amount": "3",
+            "order_id": "0x4",
+            "outcome": "Up",
+            "owner": "owner",
+            "price": "0.45",
+        }],
+    });
+    server.text(&trade.to_string()).await;
+    eventually("the trade", || updates.latest("0x4").is_none()).await;
+}
+
+#[tokio::test]
+async fn orders_are_polled_while_the_socket_is_down() {
+    let feed = OrderFeed::default();
+    let updates = feed.subscribe();
+    updates.track("0x5");
+    let (listener, mut server) = start(&feed).await;
+
+    server.text(&order_event("0x5", "2", "UPDATE")).await;
+    eventually("the update", || updates.latest("0x5").is_some()).await;
+
+    server.close().await;
+    eventually("the disconnect", || !feed.connected()).await;
+    assert!(updates.latest("0x5").is_none());
+
+    // events may have been missed in between, so the order is polled once after a reconnect
+    let mut server = StandIn::accept(&listener).await;
+    server.subscribed().await;
+    eventually("the reconnect", || feed.connected()).await;
+    assert!(updates.latest("0x5").is_none());
+    server.text(&order_event("0x5", "10", "UPDATE")).await;
+    eventually("the update after the reconnect", || {
+        updates
+            .latest("0x5")
+            .is_some_and(|o| o.status == OrderStatusType::Matched)
+    })
+    .await;
+}
@@ -0,0 +1,22 @@
+use common::{Asset, run};
+use std::env;
+
+/// `runner btc eth` trades only those assets, plain `runner` trades every enabled asset in the config
+fn get_assets() -> anyhow::Result<Option<Vec<Asset>>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    let assets = args
+        .iter()
+        .map(|s| s.parse::<Asset>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("arguments must be asset keys of the config: {e}"))?;
+    Ok(Some(assets))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    run(get_assets()?).await
+}